@@ -0,0 +1,95 @@
+use super::{Animation, Frame};
+use crate::{
+    chunks::{BlendOp, DisposeOp},
+    pixel::Pixel,
+};
+
+impl<'a> Animation<'a> {
+    /// Composites the frames onto the canvas, returning the full image shown for each frame.
+    /// Frames that don't have width * height pixels aren't drawn.
+    pub fn canvases(&self) -> Canvases<'_> {
+        let size = self.header.width as usize * self.header.height as usize;
+        Canvases {
+            frames: self.frames.iter(),
+            width: self.header.width as usize,
+            canvas: vec![Pixel::new(0, 0, 0, 0); size],
+            dispose: None,
+            first: true,
+        }
+    }
+}
+
+/// Iterator over the composited images of an [`Animation`], see [`Animation::canvases`].
+#[derive(Debug)]
+pub struct Canvases<'a> {
+    frames: std::slice::Iter<'a, Frame>,
+    width: usize,
+    canvas: Vec<Pixel>,
+    /// The previous frame, and what its region held before it was drawn if it's disposed by
+    /// reverting to that.
+    dispose: Option<(&'a Frame, Option<Vec<Vec<Pixel>>>)>,
+    first: bool,
+}
+impl<'a> Canvases<'a> {
+    /// Applies the previous frame's dispose op to the canvas.
+    fn dispose_previous(&mut self) {
+        let Some((frame, saved)) = self.dispose.take() else {
+            return;
+        };
+        match (frame.dispose_op, saved) {
+            (DisposeOp::None, _) => {}
+            (DisposeOp::Previous, Some(saved)) => {
+                for (row, saved_row) in saved.iter().enumerate() {
+                    self.region_row(frame, row).copy_from_slice(saved_row);
+                }
+            }
+            // The first frame can't be reverted, so it's cleared instead.
+            (DisposeOp::Background | DisposeOp::Previous, _) => {
+                for row in 0..frame.height as usize {
+                    self.region_row(frame, row).fill(Pixel::new(0, 0, 0, 0));
+                }
+            }
+        }
+    }
+
+    /// The canvas pixels under one row of `frame`, leaving out any that lie outside the canvas.
+    fn region_row(&mut self, frame: &Frame, row: usize) -> &mut [Pixel] {
+        let left = (frame.x_offset as usize).min(self.width);
+        let right = (left + frame.width as usize).min(self.width);
+        let start = (frame.y_offset as usize + row) * self.width;
+        self.canvas
+            .get_mut(start + left..start + right)
+            .unwrap_or_default()
+    }
+}
+impl<'a> Iterator for Canvases<'a> {
+    type Item = Vec<Pixel>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let frame = self.frames.next()?;
+        self.dispose_previous();
+        let saved = (frame.dispose_op == DisposeOp::Previous && !self.first).then(|| {
+            (0..frame.height as usize)
+                .map(|row| self.region_row(frame, row).to_vec())
+                .collect()
+        });
+        self.first = false;
+        let width = frame.width as usize;
+        let rows = if frame.pixels.len() == width * frame.height as usize {
+            frame.pixels.chunks(width.max(1))
+        } else {
+            [].chunks(1)
+        };
+        for (row, pixels) in rows.enumerate() {
+            let blend_op = frame.blend_op;
+            for (dst, src) in self.region_row(frame, row).iter_mut().zip(pixels) {
+                *dst = match blend_op {
+                    BlendOp::Source => *src,
+                    BlendOp::Over => src.over(*dst),
+                };
+            }
+        }
+        self.dispose = Some((frame, saved));
+        Some(self.canvas.clone())
+    }
+}
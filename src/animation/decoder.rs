@@ -0,0 +1,213 @@
+use super::{Animation, Frame};
+use crate::{
+    chunks::{fcTLChunk, iter_chunks, BlendOp, Chunk, DisposeOp, IHDRChunk},
+    error::DecodeError,
+    gamma::correct_gamma,
+    limits::ImageTally,
+    options::DecoderOptions,
+    png::{convert_to_srgb, decode_image_data, parse_signature, resolve_background},
+    significant_bits::scale_significant_bits,
+};
+
+impl<'a> Animation<'a> {
+    /// Decodes an animated PNG, returning an error if a problem was found with the data or the
+    /// animation chunks. The default [`DecodeLimits`](crate::DecodeLimits) apply.
+    ///
+    /// Images without an acTL chunk aren't animated, and are decoded as a single frame that
+    /// covers the canvas.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::decode_with(bytes, &DecoderOptions::new())
+    }
+
+    /// Decodes an animated PNG like [`decode`](Self::decode), with control over limits and
+    /// color correction through `options`. The limits on pixels and decompressed bytes count every
+    /// frame and the default image together.
+    pub fn decode_with(bytes: &'a [u8], options: &DecoderOptions) -> Result<Self, DecodeError> {
+        let limits = &options.limits;
+        let (rest, _) = parse_signature(bytes).or(Err(DecodeError::InvalidSignature))?;
+        let mut chunks = iter_chunks(rest, *limits);
+        let header = match chunks.next() {
+            Some(Ok(Chunk::IHDR(ihdr))) => ihdr,
+            Some(Err(e)) => return Err(e),
+            _ => return Err(DecodeError::MissingHeader),
+        };
+        limits.check_header(&header)?;
+        let mut palette = None;
+        let mut transparency = None;
+        let mut animation_control = None;
+        let mut next_sequence_number = 0;
+        // Every frame's control chunk and compressed data. If the default image is the first
+        // frame, its data is in `data` instead.
+        let mut frames: Vec<(fcTLChunk, Vec<u8>)> = vec![];
+        let mut default_is_frame = false;
+        let mut data = vec![];
+        let mut found_image_data = false;
+        let mut extra_chunks = vec![];
+        let mut check_sequence_number = |actual| {
+            if actual != next_sequence_number {
+                return Err(DecodeError::InvalidSequenceNumber {
+                    expected: next_sequence_number,
+                    actual,
+                });
+            }
+            next_sequence_number += 1;
+            Ok(())
+        };
+        for chunk in chunks {
+            match chunk? {
+                Chunk::IHDR(_) => return Err(DecodeError::MissingHeader),
+                Chunk::PLTE(plte) => palette = Some(plte),
+                Chunk::tRNS(trns) => transparency = Some(trns),
+                Chunk::IDAT(idat) => {
+                    found_image_data = true;
+                    data.extend(idat.data)
+                }
+                Chunk::acTL(actl) => {
+                    if found_image_data || animation_control.is_some() {
+                        return Err(invalid("acTL chunk isn't the only one before IDAT"));
+                    }
+                    animation_control = Some(actl);
+                }
+                // Without an acTL chunk the image isn't animated, and the other animation
+                // chunks are ignored.
+                Chunk::fcTL(_) | Chunk::fdAT(_) if animation_control.is_none() => {}
+                Chunk::fcTL(fctl) => {
+                    check_sequence_number(fctl.sequence_number)?;
+                    let right = fctl.x_offset.checked_add(fctl.width);
+                    let bottom = fctl.y_offset.checked_add(fctl.height);
+                    if !matches!(right, Some(right) if right <= header.width)
+                        || !matches!(bottom, Some(bottom) if bottom <= header.height)
+                    {
+                        return Err(invalid("frame lies outside the canvas"));
+                    }
+                    if !found_image_data {
+                        if (fctl.width, fctl.height, fctl.x_offset, fctl.y_offset)
+                            != (header.width, header.height, 0, 0)
+                        {
+                            return Err(invalid("default image frame doesn't fill the canvas"));
+                        }
+                        default_is_frame = true;
+                    }
+                    frames.push((fctl, vec![]));
+                }
+                Chunk::fdAT(fdat) => {
+                    check_sequence_number(fdat.sequence_number)?;
+                    let frame_count = frames.len();
+                    match frames.last_mut() {
+                        Some(_) if default_is_frame && frame_count == 1 => {
+                            return Err(invalid("default image frame has fdAT chunks"))
+                        }
+                        Some((_, frame_data)) => frame_data.extend(fdat.data),
+                        None => return Err(invalid("fdAT chunk comes before any fcTL chunk")),
+                    }
+                }
+                Chunk::IEND => break,
+                c => extra_chunks.push(c),
+            }
+        }
+        if !found_image_data {
+            return Err(DecodeError::MissingImageData);
+        }
+        resolve_background(&mut extra_chunks, palette.as_ref());
+        let decode_default_image =
+            |data: &[u8]| decode_image_data(data, &header, palette.as_ref(), transparency.as_ref());
+        let frame_header = |fctl: &fcTLChunk| {
+            IHDRChunk::new(
+                fctl.width,
+                fctl.height,
+                header.bit_depth,
+                header.color_type,
+                header.interlace_method,
+            )
+        };
+        let decode_frame = |fctl: &fcTLChunk, data: &[u8]| {
+            let frame_header = frame_header(fctl);
+            decode_image_data(data, &frame_header, palette.as_ref(), transparency.as_ref())
+        };
+        let (num_plays, mut frames, mut default_image) = match animation_control {
+            Some(actl) => {
+                if frames.len() != actl.num_frames() as usize {
+                    return Err(invalid("number of frames doesn't match the acTL chunk"));
+                }
+                // Checked before anything is decompressed, so a file with many large frames
+                // fails straight away.
+                let mut tally = ImageTally::default();
+                if !default_is_frame {
+                    limits.check_image(&mut tally, &header)?;
+                }
+                for (fctl, _) in &frames {
+                    limits.check_image(&mut tally, &frame_header(fctl))?;
+                }
+                if default_is_frame {
+                    frames[0].1 = std::mem::take(&mut data);
+                }
+                let frames = frames
+                    .iter()
+                    .map(|(fctl, data)| {
+                        Ok(Frame {
+                            width: fctl.width,
+                            height: fctl.height,
+                            x_offset: fctl.x_offset,
+                            y_offset: fctl.y_offset,
+                            delay_num: fctl.delay_num,
+                            delay_den: fctl.delay_den,
+                            dispose_op: fctl.dispose_op,
+                            blend_op: fctl.blend_op,
+                            pixels: decode_frame(fctl, data)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>, DecodeError>>()?;
+                let default_image = (!default_is_frame)
+                    .then(|| decode_default_image(&data))
+                    .transpose()?;
+                (actl.num_plays(), frames, default_image)
+            }
+            None => {
+                let frame = Frame {
+                    width: header.width,
+                    height: header.height,
+                    x_offset: 0,
+                    y_offset: 0,
+                    delay_num: 0,
+                    delay_den: 0,
+                    dispose_op: DisposeOp::None,
+                    blend_op: BlendOp::Source,
+                    pixels: decode_default_image(&data)?,
+                };
+                (0, vec![frame], None)
+            }
+        };
+        if options.convert_to_srgb {
+            let images = frames.iter_mut().map(|frame| frame.pixels.as_mut_slice());
+            convert_to_srgb(
+                images.chain(default_image.as_deref_mut()),
+                &mut extra_chunks,
+            )?;
+        }
+        if let Some(display) = options.gamma_correction {
+            for frame in frames.iter_mut() {
+                correct_gamma(&mut frame.pixels, &extra_chunks, display);
+            }
+            if let Some(pixels) = default_image.as_mut() {
+                correct_gamma(pixels, &extra_chunks, display);
+            }
+        }
+        if let Some(scaling) = options.sample_scaling {
+            let images = frames.iter_mut().map(|frame| frame.pixels.as_mut_slice());
+            for pixels in images.chain(default_image.as_deref_mut()) {
+                scale_significant_bits(pixels, &extra_chunks, &header, scaling);
+            }
+        }
+        Ok(Self {
+            header,
+            num_plays,
+            frames,
+            default_image,
+            extra_chunks,
+        })
+    }
+}
+
+fn invalid(reason: &'static str) -> DecodeError {
+    DecodeError::InvalidAnimation { reason }
+}
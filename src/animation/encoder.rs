@@ -0,0 +1,344 @@
+use super::{Animation, Frame};
+use crate::{
+    chunks::trns::tRNSChunk,
+    chunks::{
+        acTLChunk, fcTLChunk, fdATChunk, iend::IENDChunk, BlendOp, Chunk, ColorType, DisposeOp,
+        IDATChunk, IHDRChunk, Interlacing, ParseableChunk,
+    },
+    depth::{dither_image, Dither},
+    encoding::{compress_zlib, optimal_format, serialize_frame},
+    error::EncodeError,
+    filters::{filter_scanlines, FilterStrategy},
+    options::EncoderOptions,
+    pixel::Pixel,
+    png::{background_chunk, SIGNATURE},
+    significant_bits::significant_bits_chunk,
+};
+
+impl<'a> Animation<'a> {
+    /// Creates an animation with an empty canvas, stored as 8 bit RGBA and played forever. Add
+    /// frames to [`frames`](Self::frames) before encoding it.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            header: IHDRChunk::new(
+                width,
+                height,
+                8,
+                ColorType::TruecolorWithAlpha,
+                Interlacing::None,
+            ),
+            num_plays: 0,
+            frames: vec![],
+            default_image: None,
+            extra_chunks: vec![],
+        }
+    }
+
+    /// Encodes the animation as an animated PNG, with the color type, bit depth and interlacing
+    /// from its header.
+    ///
+    /// The first frame is the image shown by decoders that don't support animation, unless
+    /// [`default_image`](Self::default_image) is set, so it has to cover the whole canvas.
+    pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
+        self.encode_with(&EncoderOptions::new())
+    }
+
+    /// Encodes the animation like [`encode`](Self::encode), with control over compression,
+    /// filtering and chunking through `options`. The color type and bit depth picked by
+    /// [`EncoderOptions::optimize`] are shared by every frame.
+    pub fn encode_with(&self, options: &EncoderOptions) -> Result<Vec<u8>, EncodeError> {
+        let images = self.images()?;
+        let all_pixels = images
+            .iter()
+            .flat_map(|(_, _, pixels)| pixels.iter().copied())
+            .collect::<Vec<_>>();
+        let (color_type, bit_depth) = if options.optimize {
+            optimal_format(&all_pixels)
+        } else {
+            (self.header.color_type, self.header.bit_depth)
+        };
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(EncodeError::InvalidBitDepth {
+                color_type,
+                bit_depth,
+            });
+        }
+        let interlace_method = options.interlacing.unwrap_or(self.header.interlace_method);
+        let header = IHDRChunk::new(
+            self.header.width,
+            self.header.height,
+            bit_depth,
+            color_type,
+            interlace_method,
+        );
+        let images: Vec<_> = images
+            .into_iter()
+            .map(|(width, height, pixels)| {
+                let frame_header =
+                    IHDRChunk::new(width, height, bit_depth, color_type, interlace_method);
+                let pixels = dither_image(pixels, width, &frame_header, options.dither);
+                (width, height, pixels)
+            })
+            .collect();
+        let all_pixels = match options.dither {
+            Dither::None => all_pixels,
+            _ => images
+                .iter()
+                .flat_map(|(_, _, pixels)| pixels.iter().copied())
+                .collect(),
+        };
+        // Every image is serialized up front, as the palette and transparency are needed before
+        // any image data is written.
+        let mut palette = None;
+        let mut transparency = None;
+        let mut compressed = vec![];
+        for (width, height, pixels) in images {
+            let frame_header =
+                IHDRChunk::new(width, height, bit_depth, color_type, interlace_method);
+            let mut image = serialize_frame(&pixels, &frame_header, &all_pixels)?;
+            let filter_strategy = options
+                .filter_strategy
+                .unwrap_or_else(|| FilterStrategy::recommended(&frame_header));
+            filter_scanlines(&mut image.data, &frame_header, filter_strategy);
+            compressed.push(compress_zlib(
+                &image.data,
+                options.compression_level,
+                options.compression_strategy,
+            ));
+            palette = image.palette;
+            transparency = image.transparency;
+        }
+
+        let mut png_data = SIGNATURE.to_vec();
+        png_data.extend(header.to_bytes());
+        png_data.extend(acTLChunk::new(self.frames.len() as u32, self.num_plays).to_bytes());
+        let mut significant_bits = significant_bits_chunk(
+            options.significant_bits,
+            &self.extra_chunks,
+            &self.header,
+            &header,
+        );
+        let mut time = options.time_stamp.map(|time_stamp| time_stamp.chunk());
+        for chunk in self.extra_chunks.iter() {
+            match chunk {
+                Chunk::sBIT(_) => {
+                    if let Some(sbit) = significant_bits.take() {
+                        png_data.extend(sbit.to_bytes());
+                    }
+                }
+                Chunk::tIME(_) if options.time_stamp.is_some() => {
+                    if let Some(time) = time.take() {
+                        png_data.extend(time.to_bytes());
+                    }
+                }
+                Chunk::acTL(_) | Chunk::fcTL(_) | Chunk::fdAT(_) | Chunk::bKGD(_) => {}
+                Chunk::Unknown(chunk) if chunk.is_malformed() => {}
+                chunk => png_data.extend(chunk.to_bytes()),
+            }
+        }
+        if let Some(time) = time {
+            png_data.extend(time.to_bytes());
+        }
+        if let Some(sbit) = significant_bits {
+            png_data.extend(sbit.to_bytes());
+        }
+        let background = self
+            .background()
+            .and_then(|color| background_chunk(color, &header, palette.as_mut()));
+        if let Some(plte) = palette {
+            png_data.extend(plte.to_bytes());
+        }
+        if let Some(trns) = transparency {
+            png_data.extend(tRNSChunk::new(&trns).to_bytes());
+        }
+        if let Some(bkgd) = background {
+            png_data.extend(bkgd.to_bytes());
+        }
+        let mut compressed = compressed.iter();
+        if self.default_image.is_some() {
+            let data = compressed.next().expect("the default image was serialized");
+            for data in data.chunks(options.max_idat_size) {
+                png_data.extend(IDATChunk::new(data).to_bytes());
+            }
+        }
+        let mut sequence_number = 0;
+        for (index, (frame, data)) in self.frames.iter().zip(compressed).enumerate() {
+            png_data.extend(frame_control(frame, sequence_number).to_bytes());
+            sequence_number += 1;
+            for data in data.chunks(options.max_idat_size) {
+                if index == 0 && self.default_image.is_none() {
+                    png_data.extend(IDATChunk::new(data).to_bytes());
+                } else {
+                    png_data.extend(fdATChunk::new(sequence_number, data).to_bytes());
+                    sequence_number += 1;
+                }
+            }
+        }
+        png_data.extend(IENDChunk.to_bytes());
+        Ok(png_data)
+    }
+
+    /// Shrinks every frame after the first to the smallest rectangle that covers the pixels it
+    /// changes, which usually makes the encoded animation much smaller.
+    ///
+    /// The animation looks the same afterwards, but frames are replaced by the parts of the
+    /// composited [`canvases`](Self::canvases) that differ from the previous canvas, so every
+    /// frame is drawn with [`BlendOp::Source`] and disposed with [`DisposeOp::None`]. The first
+    /// frame covers the whole canvas.
+    ///
+    /// Fails like [`encode`](Self::encode) if a frame doesn't fit on the canvas or has too few
+    /// pixels, and also if a frame has too many, since [`canvases`](Self::canvases) wouldn't
+    /// draw it. The frames are left as they were.
+    pub fn crop_frames(&mut self) -> Result<(), EncodeError> {
+        self.images()?;
+        if self
+            .frames
+            .iter()
+            .any(|frame| frame.pixels.len() != frame.width as usize * frame.height as usize)
+        {
+            return Err(invalid("frame has more pixels than its size"));
+        }
+        let width = self.header.width as usize;
+        let mut previous: Option<Vec<Pixel>> = None;
+        let mut frames = Vec::with_capacity(self.frames.len());
+        for (frame, canvas) in self.frames.iter().zip(self.canvases()) {
+            let (x, y, w, h) = match &previous {
+                None => (0, 0, self.header.width, self.header.height),
+                Some(previous) => changed_region(previous, &canvas, width),
+            };
+            let pixels = canvas
+                .chunks(width)
+                .skip(y as usize)
+                .take(h as usize)
+                .flat_map(|row| &row[x as usize..(x + w) as usize])
+                .copied()
+                .collect();
+            frames.push(Frame {
+                width: w,
+                height: h,
+                x_offset: x,
+                y_offset: y,
+                delay_num: frame.delay_num,
+                delay_den: frame.delay_den,
+                dispose_op: DisposeOp::None,
+                blend_op: BlendOp::Source,
+                pixels,
+            });
+            previous = Some(canvas);
+        }
+        self.frames = frames;
+        Ok(())
+    }
+
+    /// The width, height and pixels of every image, starting with the default image, after
+    /// checking every frame fits on the canvas and has enough pixels.
+    fn images(&self) -> Result<Vec<Image<'_>>, EncodeError> {
+        if self.frames.is_empty() {
+            return Err(invalid("animation has no frames"));
+        }
+        let mut images = vec![];
+        if let Some(pixels) = &self.default_image {
+            let canvas_size = self.header.width as usize * self.header.height as usize;
+            let pixels = sized_pixels(pixels, canvas_size)?;
+            images.push((self.header.width, self.header.height, pixels));
+        } else if (self.frames[0].width, self.frames[0].height)
+            != (self.header.width, self.header.height)
+            || (self.frames[0].x_offset, self.frames[0].y_offset) != (0, 0)
+        {
+            return Err(invalid("first frame doesn't fill the canvas"));
+        }
+        for frame in &self.frames {
+            let right = frame.x_offset.checked_add(frame.width);
+            let bottom = frame.y_offset.checked_add(frame.height);
+            if frame.width == 0
+                || frame.height == 0
+                || !matches!(right, Some(right) if right <= self.header.width)
+                || !matches!(bottom, Some(bottom) if bottom <= self.header.height)
+            {
+                return Err(invalid("frame lies outside the canvas"));
+            }
+            let size = frame.width as usize * frame.height as usize;
+            images.push((
+                frame.width,
+                frame.height,
+                sized_pixels(&frame.pixels, size)?,
+            ));
+        }
+        Ok(images)
+    }
+}
+
+/// An image's width, height and pixels.
+type Image<'a> = (u32, u32, &'a [Pixel]);
+
+impl Frame {
+    /// Creates a frame at the top left of the canvas with no delay, replacing the canvas's
+    /// pixels and leaving them in place afterwards.
+    pub fn new(width: u32, height: u32, pixels: Vec<Pixel>) -> Self {
+        Self {
+            width,
+            height,
+            x_offset: 0,
+            y_offset: 0,
+            delay_num: 0,
+            delay_den: 0,
+            dispose_op: DisposeOp::None,
+            blend_op: BlendOp::Source,
+            pixels,
+        }
+    }
+}
+
+/// The x and y offset, width and height of the smallest rectangle covering every pixel that
+/// differs between `previous` and `canvas`. If none do, a single pixel is used, since frames
+/// can't be empty.
+fn changed_region(previous: &[Pixel], canvas: &[Pixel], width: usize) -> (u32, u32, u32, u32) {
+    let mut region: Option<(usize, usize, usize, usize)> = None;
+    for (index, _) in previous
+        .iter()
+        .zip(canvas)
+        .enumerate()
+        .filter(|(_, (a, b))| a != b)
+    {
+        let (x, y) = (index % width, index / width);
+        region = Some(match region {
+            None => (x, y, x, y),
+            Some((left, top, right, bottom)) => {
+                (left.min(x), top.min(y), right.max(x), bottom.max(y))
+            }
+        });
+    }
+    let (left, top, right, bottom) = region.unwrap_or((0, 0, 0, 0));
+    (
+        left as u32,
+        top as u32,
+        (right - left + 1) as u32,
+        (bottom - top + 1) as u32,
+    )
+}
+
+fn frame_control(frame: &Frame, sequence_number: u32) -> fcTLChunk {
+    fcTLChunk::new(
+        sequence_number,
+        frame.width,
+        frame.height,
+        frame.x_offset,
+        frame.y_offset,
+        frame.delay_num,
+        frame.delay_den,
+        frame.dispose_op,
+        frame.blend_op,
+    )
+}
+
+/// The first `size` pixels, erroring if there aren't enough of them.
+fn sized_pixels(pixels: &[Pixel], size: usize) -> Result<&[Pixel], EncodeError> {
+    pixels.get(..size).ok_or(EncodeError::NotEnoughPixels {
+        expected: size,
+        actual: pixels.len(),
+    })
+}
+
+fn invalid(reason: &'static str) -> EncodeError {
+    EncodeError::InvalidAnimation { reason }
+}
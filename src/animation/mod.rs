@@ -0,0 +1,103 @@
+//! Animated PNG (APNG) images, made of a series of frames drawn onto a canvas.
+mod canvas;
+mod decoder;
+mod encoder;
+
+use std::time::Duration;
+
+pub use canvas::Canvases;
+
+use crate::{
+    chunks::{BlendOp, Chunk, DisposeOp, IHDRChunk},
+    pixel::Pixel,
+    png::background_color,
+};
+
+/// An animated PNG, decoded into its frames.
+///
+/// Frames only cover part of the canvas and are drawn on top of the frames before them, use
+/// [`canvases`](Self::canvases) to get each fully composited image.
+///
+/// ```
+/// use simple_png::{
+///     animation::{Animation, Frame},
+///     chunks::{BlendOp, DisposeOp},
+///     Pixel,
+/// };
+///
+/// let red = Pixel::new(u16::MAX, 0, 0, u16::MAX);
+/// let blue = Pixel::new(0, 0, u16::MAX, u16::MAX);
+/// // A red 2x1 canvas, then a blue pixel on the right that's cleared once it has been shown.
+/// let mut animation = Animation::new(2, 1);
+/// animation.frames.push(Frame::new(2, 1, vec![red; 2]));
+/// let mut frame = Frame::new(1, 1, vec![blue]);
+/// frame.x_offset = 1;
+/// frame.dispose_op = DisposeOp::Background;
+/// frame.blend_op = BlendOp::Over;
+/// animation.frames.push(frame);
+/// let data = animation.encode()?;
+///
+/// let animation = Animation::decode(&data)?;
+/// assert_eq!(animation.frames.len(), 2);
+/// assert_eq!(animation.frames[1].dispose_op, DisposeOp::Background);
+/// assert_eq!(animation.frames[1].blend_op, BlendOp::Over);
+/// let canvases: Vec<_> = animation.canvases().collect();
+/// assert_eq!(canvases, [vec![red, red], vec![red, blue]]);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Animation<'a> {
+    /// The IHDR metadata, whose dimensions are the size of the canvas.
+    pub header: IHDRChunk,
+    /// How many times the animation is played, where 0 means forever.
+    pub num_plays: u32,
+    /// The frames, in the order they're shown.
+    pub frames: Vec<Frame>,
+    /// The image shown by decoders that don't support animation, if it isn't the first frame.
+    pub default_image: Option<Vec<Pixel>>,
+    /// Any PNG chunks that the library doesn't interpret, other than the animation chunks.
+    pub extra_chunks: Vec<Chunk<'a>>,
+}
+impl<'a> Animation<'a> {
+    /// The background color from the animation's bKGD chunk, if it has one.
+    pub fn background(&self) -> Option<Pixel> {
+        self.extra_chunks.iter().find_map(|chunk| match chunk {
+            Chunk::bKGD(bkgd) => background_color(bkgd.background(), &self.header),
+            _ => None,
+        })
+    }
+}
+
+/// A single frame of an [`Animation`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// Width of the frame in pixels.
+    pub width: u32,
+    /// Height of the frame in pixels.
+    pub height: u32,
+    /// Position of the frame's left edge on the canvas.
+    pub x_offset: u32,
+    /// Position of the frame's top edge on the canvas.
+    pub y_offset: u32,
+    /// The numerator of how many seconds the frame is shown for.
+    pub delay_num: u16,
+    /// The denominator of how many seconds the frame is shown for, where 0 counts as 100.
+    pub delay_den: u16,
+    /// What happens to the frame's region of the canvas once it has been shown.
+    pub dispose_op: DisposeOp,
+    /// How the frame is drawn onto the canvas.
+    pub blend_op: BlendOp,
+    /// The frame's pixels. Top left is 0 and bottom right is width * height - 1.
+    pub pixels: Vec<Pixel>,
+}
+impl Frame {
+    /// How long the frame is shown for.
+    pub fn delay(&self) -> Duration {
+        let den = if self.delay_den == 0 {
+            100
+        } else {
+            self.delay_den
+        };
+        Duration::from_secs_f64(self.delay_num as f64 / den as f64)
+    }
+}
@@ -12,7 +12,7 @@ fn main() -> anyhow::Result<()> {
         .init();
     let file_name = args.last().unwrap();
     let input = std::fs::read(file_name)?;
-    let output = PNG::decode(&input)?.encode();
+    let output = PNG::decode(&input)?.encode()?;
     std::fs::write("output.png", output)?;
     Ok(())
 }
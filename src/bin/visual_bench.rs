@@ -47,7 +47,7 @@ fn main() -> anyhow::Result<()> {
                     "Failed to decode {}.",
                     image_path.to_str().unwrap()
                 ))?
-                .encode(),
+                .encode()?,
         )?;
         processed_images.push(test_name.to_owned());
     }
@@ -0,0 +1,59 @@
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::DecodeError;
+use nom::{combinator::all_consuming, number::complete::be_u32, sequence::tuple};
+
+/// Marks the file as an animated PNG, with the number of frames and how often they're played.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct acTLChunk {
+    num_frames: u32,
+    num_plays: u32,
+}
+impl acTLChunk {
+    /// Creates an acTL chunk. A `num_plays` of 0 loops the animation forever.
+    pub fn new(num_frames: u32, num_plays: u32) -> Self {
+        Self {
+            num_frames,
+            num_plays,
+        }
+    }
+
+    /// The number of frames in the animation.
+    pub fn num_frames(&self) -> u32 {
+        self.num_frames
+    }
+
+    /// How many times the animation is played, where 0 means forever.
+    pub fn num_plays(&self) -> u32 {
+        self.num_plays
+    }
+}
+impl<'a> ParseableChunk<'a> for acTLChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"acTL";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (_, (num_frames, num_plays)) =
+            all_consuming(tuple((be_u32, be_u32)))(chunk_data).map_err(malformed(Self::HEADER))?;
+        if num_frames == 0 {
+            return Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            });
+        }
+        Ok(Self {
+            num_frames,
+            num_plays,
+        })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = vec![0, 0, 0, 8];
+        bytes.extend(Self::HEADER);
+        bytes.extend(self.num_frames.to_be_bytes());
+        bytes.extend(self.num_plays.to_be_bytes());
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
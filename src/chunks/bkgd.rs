@@ -0,0 +1,71 @@
+use super::{crc::calculate_crc, ParseableChunk};
+use crate::error::DecodeError;
+
+/// A default background color to show the image against.
+///
+/// The layout of the color depends on the image's color type, and samples are in the image's
+/// bit depth.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct bKGDChunk {
+    background: Background,
+}
+impl bKGDChunk {
+    /// Creates a bKGD chunk with the given background color.
+    pub fn new(background: Background) -> Self {
+        Self { background }
+    }
+
+    /// The background color.
+    pub fn background(&self) -> Background {
+        self.background
+    }
+}
+impl<'a> ParseableChunk<'a> for bKGDChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"bKGD";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let sample = |i: usize| u16::from_be_bytes([chunk_data[i], chunk_data[i + 1]]);
+        let background = match chunk_data.len() {
+            1 => Background::PaletteIndex(chunk_data[0]),
+            2 => Background::Grey(sample(0)),
+            6 => Background::Rgb(sample(0), sample(2), sample(4)),
+            _ => {
+                return Err(DecodeError::MalformedChunk {
+                    chunk_type: *Self::HEADER,
+                })
+            }
+        };
+        Ok(Self { background })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let data = match self.background {
+            Background::Grey(grey) => grey.to_be_bytes().to_vec(),
+            Background::Rgb(red, green, blue) => [red, green, blue]
+                .into_iter()
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+            Background::PaletteIndex(index) => vec![index],
+        };
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        bytes.extend(data);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
+
+/// The background color of a [`bKGDChunk`], in the layout for the image's color type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Background {
+    /// A grey sample, for greyscale images with or without alpha.
+    Grey(u16),
+    /// Red, green and blue samples, for truecolor images with or without alpha.
+    Rgb(u16, u16, u16),
+    /// An entry in the palette, for indexed color images.
+    PaletteIndex(u8),
+}
@@ -0,0 +1,82 @@
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::DecodeError;
+use nom::{combinator::all_consuming, multi::count, number::complete::be_u32};
+
+/// The CIE 1931 x,y chromaticities of the display primaries and white point the image's samples
+/// are relative to.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct cHRMChunk {
+    /// White point, red, green and blue x and y, each times 100000.
+    values: [u32; 8],
+}
+impl cHRMChunk {
+    /// Creates a cHRM chunk from the x,y chromaticities of the white point and primaries.
+    /// Values are stored rounded to 5 decimal places.
+    pub fn new(
+        white_point: (f64, f64),
+        red: (f64, f64),
+        green: (f64, f64),
+        blue: (f64, f64),
+    ) -> Self {
+        let scale = |v: f64| (v * 100_000.0).round().clamp(0.0, i32::MAX as f64) as u32;
+        let values = [white_point, red, green, blue]
+            .map(|(x, y)| [scale(x), scale(y)])
+            .concat();
+        Self {
+            values: values.try_into().expect("4 pairs make 8 values"),
+        }
+    }
+
+    /// The x,y chromaticity of the white point.
+    pub fn white_point(&self) -> (f64, f64) {
+        self.pair(0)
+    }
+
+    /// The x,y chromaticity of the red primary.
+    pub fn red(&self) -> (f64, f64) {
+        self.pair(1)
+    }
+
+    /// The x,y chromaticity of the green primary.
+    pub fn green(&self) -> (f64, f64) {
+        self.pair(2)
+    }
+
+    /// The x,y chromaticity of the blue primary.
+    pub fn blue(&self) -> (f64, f64) {
+        self.pair(3)
+    }
+
+    fn pair(&self, index: usize) -> (f64, f64) {
+        let unscale = |v: u32| v as f64 / 100_000.0;
+        (
+            unscale(self.values[index * 2]),
+            unscale(self.values[index * 2 + 1]),
+        )
+    }
+}
+impl<'a> ParseableChunk<'a> for cHRMChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"cHRM";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (_, values) =
+            all_consuming(count(be_u32, 8))(chunk_data).map_err(malformed(Self::HEADER))?;
+        Ok(Self {
+            values: values.try_into().expect("8 values were parsed"),
+        })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = vec![0, 0, 0, 32];
+        bytes.extend(Self::HEADER);
+        for value in self.values {
+            bytes.extend(value.to_be_bytes());
+        }
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
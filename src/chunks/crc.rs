@@ -18,7 +18,7 @@ const CRC_TABLE: [u32; 256] = {
     table
 };
 
-fn update_crc<I: IntoIterator<Item = u8>>(crc: u32, data: I) -> u32 {
+pub(crate) fn update_crc<I: IntoIterator<Item = u8>>(crc: u32, data: I) -> u32 {
     let mut new_crc = crc;
     for b in data.into_iter() {
         let index = (new_crc ^ b as u32) & 0xff;
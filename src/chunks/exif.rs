@@ -0,0 +1,69 @@
+use std::fmt;
+
+use super::{crc::calculate_crc, ParseableChunk};
+use crate::error::{DecodeError, EncodeError};
+
+/// Exif metadata, such as the camera that took the image and the way up it should be shown.
+///
+/// The data is a TIFF structure as described by the Exif specification, starting with its
+/// byte order mark. [`Exif::parse`](crate::Exif::parse) reads the tags the library knows.
+#[allow(non_camel_case_types)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct eXIfChunk {
+    data: Vec<u8>,
+}
+impl eXIfChunk {
+    /// Creates an eXIf chunk holding `data`, failing with [`EncodeError::InvalidExif`] if it
+    /// doesn't start with a TIFF header.
+    pub fn new(data: Vec<u8>) -> Result<Self, EncodeError> {
+        if !has_tiff_header(&data) {
+            return Err(EncodeError::InvalidExif);
+        }
+        Ok(Self { data })
+    }
+
+    /// The raw Exif data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub(crate) fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+impl<'a> ParseableChunk<'a> for eXIfChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"eXIf";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        if !has_tiff_header(chunk_data) {
+            return Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            });
+        }
+        Ok(Self {
+            data: chunk_data.to_vec(),
+        })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = (self.data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        bytes.extend(&self.data);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
+impl fmt::Debug for eXIfChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("eXIfChunk")
+            .field("data_size", &self.data.len())
+            .finish_non_exhaustive()
+    }
+}
+
+fn has_tiff_header(data: &[u8]) -> bool {
+    data.starts_with(b"II*\0") || data.starts_with(b"MM\0*")
+}
@@ -0,0 +1,192 @@
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::DecodeError;
+use nom::{
+    combinator::all_consuming,
+    number::complete::{be_u16, be_u32, u8},
+    sequence::tuple,
+};
+
+/// The size, position, delay and disposal of an animation frame.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct fcTLChunk {
+    pub(crate) sequence_number: u32,
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) x_offset: u32,
+    pub(crate) y_offset: u32,
+    pub(crate) delay_num: u16,
+    pub(crate) delay_den: u16,
+    pub(crate) dispose_op: DisposeOp,
+    pub(crate) blend_op: BlendOp,
+}
+impl fcTLChunk {
+    /// Creates an fcTL chunk. The frame is shown for `delay_num / delay_den` seconds, where a
+    /// `delay_den` of 0 counts as 100.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sequence_number: u32,
+        width: u32,
+        height: u32,
+        x_offset: u32,
+        y_offset: u32,
+        delay_num: u16,
+        delay_den: u16,
+        dispose_op: DisposeOp,
+        blend_op: BlendOp,
+    ) -> Self {
+        Self {
+            sequence_number,
+            width,
+            height,
+            x_offset,
+            y_offset,
+            delay_num,
+            delay_den,
+            dispose_op,
+            blend_op,
+        }
+    }
+
+    /// The chunk's position among the file's fcTL and fdAT chunks, starting at 0.
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    /// Width of the frame in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Height of the frame in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Position of the frame's left edge on the canvas.
+    pub fn x_offset(&self) -> u32 {
+        self.x_offset
+    }
+
+    /// Position of the frame's top edge on the canvas.
+    pub fn y_offset(&self) -> u32 {
+        self.y_offset
+    }
+
+    /// The numerator of the frame's delay in seconds.
+    pub fn delay_num(&self) -> u16 {
+        self.delay_num
+    }
+
+    /// The denominator of the frame's delay in seconds.
+    pub fn delay_den(&self) -> u16 {
+        self.delay_den
+    }
+
+    /// What happens to the frame's region of the canvas once it has been shown.
+    pub fn dispose_op(&self) -> DisposeOp {
+        self.dispose_op
+    }
+
+    /// How the frame is drawn onto the canvas.
+    pub fn blend_op(&self) -> BlendOp {
+        self.blend_op
+    }
+}
+impl<'a> ParseableChunk<'a> for fcTLChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"fcTL";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (
+            _,
+            (
+                sequence_number,
+                width,
+                height,
+                x_offset,
+                y_offset,
+                delay_num,
+                delay_den,
+                dispose_op,
+                blend_op,
+            ),
+        ) = all_consuming(tuple((
+            be_u32, be_u32, be_u32, be_u32, be_u32, be_u16, be_u16, u8, u8,
+        )))(chunk_data)
+        .map_err(malformed(Self::HEADER))?;
+        let malformed_chunk = || DecodeError::MalformedChunk {
+            chunk_type: *Self::HEADER,
+        };
+        let dispose_op = match dispose_op {
+            0 => DisposeOp::None,
+            1 => DisposeOp::Background,
+            2 => DisposeOp::Previous,
+            _ => return Err(malformed_chunk()),
+        };
+        let blend_op = match blend_op {
+            0 => BlendOp::Source,
+            1 => BlendOp::Over,
+            _ => return Err(malformed_chunk()),
+        };
+        if width == 0 || height == 0 {
+            return Err(malformed_chunk());
+        }
+        Ok(Self {
+            sequence_number,
+            width,
+            height,
+            x_offset,
+            y_offset,
+            delay_num,
+            delay_den,
+            dispose_op,
+            blend_op,
+        })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = vec![0, 0, 0, 26];
+        bytes.extend(Self::HEADER);
+        for value in [
+            self.sequence_number,
+            self.width,
+            self.height,
+            self.x_offset,
+            self.y_offset,
+        ] {
+            bytes.extend(value.to_be_bytes());
+        }
+        bytes.extend(self.delay_num.to_be_bytes());
+        bytes.extend(self.delay_den.to_be_bytes());
+        bytes.push(self.dispose_op as u8);
+        bytes.push(self.blend_op as u8);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
+
+/// What happens to a frame's region of the canvas after the frame has been shown, before the
+/// next frame is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum DisposeOp {
+    /// The region is left as it is.
+    #[default]
+    None = 0,
+    /// The region is cleared to fully transparent black.
+    Background = 1,
+    /// The region is reverted to what it was before the frame was drawn.
+    Previous = 2,
+}
+
+/// How a frame is drawn onto the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum BlendOp {
+    /// The frame's pixels replace the canvas's, including alpha.
+    #[default]
+    Source = 0,
+    /// The frame is alpha composited over the canvas.
+    Over = 1,
+}
@@ -0,0 +1,54 @@
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::DecodeError;
+use nom::number::complete::be_u32;
+
+/// A piece of the compressed image data of an animation frame, other than the default image.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct fdATChunk<'a> {
+    pub(crate) sequence_number: u32,
+    pub(crate) data: &'a [u8],
+}
+impl<'a> fdATChunk<'a> {
+    /// Creates an fdAT chunk holding `data`.
+    pub fn new(sequence_number: u32, data: &'a [u8]) -> Self {
+        Self {
+            sequence_number,
+            data,
+        }
+    }
+
+    /// The chunk's position among the file's fcTL and fdAT chunks, starting at 0.
+    pub fn sequence_number(&self) -> u32 {
+        self.sequence_number
+    }
+
+    /// The compressed data in the chunk.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
+impl<'a> ParseableChunk<'a> for fdATChunk<'a> {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"fdAT";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (data, sequence_number) = be_u32(chunk_data).map_err(malformed(Self::HEADER))?;
+        Ok(Self {
+            sequence_number,
+            data,
+        })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let len = self.data.len() as u32 + 4;
+        let mut bytes = len.to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        bytes.extend(self.sequence_number.to_be_bytes());
+        bytes.extend(self.data);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
@@ -0,0 +1,53 @@
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::DecodeError;
+use nom::{combinator::all_consuming, number::complete::be_u32};
+
+/// The gamma the image's samples were encoded with, relating them to the light intensity they
+/// represent.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct gAMAChunk {
+    scaled_gamma: u32,
+}
+impl gAMAChunk {
+    /// Creates a gAMA chunk from the gamma times 100000, e.g. 45455 for 1/2.2.
+    pub fn new(scaled_gamma: u32) -> Self {
+        Self { scaled_gamma }
+    }
+
+    /// The gamma times 100000, as it's stored in the file.
+    pub fn scaled_gamma(&self) -> u32 {
+        self.scaled_gamma
+    }
+
+    /// The gamma, e.g. 0.45455 for an image encoded for a display gamma of 2.2.
+    pub fn gamma(&self) -> f64 {
+        self.scaled_gamma as f64 / 100_000.0
+    }
+}
+impl<'a> ParseableChunk<'a> for gAMAChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"gAMA";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (_, scaled_gamma) =
+            all_consuming(be_u32)(chunk_data).map_err(malformed(Self::HEADER))?;
+        // A gamma of zero would make every sample meaningless.
+        if scaled_gamma == 0 {
+            return Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            });
+        }
+        Ok(Self { scaled_gamma })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = vec![0, 0, 0, 4];
+        bytes.extend(Self::HEADER);
+        bytes.extend(self.scaled_gamma.to_be_bytes());
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
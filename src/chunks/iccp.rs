@@ -0,0 +1,100 @@
+use std::fmt;
+
+use super::{
+    crc::calculate_crc,
+    decompress_chunk_data, malformed,
+    text::{check_keyword, encode_latin1, keyword_from_bytes, parse_keyword},
+    ParseableChunk,
+};
+use crate::{
+    encoding::compress_zlib,
+    error::{DecodeError, EncodeError},
+    limits::DecodeLimits,
+    options::CompressionStrategy,
+};
+use nom::number::complete::u8;
+
+/// An embedded ICC profile, which describes the color space of the image's samples.
+#[allow(non_camel_case_types)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct iCCPChunk {
+    name: String,
+    profile: Vec<u8>,
+    /// The compressed profile as stored in the file, kept so the chunk is written back unchanged.
+    compressed: Vec<u8>,
+}
+impl iCCPChunk {
+    /// Creates an iCCP chunk holding `profile`, failing if `name` isn't a valid keyword.
+    pub fn new(name: &str, profile: &[u8]) -> Result<Self, EncodeError> {
+        check_keyword(name)?;
+        Ok(Self {
+            name: name.to_owned(),
+            profile: profile.to_vec(),
+            compressed: compress_zlib(profile, 8, CompressionStrategy::Default),
+        })
+    }
+
+    /// The profile's name, which follows the same rules as text chunk keywords.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The decompressed ICC profile.
+    pub fn profile(&self) -> &[u8] {
+        &self.profile
+    }
+
+    /// Interprets an iCCP chunk's data, failing if the profile decompresses to more than `limit`
+    /// bytes.
+    pub(crate) fn from_bytes_with_limit(
+        chunk_data: &[u8],
+        limit: usize,
+    ) -> Result<Self, DecodeError> {
+        let (rest, name) = parse_keyword(chunk_data).map_err(malformed(Self::HEADER))?;
+        let (compressed, compression_method) = u8(rest).map_err(malformed(Self::HEADER))?;
+        if compression_method != 0 {
+            return Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            });
+        }
+        Ok(Self {
+            name: keyword_from_bytes(name, Self::HEADER)?,
+            profile: decompress_chunk_data(
+                compressed,
+                limit,
+                "max_icc_profile_size",
+                Self::HEADER,
+            )?,
+            compressed: compressed.to_vec(),
+        })
+    }
+}
+impl<'a> ParseableChunk<'a> for iCCPChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"iCCP";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::from_bytes_with_limit(chunk_data, DecodeLimits::new().max_icc_profile_size)
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut data = encode_latin1(&self.name);
+        data.extend([0, 0]);
+        data.extend(&self.compressed);
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        bytes.extend(data);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
+impl fmt::Debug for iCCPChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("iCCPChunk")
+            .field("name", &self.name)
+            .field("profile_size", &self.profile.len())
+            .finish_non_exhaustive()
+    }
+}
@@ -1,24 +1,37 @@
 use super::{crc::calculate_crc, ParseableChunk};
-use nom::IResult;
+use crate::error::DecodeError;
 
-#[derive(Debug)]
+/// A piece of the compressed image data. An image's data is the concatenation of all of its
+/// IDAT chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct IDATChunk<'a> {
     pub(crate) data: &'a [u8],
 }
+impl<'a> IDATChunk<'a> {
+    /// Creates an IDAT chunk holding `data`.
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// The compressed data in the chunk.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+}
 impl<'a> ParseableChunk<'a> for IDATChunk<'a> {
     type Output = Vec<u8>;
 
     const HEADER: &'static [u8; 4] = b"IDAT";
 
-    fn from_bytes(chunk_data: &'a [u8]) -> IResult<&[u8], Self> {
-        Ok((&chunk_data[0..0], IDATChunk { data: chunk_data }))
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        Ok(IDATChunk { data: chunk_data })
     }
 
     fn to_bytes(&self) -> Self::Output {
-        let len = self.data.as_ref().len() as u32;
+        let len = self.data.len() as u32;
         let mut bytes = len.to_be_bytes().to_vec();
         bytes.extend(Self::HEADER);
-        bytes.extend(self.data.as_ref());
+        bytes.extend(self.data);
         let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
         bytes.extend(crc);
         bytes
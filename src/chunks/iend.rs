@@ -1,4 +1,5 @@
 use super::{crc::calculate_crc, ParseableChunk};
+use crate::error::DecodeError;
 
 pub(crate) struct IENDChunk;
 impl<'a> ParseableChunk<'a> for IENDChunk {
@@ -6,8 +7,8 @@ impl<'a> ParseableChunk<'a> for IENDChunk {
 
     const HEADER: &'static [u8; 4] = b"IEND";
 
-    fn from_bytes(chunk_data: &[u8]) -> nom::IResult<&[u8], Self> {
-        Ok((chunk_data, Self))
+    fn from_bytes(_chunk_data: &[u8]) -> Result<Self, DecodeError> {
+        Ok(Self)
     }
 
     fn to_bytes(&self) -> Self::Output {
@@ -1,17 +1,55 @@
-use super::{crc::calculate_crc, ParseableChunk};
-use nom::{bytes::complete::take, number::complete::be_u32, sequence::tuple, IResult};
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::DecodeError;
+use nom::{
+    bytes::complete::take, combinator::all_consuming, number::complete::be_u32, sequence::tuple,
+};
 
-#[derive(Debug, Default)]
+/// The image header, which describes the image's dimensions and how its pixels are stored.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
 pub struct IHDRChunk {
+    /// Width of the image in pixels.
     pub width: u32,
+    /// Height of the image in pixels.
     pub height: u32,
-    pub(crate) bit_depth: u8,
-    pub(crate) color_type: ColorType,
+    /// Number of bits per sample, or per palette index for indexed images.
+    pub bit_depth: u8,
+    /// How the samples of each pixel are laid out.
+    pub color_type: ColorType,
     pub(crate) compression_method: u8,
     pub(crate) filter_method: u8,
-    pub(crate) interlace_method: Interlacing,
+    /// Whether the image data is stored interlaced.
+    pub interlace_method: Interlacing,
 }
 impl IHDRChunk {
+    /// Creates a header for an image with the given dimensions and format.
+    pub fn new(
+        width: u32,
+        height: u32,
+        bit_depth: u8,
+        color_type: ColorType,
+        interlace_method: Interlacing,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method,
+        }
+    }
+
+    /// The compression method, always 0 (zlib) in valid files.
+    pub fn compression_method(&self) -> u8 {
+        self.compression_method
+    }
+
+    /// The filter method, always 0 (adaptive filtering) in valid files.
+    pub fn filter_method(&self) -> u8 {
+        self.filter_method
+    }
+
     pub(crate) fn filter_width(&self) -> u8 {
         let channel_count = self.color_type.channel_count();
         let sample_width = u8::max(self.bit_depth / 8, 1);
@@ -27,21 +65,38 @@ impl<'a> ParseableChunk<'a> for IHDRChunk {
 
     const HEADER: &'static [u8; 4] = b"IHDR";
 
-    fn from_bytes(chunk_data: &'a [u8]) -> IResult<&'a [u8], Self> {
-        let (rest, (width, height, other_bytes)) =
-            tuple((be_u32, be_u32, take(5usize)))(chunk_data)?;
-        Ok((
-            rest,
-            IHDRChunk {
-                width,
-                height,
-                bit_depth: other_bytes[0],
-                color_type: other_bytes[1].into(),
-                compression_method: other_bytes[2],
-                filter_method: other_bytes[3],
-                interlace_method: other_bytes[4].into(),
-            },
-        ))
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (_, (width, height, other_bytes)) =
+            all_consuming(tuple((be_u32, be_u32, take(5usize))))(chunk_data)
+                .map_err(malformed(Self::HEADER))?;
+        let invalid = |field, value| DecodeError::InvalidHeaderField { field, value };
+        // The spec limits both dimensions to 2^31 - 1.
+        if width == 0 || width > i32::MAX as u32 {
+            return Err(invalid("width", width));
+        }
+        if height == 0 || height > i32::MAX as u32 {
+            return Err(invalid("height", height));
+        }
+        let color_type = ColorType::try_from(other_bytes[1])?;
+        let bit_depth = other_bytes[0];
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(invalid("bit_depth", bit_depth.into()));
+        }
+        if other_bytes[2] != 0 {
+            return Err(invalid("compression_method", other_bytes[2].into()));
+        }
+        if other_bytes[3] != 0 {
+            return Err(invalid("filter_method", other_bytes[3].into()));
+        }
+        Ok(IHDRChunk {
+            width,
+            height,
+            bit_depth,
+            color_type,
+            compression_method: other_bytes[2],
+            filter_method: other_bytes[3],
+            interlace_method: other_bytes[4].try_into()?,
+        })
     }
 
     fn to_bytes(&self) -> Self::Output {
@@ -62,24 +117,37 @@ impl<'a> ParseableChunk<'a> for IHDRChunk {
     }
 }
 
-#[derive(Debug, Default, Clone, Copy)]
-pub(crate) enum ColorType {
+/// The color types a PNG image can be stored as.
+///
+/// Each color type only allows certain bit depths: greyscale allows 1, 2, 4, 8 and 16, indexed
+/// color allows 1, 2, 4 and 8, and the rest allow 8 and 16.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColorType {
+    /// A single grey sample per pixel.
     #[default]
     Greyscale = 0,
+    /// Red, green and blue samples per pixel.
     Truecolor = 2,
+    /// A single index into the image's palette per pixel.
     IndexedColor = 3,
+    /// A grey sample and an alpha sample per pixel.
     GreyscaleWithAlpha = 4,
+    /// Red, green, blue and alpha samples per pixel.
     TruecolorWithAlpha = 6,
 }
-impl From<u8> for ColorType {
-    fn from(value: u8) -> Self {
+impl TryFrom<u8> for ColorType {
+    type Error = DecodeError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            0 => Self::Greyscale,
-            2 => Self::Truecolor,
-            3 => Self::IndexedColor,
-            4 => Self::GreyscaleWithAlpha,
-            6 => Self::TruecolorWithAlpha,
-            _ => panic!(),
+            0 => Ok(Self::Greyscale),
+            2 => Ok(Self::Truecolor),
+            3 => Ok(Self::IndexedColor),
+            4 => Ok(Self::GreyscaleWithAlpha),
+            6 => Ok(Self::TruecolorWithAlpha),
+            v => Err(DecodeError::InvalidHeaderField {
+                field: "color_type",
+                value: v.into(),
+            }),
         }
     }
 }
@@ -93,19 +161,35 @@ impl ColorType {
             Self::TruecolorWithAlpha => 4,
         }
     }
+
+    pub(crate) fn allowed_bit_depths(&self) -> &'static [u8] {
+        match self {
+            Self::Greyscale => &[1, 2, 4, 8, 16],
+            Self::IndexedColor => &[1, 2, 4, 8],
+            Self::GreyscaleWithAlpha | Self::Truecolor | Self::TruecolorWithAlpha => &[8, 16],
+        }
+    }
 }
-#[derive(Debug, Default, Clone, Copy)]
-pub(crate) enum Interlacing {
+/// The order in which an image's pixels are stored.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Interlacing {
+    /// Scanline by scanline, from top to bottom.
     #[default]
     None,
+    /// In seven passes of increasing detail, so a coarse version of the image can be shown
+    /// before all of it has been read.
     Adam7,
 }
-impl From<u8> for Interlacing {
-    fn from(value: u8) -> Self {
+impl TryFrom<u8> for Interlacing {
+    type Error = DecodeError;
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
-            0 => Self::None,
-            1 => Self::Adam7,
-            _ => panic!(),
+            0 => Ok(Self::None),
+            1 => Ok(Self::Adam7),
+            v => Err(DecodeError::InvalidHeaderField {
+                field: "interlace_method",
+                value: v.into(),
+            }),
         }
     }
 }
@@ -0,0 +1,163 @@
+use std::fmt;
+
+use super::{
+    crc::calculate_crc,
+    decompress_chunk_data, malformed,
+    text::{check_keyword, encode_latin1, keyword_from_bytes, parse_keyword},
+    ParseableChunk,
+};
+use crate::{
+    encoding::compress_zlib,
+    error::{DecodeError, EncodeError},
+    limits::DecodeLimits,
+    options::CompressionStrategy,
+};
+use nom::{number::complete::u8, sequence::tuple};
+
+/// A keyword and UTF-8 text, optionally compressed, along with the language the text is in.
+#[allow(non_camel_case_types)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct iTXtChunk {
+    keyword: String,
+    language_tag: String,
+    translated_keyword: String,
+    text: String,
+    /// The compressed text as stored in the file, if the chunk is compressed, kept so the chunk
+    /// is written back unchanged.
+    compressed: Option<Vec<u8>>,
+}
+impl iTXtChunk {
+    /// Creates an iTXt chunk, failing if the keyword or language tag isn't valid.
+    ///
+    /// The language tag is an RFC 3066 tag such as `"en"` or `"en-GB"`, and the translated
+    /// keyword is the keyword in that language. Either can be empty. The text is zlib compressed
+    /// if `compress` is set.
+    pub fn new(
+        keyword: &str,
+        language_tag: &str,
+        translated_keyword: &str,
+        text: &str,
+        compress: bool,
+    ) -> Result<Self, EncodeError> {
+        check_keyword(keyword)?;
+        let tag_is_valid = language_tag
+            .split('-')
+            .all(|part| (1..=8).contains(&part.len()))
+            && language_tag
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !language_tag.is_empty() && !tag_is_valid {
+            return Err(EncodeError::InvalidLanguageTag(language_tag.to_owned()));
+        }
+        // The translated keyword is null terminated, so it can't contain a null itself.
+        if translated_keyword.contains('\0') {
+            return Err(EncodeError::InvalidKeyword(translated_keyword.to_owned()));
+        }
+        Ok(Self {
+            keyword: keyword.to_owned(),
+            language_tag: language_tag.to_owned(),
+            translated_keyword: translated_keyword.to_owned(),
+            text: text.to_owned(),
+            compressed: compress
+                .then(|| compress_zlib(text.as_bytes(), 8, CompressionStrategy::Default)),
+        })
+    }
+
+    /// The keyword, which says what the text is, e.g. `"Title"`.
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    /// The language the text is in, which can be empty if it's unknown.
+    pub fn language_tag(&self) -> &str {
+        &self.language_tag
+    }
+
+    /// The keyword translated to the text's language, which can be empty.
+    pub fn translated_keyword(&self) -> &str {
+        &self.translated_keyword
+    }
+
+    /// The text, decompressed if the chunk is compressed.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether the text is stored compressed.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed.is_some()
+    }
+
+    /// Interprets an iTXt chunk's data, failing if the text decompresses to more than `limit`
+    /// bytes.
+    pub(crate) fn from_bytes_with_limit(
+        chunk_data: &[u8],
+        limit: usize,
+    ) -> Result<Self, DecodeError> {
+        let malformed_chunk = || DecodeError::MalformedChunk {
+            chunk_type: *Self::HEADER,
+        };
+        let (rest, (keyword, compression_flag, compression_method, language_tag, translated)) =
+            tuple((parse_keyword, u8, u8, parse_keyword, parse_keyword))(chunk_data)
+                .map_err(malformed(Self::HEADER))?;
+        let (text, compressed) = match (compression_flag, compression_method) {
+            (0, _) => (rest.to_vec(), None),
+            (1, 0) => (
+                decompress_chunk_data(rest, limit, "max_text_chunk_size", Self::HEADER)?,
+                Some(rest.to_vec()),
+            ),
+            _ => return Err(malformed_chunk()),
+        };
+        let utf8 = |bytes: Vec<u8>| String::from_utf8(bytes).map_err(|_| malformed_chunk());
+        let language_tag = utf8(language_tag.to_vec())?;
+        if !language_tag.is_ascii() {
+            return Err(malformed_chunk());
+        }
+        Ok(Self {
+            keyword: keyword_from_bytes(keyword, Self::HEADER)?,
+            language_tag,
+            translated_keyword: utf8(translated.to_vec())?,
+            text: utf8(text)?,
+            compressed,
+        })
+    }
+}
+impl<'a> ParseableChunk<'a> for iTXtChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"iTXt";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::from_bytes_with_limit(chunk_data, DecodeLimits::new().max_text_chunk_size)
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut data = encode_latin1(&self.keyword);
+        data.extend([0, self.compressed.is_some() as u8, 0]);
+        data.extend(self.language_tag.as_bytes());
+        data.push(0);
+        data.extend(self.translated_keyword.as_bytes());
+        data.push(0);
+        match &self.compressed {
+            Some(compressed) => data.extend(compressed),
+            None => data.extend(self.text.as_bytes()),
+        }
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        bytes.extend(data);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
+impl fmt::Debug for iTXtChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("iTXtChunk")
+            .field("keyword", &self.keyword)
+            .field("language_tag", &self.language_tag)
+            .field("translated_keyword", &self.translated_keyword)
+            .field("text", &self.text)
+            .field("compressed", &self.is_compressed())
+            .finish()
+    }
+}
@@ -1,67 +1,286 @@
-use nom::{
-    bytes::complete::{tag, take},
-    combinator::map,
-    multi::length_data,
-    number::complete::be_u32,
-    sequence::{terminated, tuple},
-    IResult,
-};
+//! The chunks a PNG file is made of, for reading and writing files chunk by chunk.
+use miniz_oxide::inflate::{decompress_to_vec_zlib_with_limit, TINFLStatus};
+use nom::{bytes::complete::take, number::complete::be_u32, sequence::tuple};
 
 use self::iend::IENDChunk;
+use crate::{
+    error::DecodeError,
+    limits::{ChunkTally, DecodeLimits},
+    png::SIGNATURE,
+};
 
-mod crc;
+pub(crate) mod actl;
+pub(crate) mod bkgd;
+pub(crate) mod chrm;
+pub(crate) mod crc;
+pub(crate) mod exif;
+pub(crate) mod fctl;
+pub(crate) mod fdat;
+pub(crate) mod gama;
+pub(crate) mod iccp;
 pub(crate) mod idat;
 pub(crate) mod iend;
 pub(crate) mod ihdr;
-mod phys;
+pub(crate) mod itxt;
+pub(crate) mod phys;
 pub(crate) mod plte;
+pub(crate) mod sbit;
+pub(crate) mod srgb;
+pub(crate) mod text;
+pub(crate) mod time;
 pub(crate) mod trns;
+pub(crate) mod ztxt;
+
+pub use actl::acTLChunk;
+pub use bkgd::{bKGDChunk, Background};
+pub use chrm::cHRMChunk;
+pub use exif::eXIfChunk;
+pub use fctl::{fcTLChunk, BlendOp, DisposeOp};
+pub use fdat::fdATChunk;
+pub use gama::gAMAChunk;
+pub use iccp::iCCPChunk;
+pub use idat::IDATChunk;
+pub use ihdr::{ColorType, IHDRChunk, Interlacing};
+pub use itxt::iTXtChunk;
+pub use phys::{pHYsChunk, Unit};
+pub use plte::{PLTEChunk, PaletteEntry};
+pub use sbit::{sBITChunk, SignificantBits};
+pub use srgb::{sRGBChunk, RenderingIntent};
+pub use text::{tEXtChunk, TextEntry};
+pub use time::tIMEChunk;
+pub use trns::tRNSChunk;
+pub use ztxt::zTXtChunk;
 
+/// A single chunk of a PNG file, interpreted if the library knows its type.
 #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Chunk<'a> {
-    IHDR(ihdr::IHDRChunk),
-    PLTE(plte::PLTEChunk),
-    pHYs(phys::pHYsChunk),
-    IDAT(idat::IDATChunk<'a>),
-    tRNS(trns::tRNSChunk<'a>),
+    /// The image header.
+    IHDR(IHDRChunk),
+    /// The palette.
+    PLTE(PLTEChunk),
+    /// The physical pixel dimensions.
+    pHYs(pHYsChunk),
+    /// The gamma the samples are encoded with.
+    gAMA(gAMAChunk),
+    /// The chromaticities of the primaries and white point.
+    cHRM(cHRMChunk),
+    /// Marks the samples as sRGB.
+    sRGB(sRGBChunk),
+    /// An embedded ICC profile.
+    iCCP(iCCPChunk),
+    /// The default background color.
+    bKGD(bKGDChunk),
+    /// The number of significant bits in the samples.
+    sBIT(sBITChunk),
+    /// Exif metadata.
+    eXIf(eXIfChunk),
+    /// When the image was last modified.
+    tIME(tIMEChunk),
+    /// Marks the file as an animated PNG.
+    acTL(acTLChunk),
+    /// The size, position and timing of an animation frame.
+    fcTL(fcTLChunk),
+    /// A piece of the compressed image data of an animation frame.
+    fdAT(fdATChunk<'a>),
+    /// A piece of the compressed image data.
+    IDAT(IDATChunk<'a>),
+    /// Transparency for images without an alpha channel.
+    tRNS(tRNSChunk<'a>),
+    /// Latin-1 text.
+    tEXt(tEXtChunk),
+    /// Compressed Latin-1 text.
+    zTXt(zTXtChunk),
+    /// UTF-8 text, optionally compressed.
+    iTXt(iTXtChunk),
+    /// The end of the file.
     IEND,
+    /// A chunk the library doesn't interpret.
     Unknown(RawChunk<'a>),
 }
 impl<'a> Chunk<'a> {
+    /// Interprets `chunk_data` as a chunk of type `chunk_type`. Types the library doesn't know
+    /// become [`Chunk::Unknown`].
+    pub fn parse(chunk_type: &'a [u8; 4], chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::parse_with_limits(chunk_type, chunk_data, &DecodeLimits::new())
+    }
+
+    /// Interprets `chunk_data` like [`parse`](Self::parse), failing if it decompresses to more
+    /// than `limits` allow.
+    pub(crate) fn parse_with_limits(
+        chunk_type: &'a [u8; 4],
+        chunk_data: &'a [u8],
+        limits: &DecodeLimits,
+    ) -> Result<Self, DecodeError> {
+        Ok(match chunk_type {
+            IHDRChunk::HEADER => Chunk::IHDR(IHDRChunk::from_bytes(chunk_data)?),
+            PLTEChunk::HEADER => Chunk::PLTE(PLTEChunk::from_bytes(chunk_data)?),
+            pHYsChunk::HEADER => Chunk::pHYs(pHYsChunk::from_bytes(chunk_data)?),
+            gAMAChunk::HEADER => Chunk::gAMA(gAMAChunk::from_bytes(chunk_data)?),
+            cHRMChunk::HEADER => Chunk::cHRM(cHRMChunk::from_bytes(chunk_data)?),
+            sRGBChunk::HEADER => Chunk::sRGB(sRGBChunk::from_bytes(chunk_data)?),
+            iCCPChunk::HEADER => Chunk::iCCP(iCCPChunk::from_bytes_with_limit(
+                chunk_data,
+                limits.max_icc_profile_size,
+            )?),
+            bKGDChunk::HEADER => Chunk::bKGD(bKGDChunk::from_bytes(chunk_data)?),
+            sBITChunk::HEADER => Chunk::sBIT(sBITChunk::from_bytes(chunk_data)?),
+            eXIfChunk::HEADER => Chunk::eXIf(eXIfChunk::from_bytes(chunk_data)?),
+            tIMEChunk::HEADER => Chunk::tIME(tIMEChunk::from_bytes(chunk_data)?),
+            acTLChunk::HEADER => Chunk::acTL(acTLChunk::from_bytes(chunk_data)?),
+            fcTLChunk::HEADER => Chunk::fcTL(fcTLChunk::from_bytes(chunk_data)?),
+            fdATChunk::HEADER => Chunk::fdAT(fdATChunk::from_bytes(chunk_data)?),
+            IDATChunk::HEADER => Chunk::IDAT(IDATChunk::from_bytes(chunk_data)?),
+            tRNSChunk::HEADER => Chunk::tRNS(tRNSChunk::from_bytes(chunk_data)?),
+            tEXtChunk::HEADER => Chunk::tEXt(tEXtChunk::from_bytes(chunk_data)?),
+            zTXtChunk::HEADER => Chunk::zTXt(zTXtChunk::from_bytes_with_limit(
+                chunk_data,
+                limits.max_text_chunk_size,
+            )?),
+            iTXtChunk::HEADER => Chunk::iTXt(iTXtChunk::from_bytes_with_limit(
+                chunk_data,
+                limits.max_text_chunk_size,
+            )?),
+            IENDChunk::HEADER => Chunk::IEND,
+            _ => Chunk::Unknown(RawChunk::new(chunk_type, chunk_data)),
+        })
+    }
+
+    /// The four byte chunk type, e.g. `b"IDAT"`.
+    pub fn chunk_type(&self) -> &[u8; 4] {
+        match self {
+            Self::IHDR(_) => IHDRChunk::HEADER,
+            Self::PLTE(_) => PLTEChunk::HEADER,
+            Self::pHYs(_) => pHYsChunk::HEADER,
+            Self::gAMA(_) => gAMAChunk::HEADER,
+            Self::cHRM(_) => cHRMChunk::HEADER,
+            Self::sRGB(_) => sRGBChunk::HEADER,
+            Self::iCCP(_) => iCCPChunk::HEADER,
+            Self::bKGD(_) => bKGDChunk::HEADER,
+            Self::sBIT(_) => sBITChunk::HEADER,
+            Self::eXIf(_) => eXIfChunk::HEADER,
+            Self::tIME(_) => tIMEChunk::HEADER,
+            Self::acTL(_) => acTLChunk::HEADER,
+            Self::fcTL(_) => fcTLChunk::HEADER,
+            Self::fdAT(_) => fdATChunk::HEADER,
+            Self::IDAT(_) => IDATChunk::HEADER,
+            Self::tRNS(_) => tRNSChunk::HEADER,
+            Self::tEXt(_) => tEXtChunk::HEADER,
+            Self::zTXt(_) => zTXtChunk::HEADER,
+            Self::iTXt(_) => iTXtChunk::HEADER,
+            Self::IEND => IENDChunk::HEADER,
+            Self::Unknown(chunk) => chunk.chunk_type,
+        }
+    }
+
+    /// Serializes the chunk, including its length, type and CRC.
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             Self::IHDR(chunk) => chunk.to_bytes(),
             Self::PLTE(chunk) => chunk.to_bytes(),
             Self::pHYs(chunk) => chunk.to_bytes(),
+            Self::gAMA(chunk) => chunk.to_bytes(),
+            Self::cHRM(chunk) => chunk.to_bytes(),
+            Self::sRGB(chunk) => chunk.to_bytes(),
+            Self::iCCP(chunk) => chunk.to_bytes(),
+            Self::bKGD(chunk) => chunk.to_bytes(),
+            Self::sBIT(chunk) => chunk.to_bytes(),
+            Self::eXIf(chunk) => chunk.to_bytes(),
+            Self::tIME(chunk) => chunk.to_bytes(),
+            Self::acTL(chunk) => chunk.to_bytes(),
+            Self::fcTL(chunk) => chunk.to_bytes(),
+            Self::fdAT(chunk) => chunk.to_bytes(),
             Self::IDAT(chunk) => chunk.to_bytes(),
             Self::tRNS(chunk) => chunk.to_bytes(),
+            Self::tEXt(chunk) => chunk.to_bytes(),
+            Self::zTXt(chunk) => chunk.to_bytes(),
+            Self::iTXt(chunk) => chunk.to_bytes(),
             Self::IEND => IENDChunk.to_bytes().to_vec(),
             Self::Unknown(chunk) => chunk.to_bytes(),
         }
     }
 }
 
-pub(crate) fn iter_chunks(source: &[u8]) -> ChunkIter {
+/// Splits a PNG file into its chunks, up to and including IEND, checking the signature and every
+/// chunk's CRC. Unlike [`PNG::decode`](crate::PNG::decode), the order of the chunks isn't checked
+/// and the image data isn't decompressed. Like it, malformed ancillary chunks other than the
+/// animation chunks are kept as [`Chunk::Unknown`].
+///
+/// ```
+/// use simple_png::chunks::{read_chunks, write_chunks, Chunk};
+///
+/// let data = std::fs::read("tests/png-suite/basn0g01.png")?;
+/// let chunks = read_chunks(&data)?;
+/// assert!(matches!(chunks[0], Chunk::IHDR(_)));
+/// assert_eq!(write_chunks(&chunks), data);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub fn read_chunks(bytes: &[u8]) -> Result<Vec<Chunk<'_>>, DecodeError> {
+    let rest = bytes
+        .strip_prefix(SIGNATURE)
+        .ok_or(DecodeError::InvalidSignature)?;
+    let chunks = iter_chunks(rest, DecodeLimits::new()).collect::<Result<Vec<_>, _>>()?;
+    if !matches!(chunks.last(), Some(Chunk::IEND)) {
+        return Err(DecodeError::UnexpectedEndOfFile {
+            offset: bytes.len(),
+        });
+    }
+    Ok(chunks)
+}
+
+/// Writes the PNG signature followed by `chunks`.
+pub fn write_chunks(chunks: &[Chunk]) -> Vec<u8> {
+    let mut bytes = SIGNATURE.to_vec();
+    for chunk in chunks {
+        bytes.extend(chunk.to_bytes());
+    }
+    bytes
+}
+
+/// Iterates over the chunks in `source`, which should be everything following the signature,
+/// failing once the chunks go over `limits`.
+pub(crate) fn iter_chunks(source: &[u8], limits: DecodeLimits) -> ChunkIter<'_> {
     ChunkIter {
         source,
+        offset: SIGNATURE.len(),
         finished: false,
+        limits,
+        tally: ChunkTally::default(),
     }
 }
 
 pub(crate) struct ChunkIter<'a> {
     source: &'a [u8],
+    offset: usize,
     finished: bool,
+    limits: DecodeLimits,
+    tally: ChunkTally,
+}
+
+impl<'a> ChunkIter<'a> {
+    /// The input that hasn't been parsed yet, starting with the next chunk.
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        self.source
+    }
 }
 
 impl<'a> Iterator for ChunkIter<'a> {
-    type Item = anyhow::Result<Chunk<'a>>;
+    type Item = Result<Chunk<'a>, DecodeError>;
     fn next(&mut self) -> Option<Self::Item> {
         if self.finished {
             return None;
         }
-        match parse_chunk(self.source) {
+        if let (Some(length), Some(chunk_type)) = (self.source.get(..4), self.source.get(4..8)) {
+            let length = u32::from_be_bytes(length.try_into().expect("4 bytes were taken"));
+            let chunk_type = chunk_type.try_into().expect("4 bytes were taken");
+            if let Err(e) = self.limits.check_chunk(&mut self.tally, chunk_type, length) {
+                self.finished = true;
+                return Some(Err(e));
+            }
+        }
+        match parse_chunk(self.source, self.offset, &self.limits) {
             Ok((rest, chunk)) => {
+                self.offset += self.source.len() - rest.len();
                 self.source = rest;
                 if matches!(chunk, Chunk::IEND) {
                     self.finished = true;
@@ -70,88 +289,139 @@ impl<'a> Iterator for ChunkIter<'a> {
             }
             Err(e) => {
                 self.finished = true;
-                Some(Err(e.to_owned().into()))
+                Some(Err(e))
             }
         }
     }
 }
 
-fn parse_chunk(input: &[u8]) -> IResult<&[u8], Chunk<'_>> {
-    let (rest, (header, chunk_data)) = valid_chunk(input)?;
-    match header {
-        ihdr::IHDRChunk::HEADER => Ok((
-            rest,
-            Chunk::IHDR(ihdr::IHDRChunk::from_bytes(chunk_data)?.1),
-        )),
-        plte::PLTEChunk::HEADER => Ok((
-            rest,
-            Chunk::PLTE(plte::PLTEChunk::from_bytes(chunk_data)?.1),
-        )),
-        phys::pHYsChunk::HEADER => Ok((
-            rest,
-            Chunk::pHYs(phys::pHYsChunk::from_bytes(chunk_data)?.1),
-        )),
-        idat::IDATChunk::HEADER => Ok((
-            rest,
-            Chunk::IDAT(idat::IDATChunk::from_bytes(chunk_data)?.1),
-        )),
-        trns::tRNSChunk::HEADER => Ok((
-            rest,
-            Chunk::tRNS(trns::tRNSChunk::from_bytes(chunk_data)?.1),
-        )),
-        iend::IENDChunk::HEADER => Ok((rest, Chunk::IEND)),
-        _ => Ok((
-            rest,
-            Chunk::Unknown(RawChunk {
-                _chunk_type: header,
-                _chunk_data: chunk_data,
-            }),
-        )),
-    }
+fn parse_chunk<'a>(
+    input: &'a [u8],
+    offset: usize,
+    limits: &DecodeLimits,
+) -> Result<(&'a [u8], Chunk<'a>), DecodeError> {
+    let (rest, chunk_type, chunk_data) = valid_chunk(input, offset)?;
+    let chunk = match Chunk::parse_with_limits(chunk_type, chunk_data, limits) {
+        Err(DecodeError::MalformedChunk { .. }) if is_skippable(chunk_type) => {
+            Chunk::Unknown(RawChunk::new(chunk_type, chunk_data))
+        }
+        chunk => chunk?,
+    };
+    Ok((rest, chunk))
+}
+
+/// Whether a malformed chunk of type `chunk_type` can be kept as a [`Chunk::Unknown`] instead of
+/// failing the decode. This holds for ancillary chunks, which are marked by a lowercase first
+/// letter, apart from the animation chunks, which an animated PNG can't be decoded without.
+fn is_skippable(chunk_type: &[u8; 4]) -> bool {
+    chunk_type[0].is_ascii_lowercase()
+        && ![acTLChunk::HEADER, fcTLChunk::HEADER, fdATChunk::HEADER].contains(&chunk_type)
 }
 
-#[derive(Debug)]
+/// A chunk the library doesn't interpret, kept as its type and raw data.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RawChunk<'a> {
-    _chunk_type: &'a [u8; 4],
-    _chunk_data: &'a [u8],
+    chunk_type: &'a [u8; 4],
+    chunk_data: &'a [u8],
 }
 impl<'a> RawChunk<'a> {
+    /// Creates a chunk from its type and data.
+    pub fn new(chunk_type: &'a [u8; 4], chunk_data: &'a [u8]) -> Self {
+        Self {
+            chunk_type,
+            chunk_data,
+        }
+    }
+
+    /// The four byte chunk type.
+    pub fn chunk_type(&self) -> &'a [u8; 4] {
+        self.chunk_type
+    }
+
+    /// The chunk's data, without its length, type and CRC.
+    pub fn data(&self) -> &'a [u8] {
+        self.chunk_data
+    }
+
+    /// Whether the chunk has a type the library knows, but data it couldn't interpret. Decoding
+    /// keeps malformed ancillary chunks like this, and encoding leaves them out.
+    pub(crate) fn is_malformed(&self) -> bool {
+        matches!(
+            Chunk::parse(self.chunk_type, self.chunk_data),
+            Err(DecodeError::MalformedChunk { .. })
+        )
+    }
+
+    /// Serializes the chunk, including its length, type and CRC.
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut data = (self._chunk_data.len() as u32).to_be_bytes().to_vec();
-        data.extend_from_slice(self._chunk_type);
-        data.extend(self._chunk_data);
+        let mut data = (self.chunk_data.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(self.chunk_type);
+        data.extend(self.chunk_data);
         data.extend(crc::calculate_crc(data[4..].iter().copied()).to_be_bytes());
         data
     }
 }
 
-fn valid_chunk<'a, Error: nom::error::ParseError<&'a [u8]>>(
-    input: &'a [u8],
-) -> IResult<&'a [u8], (&'a [u8; 4], &'a [u8]), Error> {
-    let (header_length, crc_length) = (4, 4);
-    let (input, chunk_data) = length_data(map(be_u32, |v| v + header_length + crc_length))(input)?;
-    let crc = crc::calculate_crc(
-        chunk_data[0..chunk_data.len() - crc_length as usize]
-            .iter()
-            .copied(),
-    )
-    .to_be_bytes();
-    let (_, data) = tuple((
-        map(take(header_length), |v: &[u8]| {
-            v.try_into().expect("4 bytes should have been taken")
-        }),
-        terminated(
-            take(chunk_data.len() - (header_length + crc_length) as usize),
-            tag(crc),
-        ),
-    ))(chunk_data)?;
-    Ok((input, data))
-}
-
-pub(crate) trait ParseableChunk<'a>: Sized {
+/// The input following a chunk, the chunk's type and the chunk's data.
+type SplitChunk<'a> = (&'a [u8], &'a [u8; 4], &'a [u8]);
+
+/// Splits a chunk off the front of `input`, verifying its CRC. `offset` is the position of
+/// `input` within the file and is only used for error reporting.
+fn valid_chunk(input: &[u8], offset: usize) -> Result<SplitChunk<'_>, DecodeError> {
+    let header_length = 4usize;
+    let (rest, (length, chunk_type)) = tuple((be_u32, take(header_length)))(input)
+        .map_err(|_: NomError| DecodeError::UnexpectedEndOfFile { offset })?;
+    let (rest, (chunk_data, expected)) = tuple((take(length), be_u32))(rest)
+        .map_err(|_: NomError| DecodeError::UnexpectedEndOfFile { offset })?;
+    let chunk_type: &[u8; 4] = chunk_type
+        .try_into()
+        .expect("4 bytes should have been taken");
+    let actual = crc::calculate_crc(chunk_type.iter().chain(chunk_data).copied());
+    if actual != expected {
+        return Err(DecodeError::CrcMismatch {
+            chunk_type: *chunk_type,
+            offset,
+            expected,
+            actual,
+        });
+    }
+    Ok((rest, chunk_type, chunk_data))
+}
+
+type NomError<'a> = nom::Err<nom::error::Error<&'a [u8]>>;
+
+/// Maps any nom error raised while parsing the data of a `chunk_type` chunk to a
+/// [`DecodeError::MalformedChunk`].
+pub(crate) fn malformed<'a>(chunk_type: &[u8; 4]) -> impl FnOnce(NomError<'a>) -> DecodeError {
+    let chunk_type = *chunk_type;
+    move |_| DecodeError::MalformedChunk { chunk_type }
+}
+
+/// Decompresses the zlib compressed part of a `chunk_type` chunk's data, failing with the limit
+/// called `limit_name` if it takes more than `limit` bytes.
+pub(crate) fn decompress_chunk_data(
+    data: &[u8],
+    limit: usize,
+    limit_name: &'static str,
+    chunk_type: &[u8; 4],
+) -> Result<Vec<u8>, DecodeError> {
+    decompress_to_vec_zlib_with_limit(data, limit).map_err(|e| match e.status {
+        TINFLStatus::HasMoreOutput => DecodeError::LimitExceeded { limit: limit_name },
+        _ => DecodeError::MalformedChunk {
+            chunk_type: *chunk_type,
+        },
+    })
+}
+
+/// A chunk type the library can read and write.
+pub trait ParseableChunk<'a>: Sized {
+    /// The serialized chunk.
     type Output: AsRef<[u8]>;
+    /// The four byte chunk type, e.g. `b"IDAT"`.
     const HEADER: &'static [u8; 4];
 
-    fn from_bytes(chunk_data: &'a [u8]) -> IResult<&'a [u8], Self>;
+    /// Interprets a chunk's data, without its length, type and CRC.
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError>;
+    /// Serializes the chunk, including its length, type and CRC.
     fn to_bytes(&self) -> Self::Output;
 }
@@ -1,22 +1,52 @@
-use super::{crc::calculate_crc, ParseableChunk};
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::DecodeError;
 use nom::{
+    combinator::all_consuming,
     number::complete::{be_u32, u8},
     sequence::tuple,
-    IResult,
 };
 
+/// The intended pixel size or aspect ratio of the image.
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct pHYsChunk {
-    _x_axis_ppu: u32,
-    _y_axis_ppu: u32,
-    _unit_specifier: u8,
+    x_axis_ppu: u32,
+    y_axis_ppu: u32,
+    unit_specifier: u8,
+}
+impl pHYsChunk {
+    /// Creates a pHYs chunk from the number of pixels per unit along each axis.
+    pub fn new(x_axis_ppu: u32, y_axis_ppu: u32, unit: Unit) -> Self {
+        Self {
+            x_axis_ppu,
+            y_axis_ppu,
+            unit_specifier: unit as u8,
+        }
+    }
+
+    /// Pixels per unit along the x axis.
+    pub fn x_axis_ppu(&self) -> u32 {
+        self.x_axis_ppu
+    }
+
+    /// Pixels per unit along the y axis.
+    pub fn y_axis_ppu(&self) -> u32 {
+        self.y_axis_ppu
+    }
+
+    /// The unit both axes are measured in.
+    pub fn unit(&self) -> Unit {
+        self.unit_specifier.into()
+    }
 }
 
-#[derive(Debug)]
-enum Unit {
-    Unknown,
-    Meter,
+/// The unit of a [`pHYsChunk`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    /// No unit, the chunk only gives the pixel aspect ratio.
+    Unknown = 0,
+    /// Pixels per meter.
+    Meter = 1,
 }
 impl From<u8> for Unit {
     fn from(value: u8) -> Self {
@@ -33,26 +63,24 @@ impl<'a> ParseableChunk<'a> for pHYsChunk {
 
     const HEADER: &'static [u8; 4] = b"pHYs";
 
-    fn from_bytes(chunk_data: &'a [u8]) -> IResult<&[u8], Self> {
-        let (rest, (_x_axis_ppu, _y_axis_ppu, _unit_specifier)) =
-            tuple((be_u32, be_u32, u8))(chunk_data)?;
-        Ok((
-            rest,
-            pHYsChunk {
-                _x_axis_ppu,
-                _y_axis_ppu,
-                _unit_specifier,
-            },
-        ))
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (_, (x_axis_ppu, y_axis_ppu, unit_specifier)) =
+            all_consuming(tuple((be_u32, be_u32, u8)))(chunk_data)
+                .map_err(malformed(Self::HEADER))?;
+        Ok(pHYsChunk {
+            x_axis_ppu,
+            y_axis_ppu,
+            unit_specifier,
+        })
     }
 
     fn to_bytes(&self) -> Self::Output {
         let mut bytes: Vec<u8> = Vec::with_capacity(21);
         bytes.extend(&[0, 0, 0, 9]);
         bytes.extend(Self::HEADER);
-        bytes.extend(&self._x_axis_ppu.to_be_bytes());
-        bytes.extend(&self._y_axis_ppu.to_be_bytes());
-        bytes.push(self._unit_specifier);
+        bytes.extend(&self.x_axis_ppu.to_be_bytes());
+        bytes.extend(&self.y_axis_ppu.to_be_bytes());
+        bytes.push(self.unit_specifier);
         bytes.extend(calculate_crc(bytes[4..].iter().copied()).to_be_bytes());
         bytes
     }
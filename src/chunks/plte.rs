@@ -1,16 +1,30 @@
-use super::ParseableChunk;
-use nom::{bytes::complete::take, combinator::map, multi::count, IResult};
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::DecodeError;
+use nom::{bytes::complete::take, combinator::map, multi::count};
 
-#[derive(Debug)]
-pub(crate) struct Entry(pub u8, pub u8, pub u8);
+/// A palette color, as red, green and blue samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PaletteEntry(pub u8, pub u8, pub u8);
 
+/// The palette of an indexed color image, or a suggested palette for other color types.
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PLTEChunk {
-    colors: Vec<Entry>,
+    colors: Vec<PaletteEntry>,
 }
 impl PLTEChunk {
-    pub(crate) fn get_color(&self, index: u8) -> Option<&Entry> {
+    /// Creates a palette from its colors. A valid palette holds between 1 and 256 of them.
+    pub fn new(colors: Vec<PaletteEntry>) -> Self {
+        Self { colors }
+    }
+
+    /// The colors in the palette, in index order.
+    pub fn colors(&self) -> &[PaletteEntry] {
+        &self.colors
+    }
+
+    /// The color at `index`, if the palette is that long.
+    pub fn get_color(&self, index: u8) -> Option<&PaletteEntry> {
         self.colors.get(index as usize)
     }
 }
@@ -19,16 +33,31 @@ impl<'a> ParseableChunk<'a> for PLTEChunk {
 
     const HEADER: &'static [u8; 4] = b"PLTE";
 
-    fn from_bytes(chunk_data: &'a [u8]) -> IResult<&'a [u8], Self> {
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
         let entry_count = chunk_data.len() / 3;
-        let (rest, entries) = count(
-            map(take(3usize), |i: &[u8]| Entry(i[0], i[1], i[2])),
+        // A palette holds between 1 and 256 entries of exactly 3 bytes each.
+        if !chunk_data.len().is_multiple_of(3) || !(1..=256).contains(&entry_count) {
+            return Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            });
+        }
+        let (_, entries) = count(
+            map(take(3usize), |i: &[u8]| PaletteEntry(i[0], i[1], i[2])),
             entry_count,
-        )(chunk_data)?;
-        Ok((rest, PLTEChunk { colors: entries }))
+        )(chunk_data)
+        .map_err(malformed(Self::HEADER))?;
+        Ok(PLTEChunk { colors: entries })
     }
 
     fn to_bytes(&self) -> Self::Output {
-        unimplemented!()
+        let len = self.colors.len() as u32 * 3;
+        let mut bytes = len.to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        for PaletteEntry(red, green, blue) in self.colors.iter() {
+            bytes.extend([*red, *green, *blue]);
+        }
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
     }
 }
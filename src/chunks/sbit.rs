@@ -0,0 +1,92 @@
+use super::{crc::calculate_crc, ParseableChunk};
+use crate::error::DecodeError;
+
+/// The number of bits that were significant in the original samples, for images stored at a
+/// higher bit depth than the data they came from.
+///
+/// The layout depends on the image's color type. Indexed color images give the significant bits
+/// of their palette's red, green and blue values.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct sBITChunk {
+    significant_bits: SignificantBits,
+}
+impl sBITChunk {
+    /// Creates an sBIT chunk with the given significant bits.
+    pub fn new(significant_bits: SignificantBits) -> Self {
+        Self { significant_bits }
+    }
+
+    /// The significant bits of each channel.
+    pub fn significant_bits(&self) -> SignificantBits {
+        self.significant_bits
+    }
+}
+impl<'a> ParseableChunk<'a> for sBITChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"sBIT";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let significant_bits = match *chunk_data {
+            [grey] => SignificantBits::Grey(grey),
+            [grey, alpha] => SignificantBits::GreyAlpha(grey, alpha),
+            [red, green, blue] => SignificantBits::Rgb(red, green, blue),
+            [red, green, blue, alpha] => SignificantBits::Rgba(red, green, blue, alpha),
+            _ => {
+                return Err(DecodeError::MalformedChunk {
+                    chunk_type: *Self::HEADER,
+                })
+            }
+        };
+        if chunk_data.contains(&0) {
+            return Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            });
+        }
+        Ok(Self { significant_bits })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let data = match self.significant_bits {
+            SignificantBits::Grey(grey) => vec![grey],
+            SignificantBits::GreyAlpha(grey, alpha) => vec![grey, alpha],
+            SignificantBits::Rgb(red, green, blue) => vec![red, green, blue],
+            SignificantBits::Rgba(red, green, blue, alpha) => vec![red, green, blue, alpha],
+        };
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        bytes.extend(data);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
+
+/// The significant bits of each channel in an [`sBITChunk`], in the layout for the image's
+/// color type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SignificantBits {
+    /// For greyscale images.
+    Grey(u8),
+    /// For greyscale images with alpha.
+    GreyAlpha(u8, u8),
+    /// For truecolor and indexed color images.
+    Rgb(u8, u8, u8),
+    /// For truecolor images with alpha.
+    Rgba(u8, u8, u8, u8),
+}
+impl SignificantBits {
+    /// The significant bits of a pixel's red, green, blue and alpha channels. Grey is used for
+    /// all three colors, and the alpha is `None` for layouts without it.
+    pub(crate) fn channels(&self) -> [Option<u8>; 4] {
+        match *self {
+            Self::Grey(grey) => [Some(grey), Some(grey), Some(grey), None],
+            Self::GreyAlpha(grey, alpha) => [Some(grey), Some(grey), Some(grey), Some(alpha)],
+            Self::Rgb(red, green, blue) => [Some(red), Some(green), Some(blue), None],
+            Self::Rgba(red, green, blue, alpha) => {
+                [Some(red), Some(green), Some(blue), Some(alpha)]
+            }
+        }
+    }
+}
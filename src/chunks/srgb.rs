@@ -0,0 +1,65 @@
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::DecodeError;
+use nom::{combinator::all_consuming, number::complete::u8};
+
+/// Marks the image's samples as being in the sRGB color space.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct sRGBChunk {
+    rendering_intent: RenderingIntent,
+}
+impl sRGBChunk {
+    /// Creates an sRGB chunk with the given rendering intent.
+    pub fn new(rendering_intent: RenderingIntent) -> Self {
+        Self { rendering_intent }
+    }
+
+    /// How colors outside the display's gamut should be handled.
+    pub fn rendering_intent(&self) -> RenderingIntent {
+        self.rendering_intent
+    }
+}
+impl<'a> ParseableChunk<'a> for sRGBChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"sRGB";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (_, intent) = all_consuming(u8)(chunk_data).map_err(malformed(Self::HEADER))?;
+        let rendering_intent = match intent {
+            0 => RenderingIntent::Perceptual,
+            1 => RenderingIntent::RelativeColorimetric,
+            2 => RenderingIntent::Saturation,
+            3 => RenderingIntent::AbsoluteColorimetric,
+            _ => {
+                return Err(DecodeError::MalformedChunk {
+                    chunk_type: *Self::HEADER,
+                })
+            }
+        };
+        Ok(Self { rendering_intent })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = vec![0, 0, 0, 1];
+        bytes.extend(Self::HEADER);
+        bytes.push(self.rendering_intent as u8);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
+
+/// The ICC rendering intents, which say how colors should be mapped to a display's gamut.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RenderingIntent {
+    /// For images that prefer good adaptation to the display over accurate colors, like
+    /// photographs.
+    Perceptual = 0,
+    /// For images that need exact colors, like logos.
+    RelativeColorimetric = 1,
+    /// For images that prefer saturated colors over accurate hues, like charts.
+    Saturation = 2,
+    /// For images that need absolute colors, like proofs of output on another device.
+    AbsoluteColorimetric = 3,
+}
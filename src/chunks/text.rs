@@ -0,0 +1,133 @@
+use super::{crc::calculate_crc, malformed, ParseableChunk};
+use crate::error::{DecodeError, EncodeError};
+use nom::{
+    bytes::complete::{tag, take_till},
+    sequence::terminated,
+    IResult,
+};
+
+/// A keyword and text, stored uncompressed in Latin-1.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct tEXtChunk {
+    keyword: String,
+    text: String,
+}
+impl tEXtChunk {
+    /// Creates a tEXt chunk, failing if the keyword isn't valid or either string can't be
+    /// represented in Latin-1.
+    pub fn new(keyword: &str, text: &str) -> Result<Self, EncodeError> {
+        check_keyword(keyword)?;
+        check_latin1(text)?;
+        Ok(Self {
+            keyword: keyword.to_owned(),
+            text: text.to_owned(),
+        })
+    }
+
+    /// The keyword, which says what the text is, e.g. `"Title"`.
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    /// The text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+impl<'a> ParseableChunk<'a> for tEXtChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"tEXt";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let (text, keyword) = parse_keyword(chunk_data).map_err(malformed(Self::HEADER))?;
+        if text.contains(&0) {
+            return Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            });
+        }
+        Ok(Self {
+            keyword: keyword_from_bytes(keyword, Self::HEADER)?,
+            text: decode_latin1(text),
+        })
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut data = encode_latin1(&self.keyword);
+        data.push(0);
+        data.extend(encode_latin1(&self.text));
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        bytes.extend(data);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
+
+/// A piece of textual metadata, from any of the tEXt, zTXt and iTXt chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEntry<'a> {
+    /// The keyword, which says what the text is, e.g. `"Title"`.
+    pub keyword: &'a str,
+    /// The text.
+    pub text: &'a str,
+    /// The language the text is in, only given by iTXt chunks.
+    pub language_tag: Option<&'a str>,
+    /// The keyword translated to the text's language, only given by iTXt chunks.
+    pub translated_keyword: Option<&'a str>,
+}
+
+/// Splits the null terminated keyword off the front of a text chunk's data.
+pub(crate) fn parse_keyword(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    terminated(take_till(|b| b == 0), tag([0]))(input)
+}
+
+/// Decodes and validates a keyword read from a `chunk_type` chunk.
+pub(crate) fn keyword_from_bytes(
+    keyword: &[u8],
+    chunk_type: &[u8; 4],
+) -> Result<String, DecodeError> {
+    let keyword = decode_latin1(keyword);
+    check_keyword(&keyword).map_err(|_| DecodeError::MalformedChunk {
+        chunk_type: *chunk_type,
+    })?;
+    Ok(keyword)
+}
+
+/// Keywords are 1 to 79 printable Latin-1 characters, without leading, trailing or consecutive
+/// spaces.
+pub(crate) fn check_keyword(keyword: &str) -> Result<(), EncodeError> {
+    let printable = keyword
+        .chars()
+        .all(|c| matches!(c as u32, 32..=126 | 161..=255));
+    if !printable
+        || !(1..=79).contains(&keyword.chars().count())
+        || keyword.starts_with(' ')
+        || keyword.ends_with(' ')
+        || keyword.contains("  ")
+    {
+        return Err(EncodeError::InvalidKeyword(keyword.to_owned()));
+    }
+    Ok(())
+}
+
+/// Latin-1 text can hold any character up to U+00FF, except null.
+pub(crate) fn check_latin1(text: &str) -> Result<(), EncodeError> {
+    if text.chars().all(|c| matches!(c as u32, 1..=255)) {
+        Ok(())
+    } else {
+        Err(EncodeError::NotLatin1)
+    }
+}
+
+/// Latin-1 characters are the first 256 Unicode code points, so each byte maps to one `char`.
+pub(crate) fn decode_latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| *b as char).collect()
+}
+
+/// Encodes text that has already been checked with [`check_latin1`] or [`check_keyword`].
+pub(crate) fn encode_latin1(text: &str) -> Vec<u8> {
+    text.chars().map(|c| c as u8).collect()
+}
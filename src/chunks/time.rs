@@ -0,0 +1,138 @@
+use time::{util, Date, Month, OffsetDateTime, PrimitiveDateTime, Time, UtcOffset};
+
+use super::{crc::calculate_crc, ParseableChunk};
+use crate::error::{DecodeError, EncodeError};
+
+/// When the image was last modified, in UTC.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct tIMEChunk {
+    year: u16,
+    month: u8,
+    day: u8,
+    hour: u8,
+    minute: u8,
+    second: u8,
+}
+impl tIMEChunk {
+    /// Creates a tIME chunk, failing with [`EncodeError::InvalidTime`] if the date doesn't
+    /// exist or the time is out of range. The second can be 60 to allow for leap seconds.
+    pub fn new(
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    ) -> Result<Self, EncodeError> {
+        let chunk = Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        };
+        if !chunk.is_valid() {
+            return Err(EncodeError::InvalidTime);
+        }
+        Ok(chunk)
+    }
+
+    /// Creates a tIME chunk holding `date_time` converted to UTC, failing with
+    /// [`EncodeError::InvalidTime`] if the year is negative. Fractions of a second are dropped.
+    pub fn from_date_time(date_time: OffsetDateTime) -> Result<Self, EncodeError> {
+        let date_time = date_time.to_offset(UtcOffset::UTC);
+        Ok(Self {
+            year: date_time
+                .year()
+                .try_into()
+                .map_err(|_| EncodeError::InvalidTime)?,
+            month: date_time.month() as u8,
+            day: date_time.day(),
+            hour: date_time.hour(),
+            minute: date_time.minute(),
+            second: date_time.second(),
+        })
+    }
+
+    /// The time as a UTC date and time, or `None` if the year is past what
+    /// [`OffsetDateTime`] can hold. A leap second becomes the second before it.
+    pub fn date_time(&self) -> Option<OffsetDateTime> {
+        let month = Month::try_from(self.month).ok()?;
+        let date = Date::from_calendar_date(self.year.into(), month, self.day).ok()?;
+        let time = Time::from_hms(self.hour, self.minute, self.second.min(59)).ok()?;
+        Some(PrimitiveDateTime::new(date, time).assume_utc())
+    }
+
+    /// The year, e.g. 2024.
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// The month, from 1 to 12.
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// The day of the month, from 1.
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// The hour, from 0 to 23.
+    pub fn hour(&self) -> u8 {
+        self.hour
+    }
+
+    /// The minute, from 0 to 59.
+    pub fn minute(&self) -> u8 {
+        self.minute
+    }
+
+    /// The second, from 0 to 60.
+    pub fn second(&self) -> u8 {
+        self.second
+    }
+
+    fn is_valid(&self) -> bool {
+        let leap_year = util::is_leap_year(self.year.into());
+        let days_in_month = match self.month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap_year => 29,
+            2 => 28,
+            _ => return false,
+        };
+        (1..=days_in_month).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second <= 60
+    }
+}
+impl<'a> ParseableChunk<'a> for tIMEChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"tIME";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        let malformed = DecodeError::MalformedChunk {
+            chunk_type: *Self::HEADER,
+        };
+        let [year_high, year_low, month, day, hour, minute, second] = *chunk_data else {
+            return Err(malformed);
+        };
+        let year = u16::from_be_bytes([year_high, year_low]);
+        Self::new(year, month, day, hour, minute, second).map_err(|_| malformed)
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut bytes = vec![0, 0, 0, 7];
+        bytes.extend(Self::HEADER);
+        bytes.extend(self.year.to_be_bytes());
+        bytes.extend([self.month, self.day, self.hour, self.minute, self.second]);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
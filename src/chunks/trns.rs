@@ -1,23 +1,54 @@
-use super::ParseableChunk;
-use nom::IResult;
+use super::{crc::calculate_crc, ParseableChunk};
+use crate::error::DecodeError;
 
+/// Transparency information for images without an alpha channel.
+///
+/// For greyscale and truecolor images it holds a single color that is fully transparent, for
+/// indexed color images it holds an alpha value for each palette entry. How its data is
+/// interpreted depends on the image's color type, so the accessors can fail.
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct tRNSChunk<'a> {
     inner: &'a [u8],
 }
 impl<'a> tRNSChunk<'a> {
-    pub(crate) fn as_greyscale(&self) -> u16 {
-        u16::from_be_bytes(self.inner[0..2].try_into().unwrap())
+    /// Creates a tRNS chunk from its raw data.
+    pub fn new(inner: &'a [u8]) -> Self {
+        Self { inner }
     }
-    pub(crate) fn as_truecolor(&self) -> (u16, u16, u16) {
-        (
-            u16::from_be_bytes(self.inner[0..2].try_into().unwrap()),
-            u16::from_be_bytes(self.inner[2..4].try_into().unwrap()),
-            u16::from_be_bytes(self.inner[4..6].try_into().unwrap()),
-        )
+
+    /// The chunk's raw data.
+    pub fn data(&self) -> &'a [u8] {
+        self.inner
+    }
+
+    /// The transparent grey sample of a greyscale image.
+    pub fn as_greyscale(&self) -> Result<u16, DecodeError> {
+        match self.inner {
+            &[high, low] => Ok(u16::from_be_bytes([high, low])),
+            _ => Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            }),
+        }
     }
-    pub(crate) fn as_palette(&self, index: u8) -> u8 {
+
+    /// The transparent red, green and blue samples of a truecolor image.
+    pub fn as_truecolor(&self) -> Result<(u16, u16, u16), DecodeError> {
+        match self.inner {
+            &[r_high, r_low, g_high, g_low, b_high, b_low] => Ok((
+                u16::from_be_bytes([r_high, r_low]),
+                u16::from_be_bytes([g_high, g_low]),
+                u16::from_be_bytes([b_high, b_low]),
+            )),
+            _ => Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            }),
+        }
+    }
+
+    /// The alpha value of palette entry `index` of an indexed color image. Entries past the end
+    /// of the chunk are fully opaque.
+    pub fn as_palette(&self, index: u8) -> u8 {
         *self.inner.get(index as usize).unwrap_or(&255)
     }
 }
@@ -26,11 +57,17 @@ impl<'a> ParseableChunk<'a> for tRNSChunk<'a> {
 
     const HEADER: &'static [u8; 4] = b"tRNS";
 
-    fn from_bytes(chunk_data: &'a [u8]) -> IResult<&'a [u8], Self> {
-        Ok((&chunk_data[0..0], tRNSChunk { inner: chunk_data }))
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        Ok(tRNSChunk { inner: chunk_data })
     }
 
     fn to_bytes(&self) -> Self::Output {
-        unimplemented!()
+        let len = self.inner.len() as u32;
+        let mut bytes = len.to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        bytes.extend(self.inner);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
     }
 }
@@ -0,0 +1,101 @@
+use std::fmt;
+
+use super::{
+    crc::calculate_crc,
+    decompress_chunk_data, malformed,
+    text::{
+        check_keyword, check_latin1, decode_latin1, encode_latin1, keyword_from_bytes,
+        parse_keyword,
+    },
+    ParseableChunk,
+};
+use crate::{
+    encoding::compress_zlib,
+    error::{DecodeError, EncodeError},
+    limits::DecodeLimits,
+    options::CompressionStrategy,
+};
+use nom::number::complete::u8;
+
+/// A keyword and text, with the text stored zlib compressed in Latin-1.
+#[allow(non_camel_case_types)]
+#[derive(Clone, PartialEq, Eq)]
+pub struct zTXtChunk {
+    keyword: String,
+    text: String,
+    /// The compressed text as stored in the file, kept so the chunk is written back unchanged.
+    compressed: Vec<u8>,
+}
+impl zTXtChunk {
+    /// Creates a zTXt chunk, failing if the keyword isn't valid or either string can't be
+    /// represented in Latin-1.
+    pub fn new(keyword: &str, text: &str) -> Result<Self, EncodeError> {
+        check_keyword(keyword)?;
+        check_latin1(text)?;
+        Ok(Self {
+            keyword: keyword.to_owned(),
+            text: text.to_owned(),
+            compressed: compress_zlib(&encode_latin1(text), 8, CompressionStrategy::Default),
+        })
+    }
+
+    /// The keyword, which says what the text is, e.g. `"Title"`.
+    pub fn keyword(&self) -> &str {
+        &self.keyword
+    }
+
+    /// The decompressed text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Interprets a zTXt chunk's data, failing if the text decompresses to more than `limit`
+    /// bytes.
+    pub(crate) fn from_bytes_with_limit(
+        chunk_data: &[u8],
+        limit: usize,
+    ) -> Result<Self, DecodeError> {
+        let (rest, keyword) = parse_keyword(chunk_data).map_err(malformed(Self::HEADER))?;
+        let (compressed, compression_method) = u8(rest).map_err(malformed(Self::HEADER))?;
+        if compression_method != 0 {
+            return Err(DecodeError::MalformedChunk {
+                chunk_type: *Self::HEADER,
+            });
+        }
+        let text = decompress_chunk_data(compressed, limit, "max_text_chunk_size", Self::HEADER)?;
+        Ok(Self {
+            keyword: keyword_from_bytes(keyword, Self::HEADER)?,
+            text: decode_latin1(&text),
+            compressed: compressed.to_vec(),
+        })
+    }
+}
+impl<'a> ParseableChunk<'a> for zTXtChunk {
+    type Output = Vec<u8>;
+
+    const HEADER: &'static [u8; 4] = b"zTXt";
+
+    fn from_bytes(chunk_data: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::from_bytes_with_limit(chunk_data, DecodeLimits::new().max_text_chunk_size)
+    }
+
+    fn to_bytes(&self) -> Self::Output {
+        let mut data = encode_latin1(&self.keyword);
+        data.extend([0, 0]);
+        data.extend(&self.compressed);
+        let mut bytes = (data.len() as u32).to_be_bytes().to_vec();
+        bytes.extend(Self::HEADER);
+        bytes.extend(data);
+        let crc = calculate_crc(bytes[4..].iter().copied()).to_be_bytes();
+        bytes.extend(crc);
+        bytes
+    }
+}
+impl fmt::Debug for zTXtChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("zTXtChunk")
+            .field("keyword", &self.keyword)
+            .field("text", &self.text)
+            .finish_non_exhaustive()
+    }
+}
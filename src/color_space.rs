@@ -0,0 +1,49 @@
+use crate::chunks::{cHRMChunk, iCCPChunk, Chunk, RenderingIntent};
+
+/// The color space an image's samples are in, as given by whichever of its color space chunks
+/// takes precedence. See [`PNG::color_space`](crate::PNG::color_space).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorSpace<'a> {
+    /// An embedded ICC profile, from an iCCP chunk.
+    IccProfile(&'a iCCPChunk),
+    /// sRGB, from an sRGB chunk.
+    Srgb(RenderingIntent),
+    /// A gamma and/or chromaticities, from gAMA and cHRM chunks.
+    Calibrated {
+        /// The gamma from the gAMA chunk, if there is one.
+        gamma: Option<f64>,
+        /// The cHRM chunk, if there is one.
+        chromaticities: Option<&'a cHRMChunk>,
+    },
+    /// The image has no color space chunks, so its color space isn't known. Most viewers
+    /// assume sRGB.
+    Unknown,
+}
+impl<'a> ColorSpace<'a> {
+    /// Picks the authoritative color space from `chunks`. An iCCP chunk overrides an sRGB chunk,
+    /// and either overrides gAMA and cHRM.
+    pub(crate) fn from_chunks(chunks: &'a [Chunk<'a>]) -> Self {
+        let mut icc_profile = None;
+        let mut srgb = None;
+        let mut gamma = None;
+        let mut chromaticities = None;
+        for chunk in chunks {
+            match chunk {
+                Chunk::iCCP(chunk) => icc_profile = icc_profile.or(Some(chunk)),
+                Chunk::sRGB(chunk) => srgb = srgb.or(Some(chunk.rendering_intent())),
+                Chunk::gAMA(chunk) => gamma = gamma.or(Some(chunk.gamma())),
+                Chunk::cHRM(chunk) => chromaticities = chromaticities.or(Some(chunk)),
+                _ => {}
+            }
+        }
+        match (icc_profile, srgb) {
+            (Some(profile), _) => Self::IccProfile(profile),
+            (None, Some(intent)) => Self::Srgb(intent),
+            (None, None) if gamma.is_some() || chromaticities.is_some() => Self::Calibrated {
+                gamma,
+                chromaticities,
+            },
+            (None, None) => Self::Unknown,
+        }
+    }
+}
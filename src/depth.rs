@@ -0,0 +1,303 @@
+//! Conversions between 16 bit samples and lower bit depths.
+//!
+//! [`Pixel`]s always hold 16 bit samples. Samples of a lower bit depth are scaled up so that
+//! their largest value becomes `u16::MAX`, and [`unscale`] reverses this exactly: for every bit
+//! depth and every sample `s` at that depth, `unscale(scale(s, depth), depth) == s`. Samples
+//! that don't fall exactly on a value of the lower bit depth are rounded to the nearest one, or
+//! dithered with [`reduce_depth`].
+//!
+//! ```
+//! use simple_png::depth::{scale, unscale};
+//!
+//! assert_eq!(scale(0x80, 8), 0x8080);
+//! assert_eq!(unscale(0x8080, 8), 0x80);
+//! assert_eq!(unscale(0x807f, 8), 0x80);
+//! ```
+
+use std::borrow::Cow;
+
+use crate::{
+    chunks::ihdr::{ColorType, IHDRChunk},
+    pixel::Pixel,
+};
+
+/// Scales a sample of `from_bit_depth` bits, from 1 to 16, up to 16 bits, rounding to the
+/// nearest value. Bit depths that divide 16 evenly give the sample's bits repeated.
+///
+/// # Panics
+///
+/// Panics if `from_bit_depth` isn't from 1 to 16.
+pub fn scale(value: u16, from_bit_depth: u8) -> u16 {
+    assert!(
+        (1..=16).contains(&from_bit_depth),
+        "bit depth {from_bit_depth} isn't from 1 to 16"
+    );
+    match from_bit_depth {
+        16 => value,
+        1 | 2 | 4 | 8 => value * (u16::MAX / ((1 << from_bit_depth) - 1)),
+        _ => {
+            let max = (1u32 << from_bit_depth) - 1;
+            ((value as u32 * u16::MAX as u32 + max / 2) / max) as u16
+        }
+    }
+}
+
+/// The inverse of [`scale`], reducing a 16 bit sample to `to_bit_depth` bits, from 1 to 16, and
+/// rounding to the nearest value.
+///
+/// # Panics
+///
+/// Panics if `to_bit_depth` isn't from 1 to 16.
+pub fn unscale(value: u16, to_bit_depth: u8) -> u16 {
+    assert!(
+        (1..=16).contains(&to_bit_depth),
+        "bit depth {to_bit_depth} isn't from 1 to 16"
+    );
+    if to_bit_depth == 16 {
+        value
+    } else {
+        let max = (1u32 << to_bit_depth) - 1;
+        ((value as u32 * max + u16::MAX as u32 / 2) / u16::MAX as u32) as u16
+    }
+}
+
+/// How samples are spread over the values of a lower bit depth when they don't fall on one
+/// exactly. Samples that do are never changed, and with [`ErrorDiffusion`](Self::ErrorDiffusion)
+/// no rounding error is carried over to or past them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dither {
+    /// Every sample is rounded to the nearest value.
+    #[default]
+    None,
+    /// Samples are rounded up or down by comparing them to an 8x8 Bayer matrix, which gives a
+    /// regular pattern that stays the same from frame to frame.
+    Ordered,
+    /// The rounding error of each sample is carried over to its neighbours to the right and
+    /// below, using Floyd-Steinberg weights.
+    ErrorDiffusion,
+}
+
+/// Reduces `pixels`, `width` pixels to a row, to samples of `bit_depth` bits, from 1 to 16. The
+/// color channels are dithered as `dither` says and alpha is rounded, and the samples are left
+/// scaled up to 16 bits.
+///
+/// ```
+/// use simple_png::{depth::{reduce_depth, Dither}, Pixel};
+///
+/// let grey = Pixel::new(0x8000, 0x8000, 0x8000, u16::MAX);
+/// let mut pixels = vec![grey; 16];
+/// reduce_depth(&mut pixels, 4, 1, Dither::Ordered);
+/// let white = pixels.iter().filter(|p| p.red == u16::MAX).count();
+/// assert_eq!(white, 8);
+/// ```
+///
+/// # Panics
+///
+/// Panics if `bit_depth` isn't from 1 to 16.
+pub fn reduce_depth(pixels: &mut [Pixel], width: u32, bit_depth: u8, dither: Dither) {
+    assert!(
+        (1..=16).contains(&bit_depth),
+        "bit depth {bit_depth} isn't from 1 to 16"
+    );
+    let mut ditherer = Ditherer::new(dither, width, bit_depth, false);
+    for row in pixels.chunks_mut(width.max(1) as usize) {
+        ditherer.dither_row(row);
+        for pixel in row {
+            pixel.alpha = scale(unscale(pixel.alpha, bit_depth), bit_depth);
+        }
+    }
+}
+
+/// The pixels of an image, `width` pixels to a row, dithered for encoding as `header`. Grey
+/// images are dithered by their luma. Indexed color images and 16 bit images are left as they
+/// are.
+pub(crate) fn dither_image<'p>(
+    pixels: &'p [Pixel],
+    width: u32,
+    header: &IHDRChunk,
+    dither: Dither,
+) -> Cow<'p, [Pixel]> {
+    if dither == Dither::None
+        || header.bit_depth == 16
+        || header.color_type == ColorType::IndexedColor
+    {
+        return Cow::Borrowed(pixels);
+    }
+    let mut pixels = pixels.to_vec();
+    let mut ditherer = Ditherer::for_header(dither, width, header);
+    for row in pixels.chunks_mut(width.max(1) as usize) {
+        ditherer.dither_row(row);
+    }
+    Cow::Owned(pixels)
+}
+
+/// Dithers an image one row at a time, top to bottom.
+pub(crate) struct Ditherer {
+    dither: Dither,
+    bit_depth: u8,
+    grey: bool,
+    y: usize,
+    /// The error carried into each pixel of the current and the next row, in sixteenths, with an
+    /// extra entry on either side.
+    errors: Vec<[i32; 3]>,
+    next_errors: Vec<[i32; 3]>,
+}
+impl Ditherer {
+    fn new(dither: Dither, width: u32, bit_depth: u8, grey: bool) -> Self {
+        let errors = match dither {
+            // Rows are at least a pixel wide, so every pixel of an image that's 0 wide is a row.
+            Dither::ErrorDiffusion => vec![[0; 3]; width.max(1) as usize + 2],
+            _ => vec![],
+        };
+        Self {
+            dither,
+            bit_depth,
+            grey,
+            y: 0,
+            next_errors: errors.clone(),
+            errors,
+        }
+    }
+
+    /// A ditherer for rows `width` pixels wide that are encoded as `header`. Indexed color and
+    /// 16 bit rows aren't changed.
+    pub(crate) fn for_header(dither: Dither, width: u32, header: &IHDRChunk) -> Self {
+        let dither = match header.color_type {
+            _ if header.bit_depth == 16 => Dither::None,
+            ColorType::IndexedColor => Dither::None,
+            _ => dither,
+        };
+        let grey = matches!(
+            header.color_type,
+            ColorType::Greyscale | ColorType::GreyscaleWithAlpha
+        );
+        Self::new(dither, width, header.bit_depth, grey)
+    }
+
+    /// Dithers the color channels of the next row. Rows after the first should be the same
+    /// width.
+    pub(crate) fn dither_row(&mut self, row: &mut [Pixel]) {
+        if self.dither == Dither::None {
+            return;
+        }
+        let max = (1u64 << self.bit_depth) - 1;
+        for (x, pixel) in row.iter_mut().enumerate() {
+            if self.grey {
+                let luma = pixel.luma();
+                (pixel.red, pixel.green, pixel.blue) = (luma, luma, luma);
+            }
+            let mut samples = [pixel.red, pixel.green, pixel.blue];
+            match self.dither {
+                Dither::None => {}
+                Dither::Ordered => {
+                    let threshold = BAYER_MATRIX[self.y % 8][x % 8] as u64 * 2 + 1;
+                    for sample in &mut samples {
+                        let value = (*sample as u64 * max * 128 + threshold * u16::MAX as u64)
+                            / (u16::MAX as u64 * 128);
+                        *sample = scale(value.min(max) as u16, self.bit_depth);
+                    }
+                }
+                Dither::ErrorDiffusion => {
+                    for (channel, sample) in samples.iter_mut().enumerate() {
+                        // Exact samples stay as they are, and the error carried to them is
+                        // dropped rather than passed on.
+                        if scale(unscale(*sample, self.bit_depth), self.bit_depth) == *sample {
+                            continue;
+                        }
+                        let wanted = *sample as i32 + self.errors[x + 1][channel] / 16;
+                        let clamped = wanted.clamp(0, u16::MAX as i32) as u16;
+                        *sample = scale(unscale(clamped, self.bit_depth), self.bit_depth);
+                        let error = wanted - *sample as i32;
+                        self.errors[x + 2][channel] += error * 7;
+                        self.next_errors[x][channel] += error * 3;
+                        self.next_errors[x + 1][channel] += error * 5;
+                        self.next_errors[x + 2][channel] += error;
+                    }
+                }
+            }
+            [pixel.red, pixel.green, pixel.blue] = samples;
+        }
+        if self.dither == Dither::ErrorDiffusion {
+            std::mem::swap(&mut self.errors, &mut self.next_errors);
+            self.next_errors.fill([0; 3]);
+        }
+        self.y += 1;
+    }
+}
+
+const BAYER_MATRIX: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+#[cfg(test)]
+mod tests {
+    use super::{reduce_depth, scale, unscale, Dither};
+    use crate::pixel::Pixel;
+
+    #[test]
+    fn scaling_round_trips_at_every_bit_depth() {
+        for bit_depth in 1..=16 {
+            let max = (1u32 << bit_depth) - 1;
+            for value in 0..=max as u16 {
+                let scaled = scale(value, bit_depth);
+                assert_eq!(unscale(scaled, bit_depth), value, "{bit_depth} bits");
+                let exact = value as f64 * u16::MAX as f64 / max as f64;
+                assert!((scaled as f64 - exact).abs() <= 0.5, "{bit_depth} bits");
+            }
+            assert_eq!(scale(max as u16, bit_depth), u16::MAX);
+        }
+    }
+
+    #[test]
+    fn unscaling_rounds_to_the_nearest_value() {
+        for bit_depth in 1..=16 {
+            let max = (1u32 << bit_depth) - 1;
+            for sample in 0..=u16::MAX {
+                let exact = sample as f64 * max as f64 / u16::MAX as f64;
+                let value = unscale(sample, bit_depth) as f64;
+                assert!((value - exact).abs() <= 0.5, "{bit_depth} bits, {sample}");
+            }
+        }
+    }
+
+    #[test]
+    fn rows_are_at_least_a_pixel_wide() {
+        let grey = Pixel::new(0x8000, 0x8000, 0x8000, u16::MAX);
+        for dither in [Dither::None, Dither::Ordered, Dither::ErrorDiffusion] {
+            let mut pixels = vec![grey; 4];
+            reduce_depth(&mut pixels, 0, 1, dither);
+            let mut column = vec![grey; 4];
+            reduce_depth(&mut column, 1, 1, dither);
+            assert_eq!(pixels, column, "{dither:?}");
+        }
+    }
+
+    #[test]
+    fn exact_samples_are_never_dithered() {
+        let grey = |sample| Pixel::new(sample, sample, sample, u16::MAX);
+        // 0x5555 and 0xAAAA are exact at 2 bits, 0x7000 and 0x9000 fall between them.
+        let row = [0x7000, 0x9000, 0x5555, 0x9000, 0x7000, 0xAAAA, 0x9000].map(grey);
+        for dither in [Dither::None, Dither::Ordered, Dither::ErrorDiffusion] {
+            let mut pixels = [row; 8].concat();
+            reduce_depth(&mut pixels, row.len() as u32, 2, dither);
+            for (pixel, original) in pixels.iter().zip(row.iter().cycle()) {
+                if matches!(original.red, 0x5555 | 0xAAAA) {
+                    assert_eq!(pixel, original, "{dither:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic = "bit depth 0 isn't from 1 to 16"]
+    fn bit_depths_are_checked() {
+        scale(0, 0);
+    }
+}
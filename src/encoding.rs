@@ -0,0 +1,370 @@
+use std::{
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap, HashSet},
+};
+
+use miniz_oxide::deflate::core::{
+    self, compress_to_output, create_comp_flags_from_zip_params, CompressorOxide, TDEFLFlush,
+    TDEFLStatus,
+};
+
+use crate::{
+    chunks::{
+        ihdr::{ColorType, IHDRChunk, Interlacing},
+        plte::{PLTEChunk, PaletteEntry},
+    },
+    depth::{scale, unscale},
+    error::EncodeError,
+    interlacing::Adam7Iter,
+    options::CompressionStrategy,
+    pixel::Pixel,
+    scanlines::image_data_length,
+};
+
+/// Unfiltered image data, ready for filtering, plus the chunks needed to interpret it.
+pub(crate) struct SerializedImage {
+    /// Scanlines, each starting with a placeholder filter type byte.
+    pub(crate) data: Vec<u8>,
+    pub(crate) palette: Option<PLTEChunk>,
+    /// The contents of a tRNS chunk, if the image needs one.
+    pub(crate) transparency: Option<Vec<u8>>,
+}
+
+/// Converts `pixels` into scanlines laid out as `header` describes.
+///
+/// Samples are reduced to the header's bit depth with rounding. Alpha is dropped for color types
+/// without an alpha channel, unless every pixel is either fully opaque or fully transparent and
+/// all transparent pixels share one color, in which case that color is stored in a tRNS chunk.
+pub(crate) fn serialize_pixels(
+    pixels: &[Pixel],
+    header: &IHDRChunk,
+) -> Result<SerializedImage, EncodeError> {
+    serialize_frame(pixels, header, pixels)
+}
+
+/// Converts `pixels` into scanlines like [`serialize_pixels`], with the palette and tRNS chunk
+/// picked for `all_pixels` instead, so that they can be shared by every frame of an animation.
+/// `all_pixels` has to include `pixels`.
+pub(crate) fn serialize_frame(
+    pixels: &[Pixel],
+    header: &IHDRChunk,
+    all_pixels: &[Pixel],
+) -> Result<SerializedImage, EncodeError> {
+    let bit_depth = header.bit_depth;
+    let (ordered, line_widths) = scanline_order(pixels, header);
+    let pixels = &ordered[..];
+    let mut writer = SampleWriter::new(bit_depth, image_data_length(header));
+    let mut palette = None;
+    let mut transparency = None;
+    match header.color_type {
+        ColorType::Greyscale => {
+            let key = color_key(all_pixels, |p| [unscale(p.luma(), bit_depth)]);
+            for line in split_lines(pixels, &line_widths) {
+                writer.start_scanline();
+                for p in line {
+                    let [grey] = key
+                        .filter(|_| p.alpha == 0)
+                        .unwrap_or([unscale(p.luma(), bit_depth)]);
+                    writer.push(grey);
+                }
+            }
+            transparency = key.map(|[grey]| grey.to_be_bytes().to_vec());
+        }
+        ColorType::Truecolor => {
+            let samples = |p: &Pixel| {
+                [
+                    unscale(p.red, bit_depth),
+                    unscale(p.green, bit_depth),
+                    unscale(p.blue, bit_depth),
+                ]
+            };
+            let key = color_key(all_pixels, samples);
+            for line in split_lines(pixels, &line_widths) {
+                writer.start_scanline();
+                for p in line {
+                    let rgb = key.filter(|_| p.alpha == 0).unwrap_or(samples(p));
+                    rgb.into_iter().for_each(|s| writer.push(s));
+                }
+            }
+            transparency = key.map(|rgb| rgb.into_iter().flat_map(u16::to_be_bytes).collect());
+        }
+        ColorType::IndexedColor => {
+            let (colors, lookup) = build_palette(all_pixels, bit_depth)?;
+            for line in split_lines(pixels, &line_widths) {
+                writer.start_scanline();
+                line.iter()
+                    .for_each(|p| writer.push(lookup[&palette_color(p)] as u16));
+            }
+            // Trailing opaque entries can be left out of the tRNS chunk.
+            let alphas: Vec<u8> = colors.iter().map(|c| c[3]).collect();
+            let used_alphas = alphas
+                .iter()
+                .rposition(|a| *a != u8::MAX)
+                .map_or(0, |i| i + 1);
+            if used_alphas > 0 {
+                transparency = Some(alphas[..used_alphas].to_vec());
+            }
+            palette = Some(PLTEChunk::new(
+                colors
+                    .iter()
+                    .map(|c| PaletteEntry(c[0], c[1], c[2]))
+                    .collect(),
+            ));
+        }
+        ColorType::GreyscaleWithAlpha => {
+            for line in split_lines(pixels, &line_widths) {
+                writer.start_scanline();
+                for p in line {
+                    writer.push(unscale(p.luma(), bit_depth));
+                    writer.push(unscale(p.alpha, bit_depth));
+                }
+            }
+        }
+        ColorType::TruecolorWithAlpha => {
+            for line in split_lines(pixels, &line_widths) {
+                writer.start_scanline();
+                for p in line {
+                    writer.push(unscale(p.red, bit_depth));
+                    writer.push(unscale(p.green, bit_depth));
+                    writer.push(unscale(p.blue, bit_depth));
+                    writer.push(unscale(p.alpha, bit_depth));
+                }
+            }
+        }
+    }
+    Ok(SerializedImage {
+        data: writer.bytes,
+        palette,
+        transparency,
+    })
+}
+
+/// Converts the palette indices of an indexed color image into scanlines laid out as `header`
+/// describes. The indices have to fit in the header's bit depth.
+pub(crate) fn serialize_indices(indices: &[u8], header: &IHDRChunk) -> Vec<u8> {
+    let (ordered, line_widths) = scanline_order(indices, header);
+    let mut writer = SampleWriter::new(header.bit_depth, image_data_length(header));
+    for line in split_lines(&ordered, &line_widths) {
+        writer.start_scanline();
+        line.iter().for_each(|index| writer.push(*index as u16));
+    }
+    writer.bytes
+}
+
+/// Orders `pixels` the way their scanlines are stored, which for interlaced images means pass by
+/// pass, along with the number of pixels in each scanline.
+fn scanline_order<'a, T: Copy>(pixels: &'a [T], header: &IHDRChunk) -> (Cow<'a, [T]>, Vec<usize>) {
+    let (width, height) = (header.width as usize, header.height as usize);
+    match header.interlace_method {
+        Interlacing::None => (Cow::Borrowed(pixels), vec![width; height]),
+        Interlacing::Adam7 => {
+            let mut ordered = Vec::with_capacity(pixels.len());
+            let mut line_widths = Vec::new();
+            for sub_image in Adam7Iter::new(width, height) {
+                line_widths.extend(std::iter::repeat_n(sub_image.width, sub_image.height));
+                ordered.extend(sub_image.pixel_indices.map(|i| pixels[i]));
+            }
+            (Cow::Owned(ordered), line_widths)
+        }
+    }
+}
+
+/// Splits `items` into consecutive scanlines of the given widths.
+fn split_lines<'a, T>(
+    mut items: &'a [T],
+    line_widths: &'a [usize],
+) -> impl Iterator<Item = &'a [T]> + 'a {
+    line_widths.iter().map(move |width| {
+        let (line, rest) = items.split_at(*width);
+        items = rest;
+        line
+    })
+}
+
+/// Picks the color type and bit depth that store `pixels` in the fewest bits per pixel without
+/// losing any information.
+pub(crate) fn optimal_format(pixels: &[Pixel]) -> (ColorType, u8) {
+    let samples = |p: &Pixel| [p.red, p.green, p.blue, p.alpha];
+    let is_grey = pixels.iter().all(|p| p.red == p.green && p.green == p.blue);
+    let depth = if pixels.iter().flat_map(samples).all(|s| is_exact(s, 8)) {
+        8
+    } else {
+        16
+    };
+    let opaque = pixels.iter().all(|p| p.alpha == u16::MAX);
+    let (color_type, bit_depth) = if is_grey {
+        let grey_depth = [1, 2, 4, 8, 16]
+            .into_iter()
+            .find(|d| pixels.iter().all(|p| is_exact(p.red, *d)))
+            .unwrap_or(16);
+        if opaque || color_key(pixels, |p| [p.red]).is_some() {
+            (ColorType::Greyscale, grey_depth)
+        } else {
+            (ColorType::GreyscaleWithAlpha, depth)
+        }
+    } else if opaque || color_key(pixels, |p| [p.red, p.green, p.blue]).is_some() {
+        (ColorType::Truecolor, depth)
+    } else {
+        (ColorType::TruecolorWithAlpha, depth)
+    };
+    if depth == 8 {
+        let mut colors = HashSet::new();
+        let fits_palette = pixels.iter().all(|p| {
+            colors.insert(samples(p));
+            colors.len() <= 256
+        });
+        let bits_per_pixel = color_type.channel_count() * bit_depth;
+        if let Some(index_depth) = [1, 2, 4, 8]
+            .into_iter()
+            .find(|d| fits_palette && colors.len() <= 1 << d)
+            .filter(|d| *d < bits_per_pixel)
+        {
+            return (ColorType::IndexedColor, index_depth);
+        }
+    }
+    (color_type, bit_depth)
+}
+
+/// Whether a 16 bit sample survives being reduced to `bit_depth` bits and scaled back up.
+fn is_exact(sample: u16, bit_depth: u8) -> bool {
+    scale(unscale(sample, bit_depth), bit_depth) == sample
+}
+
+/// Compresses `data` into a zlib stream.
+pub(crate) fn compress_zlib(data: &[u8], level: u8, strategy: CompressionStrategy) -> Vec<u8> {
+    let mut compressor = zlib_compressor(level, strategy);
+    let mut output = Vec::with_capacity(data.len() / 2);
+    let (status, _) = compress_to_output(&mut compressor, data, TDEFLFlush::Finish, |bytes| {
+        output.extend_from_slice(bytes);
+        true
+    });
+    assert_eq!(status, TDEFLStatus::Done, "compressing to a Vec can't fail");
+    output
+}
+
+/// A compressor that writes a zlib stream with the given settings.
+pub(crate) fn zlib_compressor(level: u8, strategy: CompressionStrategy) -> Box<CompressorOxide> {
+    // A positive window size makes miniz_oxide write a zlib header and checksum.
+    let flags = create_comp_flags_from_zip_params(
+        level.into(),
+        1,
+        core::CompressionStrategy::from(strategy) as i32,
+    );
+    Box::new(CompressorOxide::new(flags))
+}
+
+/// Finds the single color, as produced by `samples`, shared by every fully transparent pixel.
+///
+/// Returns `None` if there are no transparent pixels, if any pixel is partially transparent, if
+/// the transparent pixels differ in color, or if an opaque pixel has the same color.
+fn color_key<const N: usize>(
+    pixels: &[Pixel],
+    samples: impl Fn(&Pixel) -> [u16; N],
+) -> Option<[u16; N]> {
+    let mut key = None;
+    for p in pixels.iter() {
+        match p.alpha {
+            0 => {
+                let color = samples(p);
+                if *key.get_or_insert(color) != color {
+                    return None;
+                }
+            }
+            u16::MAX => {}
+            _ => return None,
+        }
+    }
+    let key = key?;
+    pixels
+        .iter()
+        .all(|p| p.alpha == 0 || samples(p) != key)
+        .then_some(key)
+}
+
+/// A palette's colors, and a map from each color to its index.
+type Palette = (Vec<[u8; 4]>, HashMap<[u8; 4], u8>);
+
+/// Collects the distinct 8 bit RGBA colors of `pixels`, in order of first appearance, along with
+/// a map from each color to its index in that list.
+fn build_palette(pixels: &[Pixel], bit_depth: u8) -> Result<Palette, EncodeError> {
+    let max = 1usize << bit_depth;
+    let mut colors = Vec::new();
+    let mut lookup = HashMap::new();
+    for p in pixels.iter() {
+        let color = palette_color(p);
+        if let Entry::Vacant(entry) = lookup.entry(color) {
+            if colors.len() == max {
+                return Err(EncodeError::TooManyColors { max });
+            }
+            entry.insert(colors.len() as u8);
+            colors.push(color);
+        }
+    }
+    Ok((colors, lookup))
+}
+
+/// The 8 bit RGBA color a pixel is stored as in a palette.
+fn palette_color(p: &Pixel) -> [u8; 4] {
+    [p.red, p.green, p.blue, p.alpha].map(|s| unscale(s, 8) as u8)
+}
+
+/// Packs samples of any bit depth into bytes, most significant bits first.
+struct SampleWriter {
+    bytes: Vec<u8>,
+    bit_depth: u8,
+    bit_offset: u8,
+}
+impl SampleWriter {
+    fn new(bit_depth: u8, capacity: usize) -> Self {
+        Self {
+            bytes: Vec::with_capacity(capacity),
+            bit_depth,
+            bit_offset: 0,
+        }
+    }
+
+    /// Starts a new scanline with a placeholder filter type. Scanlines always start on a byte
+    /// boundary, so any unused bits at the end of the previous scanline are left as zero.
+    fn start_scanline(&mut self) {
+        self.bit_offset = 0;
+        self.bytes.push(0);
+    }
+
+    fn push(&mut self, sample: u16) {
+        match self.bit_depth {
+            16 => self.bytes.extend(sample.to_be_bytes()),
+            8 => self.bytes.push(sample as u8),
+            depth => {
+                if self.bit_offset == 0 {
+                    self.bytes.push(0);
+                }
+                let shift = 8 - self.bit_offset - depth;
+                *self
+                    .bytes
+                    .last_mut()
+                    .expect("a partially filled byte should exist") |= (sample as u8) << shift;
+                self.bit_offset = (self.bit_offset + depth) % 8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SampleWriter;
+
+    #[test]
+    fn sample_writer_packs_sub_byte_samples() {
+        let mut writer = SampleWriter::new(2, 0);
+        writer.start_scanline();
+        [3, 0, 1, 2, 3].into_iter().for_each(|s| writer.push(s));
+        writer.start_scanline();
+        writer.push(1);
+        assert_eq!(writer.bytes, [0, 0b11000110, 0b11000000, 0, 0b01000000]);
+
+        let mut writer = SampleWriter::new(16, 0);
+        writer.start_scanline();
+        writer.push(0x1234);
+        assert_eq!(writer.bytes, [0, 0x12, 0x34]);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::chunks::ihdr::ColorType;
+use std::fmt;
+
+/// Everything that can go wrong while decoding a PNG.
+///
+/// Decoding never panics on malformed input, every problem is reported through one of these
+/// variants instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeError {
+    /// The data doesn't start with the 8 byte PNG signature.
+    InvalidSignature,
+    /// The data ended in the middle of a chunk, or before an IEND chunk was found.
+    UnexpectedEndOfFile {
+        /// Byte offset of the chunk that couldn't be read in full.
+        offset: usize,
+    },
+    /// A chunk's CRC doesn't match the CRC calculated from its type and data.
+    CrcMismatch {
+        /// The four byte chunk type, e.g. `b"IDAT"`.
+        chunk_type: [u8; 4],
+        /// Byte offset of the start of the chunk (its length field).
+        offset: usize,
+        /// The CRC stored in the file.
+        expected: u32,
+        /// The CRC calculated from the chunk.
+        actual: u32,
+    },
+    /// The first chunk wasn't IHDR, or no IHDR chunk was found at all.
+    MissingHeader,
+    /// A field of the IHDR chunk holds a value the spec doesn't allow.
+    InvalidHeaderField {
+        /// Name of the offending field, e.g. `"bit_depth"`.
+        field: &'static str,
+        /// The value found in the file.
+        value: u32,
+    },
+    /// A chunk's data couldn't be interpreted, e.g. because it has the wrong length.
+    MalformedChunk {
+        /// The four byte chunk type, e.g. `b"PLTE"`.
+        chunk_type: [u8; 4],
+    },
+    /// An indexed color image has no PLTE chunk.
+    MissingPalette,
+    /// A pixel refers to a palette entry that doesn't exist.
+    PaletteIndexOutOfRange(u8),
+    /// No IDAT chunk was found.
+    MissingImageData,
+    /// The compressed image data ended before the zlib stream was complete.
+    TruncatedZlibStream,
+    /// The compressed image data isn't a valid zlib stream.
+    InvalidZlibStream,
+    /// The decompressed image data doesn't have the size the header describes.
+    UnexpectedImageDataLength {
+        /// Number of bytes needed for the image described by the header.
+        expected: usize,
+        /// Number of bytes that were decompressed.
+        actual: usize,
+    },
+    /// A scanline starts with a filter type other than 0 through 4.
+    UnknownFilterType(u8),
+    /// The reconstructed image data couldn't be split into pixels.
+    InvalidImageData,
+    /// Interlaced images can't be decoded a row at a time by
+    /// [`StreamingDecoder`](crate::StreamingDecoder).
+    UnsupportedInterlacing,
+    /// Reading from the underlying reader failed.
+    Io(std::io::ErrorKind),
+    /// The image goes over one of the [`DecodeLimits`](crate::DecodeLimits).
+    LimitExceeded {
+        /// Name of the limit, e.g. `"max_pixels"`.
+        limit: &'static str,
+    },
+    /// An fcTL or fdAT chunk is out of order. Sequence numbers start at 0 and count up by one
+    /// with every fcTL and fdAT chunk.
+    InvalidSequenceNumber {
+        /// The sequence number that should have come next.
+        expected: u32,
+        /// The sequence number found in the file.
+        actual: u32,
+    },
+    /// The animation chunks of an animated PNG don't describe a valid animation.
+    InvalidAnimation {
+        /// What's wrong, e.g. `"frame lies outside the canvas"`.
+        reason: &'static str,
+    },
+    /// An iCCP chunk's ICC profile is truncated or otherwise can't be read.
+    InvalidIccProfile,
+    /// Exif data is truncated or otherwise can't be read.
+    InvalidExif,
+    /// An iCCP chunk's ICC profile is valid, but can't be used to convert the image to sRGB.
+    UnsupportedIccProfile {
+        /// Why the profile isn't supported, e.g. `"LUT-based profiles"`.
+        reason: &'static str,
+    },
+    /// The stride given to [`PNG::decode_into`](crate::PNG::decode_into) is shorter than a row
+    /// of pixels.
+    StrideTooSmall {
+        /// The number of bytes in a row of pixels.
+        expected: usize,
+        /// The stride that was given.
+        actual: usize,
+    },
+    /// The image's color type can't be decoded this way, e.g. an image that isn't indexed
+    /// passed to [`IndexedImage::decode`](crate::IndexedImage::decode).
+    UnsupportedColorType(ColorType),
+    /// The buffer given to [`PNG::decode_into`](crate::PNG::decode_into) can't hold the image.
+    BufferTooSmall {
+        /// The number of bytes the image needs.
+        expected: usize,
+        /// The length of the buffer.
+        actual: usize,
+    },
+}
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSignature => write!(f, "data doesn't start with the PNG signature"),
+            Self::UnexpectedEndOfFile { offset } => {
+                write!(f, "data ended unexpectedly in chunk at byte {offset}")
+            }
+            Self::CrcMismatch {
+                chunk_type,
+                offset,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "CRC mismatch in {} chunk at byte {offset}: expected {expected:#010x}, got {actual:#010x}",
+                String::from_utf8_lossy(chunk_type)
+            ),
+            Self::MissingHeader => write!(f, "IHDR chunk must be the first chunk"),
+            Self::InvalidHeaderField { field, value } => {
+                write!(f, "IHDR field {field} has invalid value {value}")
+            }
+            Self::MalformedChunk { chunk_type } => write!(
+                f,
+                "{} chunk is malformed",
+                String::from_utf8_lossy(chunk_type)
+            ),
+            Self::MissingPalette => write!(f, "a PLTE chunk is needed for indexed color images"),
+            Self::PaletteIndexOutOfRange(index) => {
+                write!(f, "palette index {index} is out of range")
+            }
+            Self::MissingImageData => write!(f, "no IDAT chunk was found"),
+            Self::TruncatedZlibStream => write!(f, "compressed image data is truncated"),
+            Self::InvalidZlibStream => write!(f, "compressed image data is invalid"),
+            Self::UnexpectedImageDataLength { expected, actual } => write!(
+                f,
+                "expected {expected} bytes of image data, but found {actual}"
+            ),
+            Self::UnknownFilterType(filter) => write!(f, "filter type {filter} is unknown"),
+            Self::InvalidImageData => write!(f, "image data couldn't be split into pixels"),
+            Self::UnsupportedInterlacing => {
+                write!(f, "interlaced images can't be decoded row by row")
+            }
+            Self::Io(kind) => write!(f, "reading image data failed: {kind}"),
+            Self::LimitExceeded { limit } => write!(f, "image exceeds the {limit} limit"),
+            Self::InvalidSequenceNumber { expected, actual } => write!(
+                f,
+                "expected animation chunk with sequence number {expected}, but found {actual}"
+            ),
+            Self::InvalidAnimation { reason } => write!(f, "animation is invalid: {reason}"),
+            Self::InvalidIccProfile => write!(f, "ICC profile is invalid"),
+            Self::InvalidExif => write!(f, "Exif data is invalid"),
+            Self::UnsupportedIccProfile { reason } => {
+                write!(f, "ICC profile is unsupported: {reason} can't be converted to sRGB")
+            }
+            Self::StrideTooSmall { expected, actual } => write!(
+                f,
+                "stride of {actual} bytes is shorter than a row of {expected} bytes"
+            ),
+            Self::UnsupportedColorType(color_type) => {
+                write!(f, "images with color type {color_type:?} can't be decoded this way")
+            }
+            Self::BufferTooSmall { expected, actual } => write!(
+                f,
+                "image needs a buffer of {expected} bytes, but it has {actual}"
+            ),
+        }
+    }
+}
+impl std::error::Error for DecodeError {}
+
+/// Everything that can go wrong while encoding a PNG.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EncodeError {
+    /// The header's bit depth isn't allowed for its color type.
+    InvalidBitDepth {
+        /// The color type from the header.
+        color_type: ColorType,
+        /// The bit depth from the header.
+        bit_depth: u8,
+    },
+    /// Fewer pixels were supplied than the header's dimensions require.
+    NotEnoughPixels {
+        /// width * height of the image.
+        expected: usize,
+        /// Number of pixels supplied.
+        actual: usize,
+    },
+    /// The image has more distinct colors than an indexed image of the header's bit depth can
+    /// hold.
+    TooManyColors {
+        /// The most colors the palette can hold.
+        max: usize,
+    },
+    /// [`StreamingEncoder`](crate::StreamingEncoder) can't write indexed images, because the
+    /// palette has to be written before any rows are known.
+    UnsupportedColorType(ColorType),
+    /// [`StreamingEncoder`](crate::StreamingEncoder) can't write interlaced images, because
+    /// every pass needs rows from the whole image.
+    UnsupportedInterlacing,
+    /// More rows were supplied than the header's height.
+    TooManyRows {
+        /// The height of the image.
+        height: u32,
+    },
+    /// An image was finished before all of its rows were supplied.
+    MissingRows {
+        /// The height of the image.
+        expected: u32,
+        /// Number of rows supplied.
+        actual: u32,
+    },
+    /// Writing to the underlying writer failed.
+    Io(std::io::ErrorKind),
+    /// A text chunk keyword isn't 1 to 79 printable Latin-1 characters without leading, trailing
+    /// or consecutive spaces.
+    InvalidKeyword(String),
+    /// Text for a tEXt or zTXt chunk has characters that can't be represented in Latin-1.
+    NotLatin1,
+    /// An iTXt chunk's language tag isn't a valid RFC 3066 language tag.
+    InvalidLanguageTag(String),
+    /// An [`IndexedImage`](crate::IndexedImage) has an index past the end of its palette.
+    PaletteIndexOutOfRange(u8),
+    /// Data for an eXIf chunk doesn't start with a TIFF header.
+    InvalidExif,
+    /// A tIME chunk's date doesn't exist or its time is out of range.
+    InvalidTime,
+    /// An [`Animation`](crate::animation::Animation)'s frames can't be stored as an animated PNG.
+    InvalidAnimation {
+        /// What's wrong, e.g. `"frame lies outside the canvas"`.
+        reason: &'static str,
+    },
+}
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidBitDepth {
+                color_type,
+                bit_depth,
+            } => write!(f, "bit depth {bit_depth} isn't allowed for {color_type:?}"),
+            Self::NotEnoughPixels { expected, actual } => {
+                write!(
+                    f,
+                    "expected {expected} pixels, but only {actual} were supplied"
+                )
+            }
+            Self::TooManyColors { max } => {
+                write!(f, "image has more than {max} colors and can't be indexed")
+            }
+            Self::UnsupportedColorType(color_type) => {
+                write!(f, "{color_type:?} images can't be encoded row by row")
+            }
+            Self::UnsupportedInterlacing => {
+                write!(f, "interlaced images can't be encoded row by row")
+            }
+            Self::TooManyRows { height } => {
+                write!(f, "more than {height} rows were supplied")
+            }
+            Self::MissingRows { expected, actual } => write!(
+                f,
+                "expected {expected} rows, but only {actual} were supplied"
+            ),
+            Self::Io(kind) => write!(f, "writing image data failed: {kind}"),
+            Self::InvalidKeyword(keyword) => write!(f, "{keyword:?} isn't a valid keyword"),
+            Self::NotLatin1 => write!(f, "text can't be represented in Latin-1"),
+            Self::InvalidLanguageTag(tag) => write!(f, "{tag:?} isn't a valid language tag"),
+            Self::PaletteIndexOutOfRange(index) => {
+                write!(f, "palette index {index} is out of range")
+            }
+            Self::InvalidAnimation { reason } => write!(f, "animation is invalid: {reason}"),
+            Self::InvalidExif => write!(f, "Exif data doesn't start with a TIFF header"),
+            Self::InvalidTime => write!(f, "modification time is invalid"),
+        }
+    }
+}
+impl std::error::Error for EncodeError {}
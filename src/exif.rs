@@ -0,0 +1,332 @@
+use crate::{error::DecodeError, pixel::Pixel};
+
+const MAKE: u16 = 0x010f;
+const MODEL: u16 = 0x0110;
+const ORIENTATION: u16 = 0x0112;
+const X_RESOLUTION: u16 = 0x011a;
+const Y_RESOLUTION: u16 = 0x011b;
+const RESOLUTION_UNIT: u16 = 0x0128;
+const SOFTWARE: u16 = 0x0131;
+const DATE_TIME: u16 = 0x0132;
+
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const RATIONAL: u16 = 5;
+
+/// The tags from the first IFD of Exif data, like the data held by an
+/// [`eXIfChunk`](crate::chunks::eXIfChunk), that describe the image itself.
+///
+/// Tags that are missing or have a type the Exif specification doesn't allow for them are
+/// `None`. Every other tag, including the ones in the Exif and GPS sub-IFDs, is ignored.
+///
+/// ```
+/// use simple_png::{Exif, PNG};
+///
+/// let data = std::fs::read("tests/png-suite/exif2c08.png")?;
+/// let image = PNG::decode(&data)?;
+/// let exif = Exif::parse(image.exif().unwrap().data())?;
+/// assert_eq!(exif.x_resolution, Some((72, 1)));
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Exif {
+    /// The way the stored pixels have to be turned to display the right way up.
+    pub orientation: Option<Orientation>,
+    /// When the image was last changed, as `"YYYY:MM:DD HH:MM:SS"`.
+    pub date_time: Option<String>,
+    /// The manufacturer of the camera or scanner.
+    pub make: Option<String>,
+    /// The model of the camera or scanner.
+    pub model: Option<String>,
+    /// The software that made or last edited the image.
+    pub software: Option<String>,
+    /// Pixels per resolution unit along the x axis, as a numerator and denominator.
+    pub x_resolution: Option<(u32, u32)>,
+    /// Pixels per resolution unit along the y axis, as a numerator and denominator.
+    pub y_resolution: Option<(u32, u32)>,
+    /// The unit the resolution is measured in: 1 for none, 2 for inches and 3 for centimeters.
+    pub resolution_unit: Option<u16>,
+}
+impl Exif {
+    /// Reads the tags from Exif data, failing with [`DecodeError::InvalidExif`] if the first IFD
+    /// or the value of a tag is out of bounds.
+    pub fn parse(data: &[u8]) -> Result<Self, DecodeError> {
+        let reader = Reader::new(data)?;
+        let mut exif = Exif::default();
+        for entry in reader.first_ifd()? {
+            let Some(value) = reader.value(&entry)? else {
+                continue;
+            };
+            let string = || (entry.field_type == ASCII).then(|| ascii(value));
+            let short = || (entry.field_type == SHORT).then(|| reader.u16(value));
+            let rational = || {
+                (entry.field_type == RATIONAL).then(|| (reader.u32(value), reader.u32(&value[4..])))
+            };
+            match entry.tag {
+                ORIENTATION => exif.orientation = short().and_then(Orientation::from_tag),
+                DATE_TIME => exif.date_time = string(),
+                MAKE => exif.make = string(),
+                MODEL => exif.model = string(),
+                SOFTWARE => exif.software = string(),
+                X_RESOLUTION => exif.x_resolution = rational(),
+                Y_RESOLUTION => exif.y_resolution = rational(),
+                RESOLUTION_UNIT => exif.resolution_unit = short(),
+                _ => {}
+            }
+        }
+        Ok(exif)
+    }
+
+    /// Writes the tags that are set as big endian Exif data, which can be stored in an
+    /// [`eXIfChunk`](crate::chunks::eXIfChunk).
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let string = |s: &Option<String>| {
+            s.as_ref().map(|s| {
+                let value = [s.as_bytes(), &[0]].concat();
+                (ASCII, value.len() as u32, value)
+            })
+        };
+        let short = |v: Option<u16>| v.map(|v| (SHORT, 1, v.to_be_bytes().to_vec()));
+        let rational = |v: Option<(u32, u32)>| {
+            v.map(|(n, d)| (RATIONAL, 1, [n.to_be_bytes(), d.to_be_bytes()].concat()))
+        };
+        // Entries have to be sorted by tag.
+        let entries: Vec<_> = [
+            (MAKE, string(&self.make)),
+            (MODEL, string(&self.model)),
+            (ORIENTATION, short(self.orientation.map(|o| o as u16))),
+            (X_RESOLUTION, rational(self.x_resolution)),
+            (Y_RESOLUTION, rational(self.y_resolution)),
+            (RESOLUTION_UNIT, short(self.resolution_unit)),
+            (SOFTWARE, string(&self.software)),
+            (DATE_TIME, string(&self.date_time)),
+        ]
+        .into_iter()
+        .filter_map(|(tag, value)| Some((tag, value?)))
+        .collect();
+
+        let mut bytes = b"MM\0*".to_vec();
+        bytes.extend(8u32.to_be_bytes());
+        bytes.extend((entries.len() as u16).to_be_bytes());
+        // Values that don't fit in an entry go after the IFD.
+        let values_offset = bytes.len() + entries.len() * 12 + 4;
+        let mut values = vec![];
+        for (tag, (field_type, count, mut value)) in entries {
+            bytes.extend(tag.to_be_bytes());
+            bytes.extend(field_type.to_be_bytes());
+            bytes.extend(count.to_be_bytes());
+            if value.len() <= 4 {
+                value.resize(4, 0);
+                bytes.extend(value);
+            } else {
+                bytes.extend(((values_offset + values.len()) as u32).to_be_bytes());
+                values.extend(value);
+                // Every value starts on a word boundary.
+                if values.len() % 2 == 1 {
+                    values.push(0);
+                }
+            }
+        }
+        bytes.extend(0u32.to_be_bytes());
+        bytes.extend(values);
+        bytes
+    }
+}
+
+/// How the stored pixels have to be turned to display the right way up, from the Exif
+/// Orientation tag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Orientation {
+    /// The pixels are stored the right way up.
+    Normal = 1,
+    /// The pixels have to be flipped left to right.
+    FlipHorizontal = 2,
+    /// The pixels have to be turned 180 degrees.
+    Rotate180 = 3,
+    /// The pixels have to be flipped top to bottom.
+    FlipVertical = 4,
+    /// The pixels have to be flipped over the diagonal from the top left corner.
+    Transpose = 5,
+    /// The pixels have to be turned 90 degrees clockwise.
+    Rotate90 = 6,
+    /// The pixels have to be flipped over the diagonal from the top right corner.
+    Transverse = 7,
+    /// The pixels have to be turned 90 degrees anticlockwise.
+    Rotate270 = 8,
+}
+impl Orientation {
+    fn from_tag(value: u16) -> Option<Self> {
+        Some(match value {
+            1 => Self::Normal,
+            2 => Self::FlipHorizontal,
+            3 => Self::Rotate180,
+            4 => Self::FlipVertical,
+            5 => Self::Transpose,
+            6 => Self::Rotate90,
+            7 => Self::Transverse,
+            8 => Self::Rotate270,
+            _ => return None,
+        })
+    }
+
+    /// Whether turning the pixels swaps the image's width and height.
+    pub fn swaps_dimensions(self) -> bool {
+        matches!(
+            self,
+            Self::Transpose | Self::Rotate90 | Self::Transverse | Self::Rotate270
+        )
+    }
+
+    /// Turns the `width` by `height` pixels of an image the right way up.
+    pub(crate) fn apply(self, pixels: &[Pixel], width: u32, height: u32) -> Vec<Pixel> {
+        let (width, height) = (width as usize, height as usize);
+        let (new_width, new_height) = if self.swaps_dimensions() {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        let mut turned = Vec::with_capacity(pixels.len());
+        for y in 0..new_height {
+            for x in 0..new_width {
+                let (source_x, source_y) = match self {
+                    Self::Normal => (x, y),
+                    Self::FlipHorizontal => (width - 1 - x, y),
+                    Self::Rotate180 => (width - 1 - x, height - 1 - y),
+                    Self::FlipVertical => (x, height - 1 - y),
+                    Self::Transpose => (y, x),
+                    Self::Rotate90 => (y, height - 1 - x),
+                    Self::Transverse => (width - 1 - y, height - 1 - x),
+                    Self::Rotate270 => (width - 1 - y, x),
+                };
+                turned.push(pixels[source_y * width + source_x]);
+            }
+        }
+        turned
+    }
+}
+
+/// Sets the Orientation tag of Exif data to `orientation`, if it has one. Returns whether the
+/// tag was found.
+pub(crate) fn set_orientation(data: &mut [u8], orientation: Orientation) -> bool {
+    let Ok(reader) = Reader::new(data) else {
+        return false;
+    };
+    let Ok(entries) = reader.first_ifd() else {
+        return false;
+    };
+    let little_endian = reader.little_endian;
+    let Some(entry) = entries
+        .iter()
+        .find(|entry| entry.tag == ORIENTATION && entry.field_type == SHORT && entry.count >= 1)
+    else {
+        return false;
+    };
+    let value = orientation as u16;
+    let bytes = if little_endian {
+        value.to_le_bytes()
+    } else {
+        value.to_be_bytes()
+    };
+    data[entry.value_offset..entry.value_offset + 2].copy_from_slice(&bytes);
+    true
+}
+
+/// An entry of an IFD.
+struct Entry {
+    tag: u16,
+    field_type: u16,
+    count: u32,
+    /// Where the entry's value, or the offset of its value if it doesn't fit, is in the data.
+    value_offset: usize,
+}
+
+/// Reads numbers from TIFF data in its byte order.
+struct Reader<'d> {
+    data: &'d [u8],
+    little_endian: bool,
+}
+impl<'d> Reader<'d> {
+    fn new(data: &'d [u8]) -> Result<Self, DecodeError> {
+        let little_endian = match data.get(..4) {
+            Some(b"II*\0") => true,
+            Some(b"MM\0*") => false,
+            _ => return Err(DecodeError::InvalidExif),
+        };
+        Ok(Self {
+            data,
+            little_endian,
+        })
+    }
+
+    fn first_ifd(&self) -> Result<Vec<Entry>, DecodeError> {
+        let offset = self.u32(self.bytes(4, 4)?) as usize;
+        let count = self.u16(self.bytes(offset, 2)?) as usize;
+        (0..count)
+            .map(|i| {
+                let start = offset + 2 + i * 12;
+                let entry = self.bytes(start, 12)?;
+                Ok(Entry {
+                    tag: self.u16(entry),
+                    field_type: self.u16(&entry[2..]),
+                    count: self.u32(&entry[4..]),
+                    value_offset: start + 8,
+                })
+            })
+            .collect()
+    }
+
+    /// The bytes of an entry's value, or `None` if its type isn't one the Exif specification
+    /// uses.
+    fn value(&self, entry: &Entry) -> Result<Option<&'d [u8]>, DecodeError> {
+        let size = match entry.field_type {
+            1 | 2 | 6 | 7 => 1,
+            3 | 8 => 2,
+            4 | 9 => 4,
+            5 | 10 => 8,
+            _ => return Ok(None),
+        };
+        let length = (entry.count as usize)
+            .checked_mul(size)
+            .ok_or(DecodeError::InvalidExif)?;
+        if length == 0 {
+            return Ok(None);
+        }
+        let offset = if length <= 4 {
+            entry.value_offset
+        } else {
+            self.u32(self.bytes(entry.value_offset, 4)?) as usize
+        };
+        self.bytes(offset, length).map(Some)
+    }
+
+    fn bytes(&self, offset: usize, length: usize) -> Result<&'d [u8], DecodeError> {
+        offset
+            .checked_add(length)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(DecodeError::InvalidExif)
+    }
+
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        }
+    }
+}
+
+/// An ASCII value up to its first NUL.
+fn ascii(value: &[u8]) -> String {
+    let end = value.iter().position(|b| *b == 0).unwrap_or(value.len());
+    String::from_utf8_lossy(&value[..end]).into_owned()
+}
@@ -1,20 +1,36 @@
-use anyhow::anyhow;
+use miniz_oxide::deflate::compress_to_vec;
 
 use crate::{
-    chunks::ihdr::{IHDRChunk, Interlacing},
+    chunks::ihdr::{ColorType, IHDRChunk, Interlacing},
+    error::DecodeError,
     interlacing::Adam7Iter,
     utils::div_ceil,
 };
 
-pub(crate) enum Filter {
+/// The filter types defined by the spec, applied to each byte of a scanline before compression.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// The byte is left as is.
     None,
+    /// Difference to the corresponding byte of the previous pixel.
     Sub,
+    /// Difference to the corresponding byte of the previous scanline.
     Up,
+    /// Difference to the average of the Sub and Up bytes.
     Average,
+    /// Difference to whichever of the Sub, Up and upper left bytes is closest to a linear
+    /// prediction from all three.
     Paeth,
 }
 impl Filter {
-    #[allow(unused)]
+    const ALL: [Filter; 5] = [
+        Filter::None,
+        Filter::Sub,
+        Filter::Up,
+        Filter::Average,
+        Filter::Paeth,
+    ];
+
     pub(crate) fn filter(&self, x: u8, a: u8, b: u8, c: u8) -> u8 {
         match self {
             Filter::None => x,
@@ -44,7 +60,7 @@ impl Filter {
     }
 }
 impl TryFrom<u8> for Filter {
-    type Error = anyhow::Error;
+    type Error = DecodeError;
     fn try_from(value: u8) -> Result<Self, Self::Error> {
         match value {
             0 => Ok(Self::None),
@@ -52,7 +68,7 @@ impl TryFrom<u8> for Filter {
             2 => Ok(Self::Up),
             3 => Ok(Self::Average),
             4 => Ok(Self::Paeth),
-            i => Err(anyhow!("Filter type {i} is unknown.")),
+            i => Err(DecodeError::UnknownFilterType(i)),
         }
     }
 }
@@ -71,7 +87,10 @@ fn paeth_predictor(a: u8, b: u8, c: u8) -> u8 {
     }
 }
 
-pub(crate) fn reconstruct_scanlines(image_data: &mut [u8], header: &IHDRChunk) {
+pub(crate) fn reconstruct_scanlines(
+    image_data: &mut [u8],
+    header: &IHDRChunk,
+) -> Result<(), DecodeError> {
     let pixel_width = header.color_type.channel_count() * header.bit_depth;
     match header.interlace_method {
         Interlacing::None => {
@@ -81,7 +100,7 @@ pub(crate) fn reconstruct_scanlines(image_data: &mut [u8], header: &IHDRChunk) {
                 scanline_length,
                 header.height as usize,
                 header.filter_width() as usize,
-            );
+            )?;
         }
         Interlacing::Adam7 => {
             let mut image_data_index = 0;
@@ -93,10 +112,11 @@ pub(crate) fn reconstruct_scanlines(image_data: &mut [u8], header: &IHDRChunk) {
                     scanline_length,
                     sub_image.height,
                     header.filter_width() as usize,
-                );
+                )?;
             }
         }
     };
+    Ok(())
 }
 
 fn inner_reconstruct_scanlines(
@@ -104,41 +124,76 @@ fn inner_reconstruct_scanlines(
     scanline_length: usize,
     line_count: usize,
     filter_width: usize,
-) -> usize {
-    assert!(image_data.len() % scanline_length == 0);
-    log::info!("{:?}", filter_width);
+) -> Result<usize, DecodeError> {
+    assert!(image_data.len().is_multiple_of(scanline_length));
 
-    // Handle first scanline as special case
-    log::info!("Scanline: {:?}", &image_data[0..scanline_length]);
-    let filter = Filter::try_from(image_data[0]).unwrap();
-    for b in image_data[1..(filter_width + 1)].iter_mut() {
-        *b = filter.reconstruct(*b, 0, 0, 0);
+    // The first scanline is reconstructed against a scanline of zeros.
+    let zeros = vec![0; scanline_length];
+    for i in 0..line_count {
+        let (done, rest) = image_data.split_at_mut(i * scanline_length);
+        let previous = match i {
+            0 => &zeros[..],
+            _ => &done[(i - 1) * scanline_length..],
+        };
+        reconstruct_scanline(&mut rest[..scanline_length], previous, filter_width)?;
     }
-    for i in (filter_width + 1)..scanline_length {
-        image_data[i] = filter.reconstruct(image_data[i], image_data[i - filter_width], 0, 0);
+    Ok(scanline_length * line_count)
+}
+
+/// Reverses the filter applied to `scanline`, given the already reconstructed `previous`
+/// scanline. Both include the filter type byte.
+pub(crate) fn reconstruct_scanline(
+    scanline: &mut [u8],
+    previous: &[u8],
+    filter_width: usize,
+) -> Result<(), DecodeError> {
+    let filter = Filter::try_from(scanline[0])?;
+    for j in 1..scanline.len() {
+        let (a, c) = if j > filter_width {
+            (scanline[j - filter_width], previous[j - filter_width])
+        } else {
+            (0, 0)
+        };
+        scanline[j] = filter.reconstruct(scanline[j], a, previous[j], c);
     }
-    log::info!("Scanline: {:?}", &image_data[0..scanline_length]);
+    Ok(())
+}
 
-    // Remaining scanlines
-    for i in 1..line_count {
-        let filter = Filter::try_from(image_data[i * scanline_length]).unwrap();
-        let (start, stop) = (i * scanline_length + 1, (i + 1) * scanline_length);
-        for j in start..(start + filter_width) {
-            image_data[j] =
-                filter.reconstruct(image_data[j], 0, image_data[j - scanline_length], 0);
-        }
-        for j in (start + filter_width)..stop {
-            let a = image_data[j - filter_width];
-            let b = image_data[j - scanline_length];
-            let c = image_data[j - filter_width - scanline_length];
-            image_data[j] = filter.reconstruct(image_data[j], a, b, c);
+/// How the encoder picks a filter for each scanline.
+///
+/// The spec recommends [`Filter::None`] for indexed images and images with less than 8 bits
+/// per sample, and an adaptive strategy for everything else.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FilterStrategy {
+    /// Use the same filter for every scanline.
+    Fixed(Filter),
+    /// Pick the filter whose output has the smallest sum of absolute values, treating each byte
+    /// as signed. This is the heuristic libpng uses.
+    #[default]
+    MinimumSum,
+    /// Pick the filter whose output has the lowest Shannon entropy.
+    Entropy,
+    /// Compress the scanline with every filter and pick whichever is smallest. Slow, but usually
+    /// gives the smallest output.
+    BruteForce,
+}
+
+impl FilterStrategy {
+    /// The strategy the spec recommends for images described by `header`.
+    pub(crate) fn recommended(header: &IHDRChunk) -> Self {
+        if matches!(header.color_type, ColorType::IndexedColor) || header.bit_depth < 8 {
+            Self::Fixed(Filter::None)
+        } else {
+            Self::MinimumSum
         }
-        // log::info!("Scanline: {:?}", &image_data[(i * scanline_length)..stop]);
     }
-    scanline_length * line_count
 }
 
-pub(crate) fn filter_scanlines(image_data: &mut [u8], header: &IHDRChunk) {
+pub(crate) fn filter_scanlines(
+    image_data: &mut [u8],
+    header: &IHDRChunk,
+    strategy: FilterStrategy,
+) {
     let pixel_width = header.color_type.channel_count() * header.bit_depth;
     match header.interlace_method {
         Interlacing::None => {
@@ -148,6 +203,7 @@ pub(crate) fn filter_scanlines(image_data: &mut [u8], header: &IHDRChunk) {
                 scanline_length,
                 header.height as usize,
                 header.filter_width() as usize,
+                strategy,
             );
         }
         Interlacing::Adam7 => {
@@ -160,6 +216,7 @@ pub(crate) fn filter_scanlines(image_data: &mut [u8], header: &IHDRChunk) {
                     scanline_length,
                     sub_image.height,
                     header.filter_width() as usize,
+                    strategy,
                 );
             }
         }
@@ -171,41 +228,144 @@ fn inner_filter_scanlines(
     scanline_length: usize,
     line_count: usize,
     filter_width: usize,
+    strategy: FilterStrategy,
 ) -> usize {
-    assert!(image_data.len() % scanline_length == 0);
+    assert!(image_data.len().is_multiple_of(scanline_length));
 
-    // Start from the bottom
-    for i in (1..line_count).rev() {
-        image_data[i * scanline_length] = Filter::Sub as u8;
-        let (start, stop) = (i * scanline_length + 1, (i + 1) * scanline_length);
-        // log::info!("Scanline: {:?}", &image_data[(i * scanline_length)..stop]);
-        for j in start..(start + filter_width) {
-            image_data[j] = Filter::Sub.filter(image_data[j], 0, image_data[j - filter_width], 0);
+    let mut filter = ScanlineFilter::new(scanline_length, filter_width, strategy);
+    for line in image_data
+        .chunks_exact_mut(scanline_length)
+        .take(line_count)
+    {
+        filter.filter(line);
+    }
+    scanline_length * line_count
+}
+
+/// Filters consecutive scanlines of one image, or one pass of an interlaced image, in place.
+pub(crate) struct ScanlineFilter {
+    filter_width: usize,
+    strategy: FilterStrategy,
+    // Filtering needs the unfiltered previous scanline, so keep a copy of it around. The first
+    // scanline is filtered against a scanline of zeros.
+    previous: Vec<u8>,
+    current: Vec<u8>,
+    candidate: Vec<u8>,
+    previous_filtered: Vec<u8>,
+}
+impl ScanlineFilter {
+    pub(crate) fn new(
+        scanline_length: usize,
+        filter_width: usize,
+        strategy: FilterStrategy,
+    ) -> Self {
+        Self {
+            filter_width,
+            strategy,
+            previous: vec![0; scanline_length],
+            current: vec![0; scanline_length],
+            candidate: vec![0; scanline_length],
+            previous_filtered: Vec::with_capacity(scanline_length),
         }
-        for j in ((start + filter_width)..stop).rev() {
-            let a = image_data[j - filter_width];
-            let b = image_data[j - scanline_length];
-            let c = image_data[j - filter_width - scanline_length];
-            image_data[j] = Filter::Sub.filter(image_data[j], a, b, c);
+    }
+
+    /// Filters the next scanline, which must include its filter type byte, in place.
+    pub(crate) fn filter(&mut self, line: &mut [u8]) {
+        let Self {
+            filter_width,
+            strategy,
+            previous,
+            current,
+            candidate,
+            previous_filtered,
+        } = self;
+        let filter_width = *filter_width;
+        current.copy_from_slice(line);
+        let filter = match *strategy {
+            FilterStrategy::Fixed(filter) => filter,
+            FilterStrategy::MinimumSum => Filter::ALL
+                .into_iter()
+                .min_by_key(|filter| {
+                    filter_scanline(*filter, current, previous, filter_width, candidate);
+                    candidate[1..]
+                        .iter()
+                        .map(|b| (*b as i8).unsigned_abs() as u64)
+                        .sum::<u64>()
+                })
+                .expect("there are always filters to pick from"),
+            FilterStrategy::Entropy => {
+                Filter::ALL
+                    .into_iter()
+                    .map(|filter| {
+                        filter_scanline(filter, current, previous, filter_width, candidate);
+                        (filter, entropy(&candidate[1..]))
+                    })
+                    .min_by(|(_, x), (_, y)| x.total_cmp(y))
+                    .expect("there are always filters to pick from")
+                    .0
+            }
+            FilterStrategy::BruteForce => Filter::ALL
+                .into_iter()
+                .min_by_key(|filter| {
+                    filter_scanline(*filter, current, previous, filter_width, candidate);
+                    // Compress alongside the previous scanline so repeats across scanlines,
+                    // which Up and Paeth are good at producing, are taken into account.
+                    let mut context = previous_filtered.clone();
+                    context.extend_from_slice(candidate);
+                    compress_to_vec(&context, 6).len()
+                })
+                .expect("there are always filters to pick from"),
+        };
+        filter_scanline(filter, current, previous, filter_width, line);
+        if *strategy == FilterStrategy::BruteForce {
+            previous_filtered.clear();
+            previous_filtered.extend_from_slice(line);
         }
+        std::mem::swap(previous, current);
     }
+}
 
-    // log::info!("Scanline: {:?}", &image_data[0..scanline_length]);
-    // "First" scanline can be treated differently
-    for i in ((filter_width + 1)..scanline_length).rev() {
-        image_data[i] = Filter::Sub.filter(image_data[i], image_data[i - filter_width], 0, 0)
+/// Filters `current` against `previous`, writing the filter type and filtered bytes to `output`.
+/// All three are whole scanlines, including the filter type byte.
+fn filter_scanline(
+    filter: Filter,
+    current: &[u8],
+    previous: &[u8],
+    filter_width: usize,
+    output: &mut [u8],
+) {
+    output[0] = filter as u8;
+    for j in 1..current.len() {
+        let (a, c) = if j > filter_width {
+            (current[j - filter_width], previous[j - filter_width])
+        } else {
+            (0, 0)
+        };
+        output[j] = filter.filter(current[j], a, previous[j], c);
     }
-    // Special case for the first pixel/byte.
-    image_data[0] = Filter::Sub as u8;
-    for i in image_data[1..filter_width + 1].iter_mut() {
-        *i = Filter::Sub.filter(*i, 0, 0, 0);
+}
+
+/// Shannon entropy of the bytes in `data`, in bits per byte.
+fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for b in data {
+        counts[*b as usize] += 1;
     }
-    scanline_length * line_count
+    let total = data.len() as f64;
+    counts
+        .into_iter()
+        .filter(|count| *count > 0)
+        .map(|count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{inner_filter_scanlines, inner_reconstruct_scanlines, Filter};
+    use super::{inner_filter_scanlines, inner_reconstruct_scanlines, Filter, FilterStrategy};
+    use crate::error::DecodeError;
 
     #[test]
     fn reconstruct_undoes_filter() {
@@ -213,8 +373,8 @@ mod tests {
             1, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 1,
             0, 0, 0, 0, 0, 0, 255, 255, 0, 0, 0, 0, 0, 0, 255, 255,
         ];
-        let orig_copy = data.clone();
-        inner_filter_scanlines(data, 17, 2, 8);
+        let orig_copy = *data;
+        inner_filter_scanlines(data, 17, 2, 8, FilterStrategy::Fixed(Filter::Sub));
         assert_eq!(
             data,
             &[
@@ -223,7 +383,7 @@ mod tests {
                 1, 0, 0, 0, 0, 0, 0, 255, 255, 0, 0, 0, 0, 0, 0, 0, 0,
             ]
         );
-        inner_reconstruct_scanlines(data, 17, 2, 8);
+        inner_reconstruct_scanlines(data, 17, 2, 8).unwrap();
         assert_eq!(data, &orig_copy);
     }
 
@@ -261,12 +421,72 @@ mod tests {
             255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255,
             255, 0, 0, 0, 0, 0, 0, 255, 255, 0, 0, 0, 0, 0, 0, 255, 255,
         ];
-        let orig_copy = data.clone();
-        inner_filter_scanlines(data, 257, 2, 8);
-        inner_reconstruct_scanlines(data, 257, 2, 8);
+        let orig_copy = *data;
+        inner_filter_scanlines(data, 257, 2, 8, FilterStrategy::Fixed(Filter::Sub));
+        inner_reconstruct_scanlines(data, 257, 2, 8).unwrap();
         assert_eq!(data, &orig_copy);
     }
 
+    #[test]
+    fn reconstruct_undoes_every_strategy() {
+        let strategies = [
+            FilterStrategy::Fixed(Filter::None),
+            FilterStrategy::Fixed(Filter::Sub),
+            FilterStrategy::Fixed(Filter::Up),
+            FilterStrategy::Fixed(Filter::Average),
+            FilterStrategy::Fixed(Filter::Paeth),
+            FilterStrategy::MinimumSum,
+            FilterStrategy::Entropy,
+            FilterStrategy::BruteForce,
+        ];
+        // A gradient with some noise, so different scanlines favour different filters.
+        let (scanline_length, line_count) = (25, 8);
+        let orig: Vec<u8> = (0..scanline_length * line_count)
+            .map(|i| {
+                if i % scanline_length == 0 {
+                    0
+                } else {
+                    (i * 7 + (i * i) % 13) as u8
+                }
+            })
+            .collect();
+        for strategy in strategies {
+            let mut data = orig.clone();
+            inner_filter_scanlines(&mut data, scanline_length, line_count, 3, strategy);
+            if let FilterStrategy::Fixed(filter) = strategy {
+                assert!(data
+                    .chunks(scanline_length)
+                    .all(|line| line[0] == filter as u8));
+            }
+            inner_reconstruct_scanlines(&mut data, scanline_length, line_count, 3).unwrap();
+            // Reconstruction leaves the filter type bytes in place, so only compare the rest.
+            for (line, orig_line) in data
+                .chunks(scanline_length)
+                .zip(orig.chunks(scanline_length))
+            {
+                assert_eq!(line[1..], orig_line[1..], "{strategy:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn minimum_sum_prefers_up_for_repeated_scanlines() {
+        let line = [0, 10, 200, 30, 140, 50, 160];
+        let mut data = [line, line, line].concat();
+        inner_filter_scanlines(&mut data, 7, 3, 1, FilterStrategy::MinimumSum);
+        assert_eq!(data[7], Filter::Up as u8);
+        assert_eq!(data[14], Filter::Up as u8);
+    }
+
+    #[test]
+    fn reconstruct_rejects_unknown_filter_types() {
+        let data = &mut [5, 1, 2, 3, 0, 1, 2, 3];
+        assert_eq!(
+            inner_reconstruct_scanlines(data, 4, 2, 1),
+            Err(DecodeError::UnknownFilterType(5))
+        );
+    }
+
     #[test]
     fn average_actually_averages_a_and_b() {
         assert_eq!(Filter::Average.filter(50, 50, 50, 0), 0);
@@ -0,0 +1,85 @@
+use crate::{chunks::Chunk, pixel::Pixel};
+
+/// The transfer function of the display an image is being prepared for, used to gamma correct
+/// decoded images with [`DecoderOptions::gamma_correction`].
+///
+/// [`DecoderOptions::gamma_correction`]: crate::DecoderOptions::gamma_correction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DisplayGamma {
+    /// A display whose output intensity is its input raised to this exponent, e.g. 2.2 for a
+    /// typical monitor, or 1.0 to get linear light values.
+    Exponent(f64),
+    /// A display using the sRGB transfer function.
+    Srgb,
+}
+
+/// How the samples of an image relate to light intensity, according to its chunks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SourceGamma {
+    /// Samples are intensity raised to this exponent, from a gAMA chunk.
+    Exponent(f64),
+    /// Samples use the sRGB transfer function, from an sRGB chunk.
+    Srgb,
+}
+
+/// Corrects the color channels of `pixels` from the gamma given by `chunks` to `target`. Images
+/// without an sRGB or gAMA chunk are left alone, since their gamma isn't known.
+pub(crate) fn correct_gamma(pixels: &mut [Pixel], chunks: &[Chunk], target: DisplayGamma) {
+    // sRGB takes precedence over gAMA. Decoders that can't use an iCCP chunk's profile should
+    // fall back to these, so iCCP chunks are ignored.
+    let source = chunks
+        .iter()
+        .find_map(|chunk| match chunk {
+            Chunk::sRGB(_) => Some(SourceGamma::Srgb),
+            _ => None,
+        })
+        .or_else(|| {
+            chunks.iter().find_map(|chunk| match chunk {
+                Chunk::gAMA(gama) => Some(SourceGamma::Exponent(gama.gamma())),
+                _ => None,
+            })
+        });
+    let Some(source) = source else {
+        return;
+    };
+    let table = lookup_table(source, target);
+    for pixel in pixels {
+        pixel.red = table[pixel.red as usize];
+        pixel.green = table[pixel.green as usize];
+        pixel.blue = table[pixel.blue as usize];
+    }
+}
+
+/// Maps every 16 bit sample from `source` to `target`.
+fn lookup_table(source: SourceGamma, target: DisplayGamma) -> Vec<u16> {
+    (0..=u16::MAX)
+        .map(|sample| {
+            let encoded = sample as f64 / u16::MAX as f64;
+            let linear = match source {
+                SourceGamma::Exponent(gamma) => encoded.powf(1.0 / gamma),
+                SourceGamma::Srgb => srgb_to_linear(encoded),
+            };
+            let corrected = match target {
+                DisplayGamma::Exponent(gamma) => linear.powf(1.0 / gamma),
+                DisplayGamma::Srgb => linear_to_srgb(linear),
+            };
+            (corrected.clamp(0.0, 1.0) * u16::MAX as f64).round() as u16
+        })
+        .collect()
+}
+
+fn srgb_to_linear(value: f64) -> f64 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+pub(crate) fn linear_to_srgb(value: f64) -> f64 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
@@ -0,0 +1,415 @@
+use crate::{chunks::RenderingIntent, error::DecodeError, gamma::linear_to_srgb, pixel::Pixel};
+use nom::{
+    bytes::complete::{tag, take},
+    multi::count,
+    number::complete::{be_i32, be_u16, be_u32, be_u8},
+    sequence::tuple,
+    IResult,
+};
+
+/// The sRGB primaries as they appear in an ICC profile, adapted to the D50 white point all
+/// profiles are relative to. Each column is the XYZ of one primary.
+const SRGB_TO_XYZ: [[f64; 3]; 3] = [
+    [0.4360747, 0.3850649, 0.1430804],
+    [0.2225045, 0.7168786, 0.0606169],
+    [0.0139322, 0.0971045, 0.7141733],
+];
+
+/// An ICC profile that can be used to convert an image to sRGB.
+///
+/// Only the profiles cameras and design tools commonly embed are supported: version 2 and 4
+/// RGB profiles made of a matrix and tone reproduction curves, and greyscale profiles made of a
+/// single curve. Profiles that use lookup tables fail to parse with
+/// [`DecodeError::UnsupportedIccProfile`].
+///
+/// ```
+/// use simple_png::{chunks::RenderingIntent, ColorSpace, IccProfile, PNG};
+///
+/// // A greyscale image whose profile says its samples are linear light.
+/// let data = std::fs::read("tests/decoding/test-icc.png")?;
+/// let mut image = PNG::decode(&data)?;
+/// let ColorSpace::IccProfile(iccp) = image.color_space() else {
+///     panic!("the image has an iCCP chunk");
+/// };
+/// assert_eq!(iccp.name(), "Linear");
+/// let profile = IccProfile::parse(iccp.profile())?;
+/// assert_eq!(profile.rendering_intent(), RenderingIntent::RelativeColorimetric);
+///
+/// // Linear greys become lighter when they're encoded as sRGB.
+/// let linear_pixels = image.pixels.clone();
+/// profile.convert_to_srgb(&mut image.pixels);
+/// for (linear, srgb) in linear_pixels.iter().zip(&image.pixels) {
+///     assert!(srgb.red >= linear.red);
+/// }
+/// assert_ne!(image.pixels, linear_pixels);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct IccProfile {
+    rendering_intent: RenderingIntent,
+    transform: Transform,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Transform {
+    /// Each channel is linearized by its curve, then the matrix takes the linear values to
+    /// linear sRGB.
+    Rgb {
+        curves: [Curve; 3],
+        matrix: [[f64; 3]; 3],
+    },
+    /// Every channel is linearized by the same curve.
+    Grey(Curve),
+}
+
+/// A tone reproduction curve, which maps a sample to linear light.
+#[derive(Debug, Clone, PartialEq)]
+enum Curve {
+    Gamma(f64),
+    /// Samples spaced evenly over 0.0 to 1.0, interpolated linearly.
+    Table(Vec<u16>),
+    /// One of the ICC parametric curves, with its function type and parameters.
+    Parametric(u16, [f64; 7]),
+}
+
+impl IccProfile {
+    /// Reads an ICC profile, like the one held by an
+    /// [`iCCPChunk`](crate::chunks::iCCPChunk).
+    pub fn parse(profile: &[u8]) -> Result<Self, DecodeError> {
+        let (_, (size, _, version, _, class, color_space, connection_space)) = tuple((
+            be_u32,
+            take(4usize),
+            be_u8,
+            take(3usize),
+            take(4usize),
+            take(4usize),
+            take(4usize),
+        ))(profile)
+        .map_err(invalid)?;
+        // The header is 128 bytes, followed by the tag count.
+        if profile.get(36..40) != Some(b"acsp") || !(132..=profile.len()).contains(&(size as usize))
+        {
+            return Err(DecodeError::InvalidIccProfile);
+        }
+        let profile = &profile[..size as usize];
+        if version != 2 && version != 4 {
+            return Err(unsupported("profiles of versions other than 2 and 4"));
+        }
+        if class == b"link" || class == b"abst" || class == b"nmcl" {
+            return Err(unsupported(
+                "device link, abstract and named color profiles",
+            ));
+        }
+        if connection_space != b"XYZ " {
+            return Err(unsupported("profiles with a Lab connection space"));
+        }
+        let rendering_intent = match be_u32(&profile[64..]).map_err(invalid)?.1 {
+            0 => RenderingIntent::Perceptual,
+            1 => RenderingIntent::RelativeColorimetric,
+            2 => RenderingIntent::Saturation,
+            3 => RenderingIntent::AbsoluteColorimetric,
+            _ => return Err(DecodeError::InvalidIccProfile),
+        };
+        let tags = parse_tag_table(profile)?;
+        let tag = |signature: &[u8; 4]| {
+            tags.iter()
+                .find(|(tag_signature, _)| tag_signature == signature)
+                .map(|(_, data)| *data)
+        };
+        let has_lut = tag(b"A2B0").is_some() || tag(b"A2B1").is_some();
+        let transform = match color_space {
+            b"RGB " => {
+                let required = [b"rXYZ", b"gXYZ", b"bXYZ", b"rTRC", b"gTRC", b"bTRC"].map(&tag);
+                let [Some(r), Some(g), Some(b), Some(r_trc), Some(g_trc), Some(b_trc)] = required
+                else {
+                    return Err(if has_lut {
+                        unsupported("LUT-based profiles")
+                    } else {
+                        DecodeError::InvalidIccProfile
+                    });
+                };
+                let primaries = [parse_xyz(r)?, parse_xyz(g)?, parse_xyz(b)?];
+                let to_xyz = [0, 1, 2].map(|row| primaries.map(|xyz| xyz[row]));
+                let from_xyz = invert(SRGB_TO_XYZ).expect("the sRGB matrix is invertible");
+                Transform::Rgb {
+                    curves: [
+                        parse_curve(r_trc)?,
+                        parse_curve(g_trc)?,
+                        parse_curve(b_trc)?,
+                    ],
+                    matrix: multiply(from_xyz, to_xyz),
+                }
+            }
+            b"GRAY" => match tag(b"kTRC") {
+                Some(curve) => Transform::Grey(parse_curve(curve)?),
+                None if has_lut => return Err(unsupported("LUT-based profiles")),
+                None => return Err(DecodeError::InvalidIccProfile),
+            },
+            _ => return Err(unsupported("profiles that aren't RGB or greyscale")),
+        };
+        Ok(Self {
+            rendering_intent,
+            transform,
+        })
+    }
+
+    /// The rendering intent from the profile's header.
+    pub fn rendering_intent(&self) -> RenderingIntent {
+        self.rendering_intent
+    }
+
+    /// Converts the color channels of `pixels` from the profile's color space to sRGB. Alpha is
+    /// left as it is.
+    pub fn convert_to_srgb(&self, pixels: &mut [Pixel]) {
+        match &self.transform {
+            Transform::Rgb { curves, matrix } => {
+                let tables = curves.each_ref().map(Curve::lookup_table);
+                for pixel in pixels {
+                    let linear = [
+                        tables[0][pixel.red as usize],
+                        tables[1][pixel.green as usize],
+                        tables[2][pixel.blue as usize],
+                    ];
+                    let [red, green, blue] = matrix.map(|row| {
+                        encode_srgb(row.iter().zip(linear).map(|(m, v)| m * v as f64).sum())
+                    });
+                    pixel.red = red;
+                    pixel.green = green;
+                    pixel.blue = blue;
+                }
+            }
+            Transform::Grey(curve) => {
+                let table = curve
+                    .lookup_table()
+                    .into_iter()
+                    .map(|linear| encode_srgb(linear as f64))
+                    .collect::<Vec<_>>();
+                for pixel in pixels {
+                    pixel.red = table[pixel.red as usize];
+                    pixel.green = table[pixel.green as usize];
+                    pixel.blue = table[pixel.blue as usize];
+                }
+            }
+        }
+    }
+}
+
+impl Curve {
+    /// The linear value of every 16 bit sample.
+    fn lookup_table(&self) -> Vec<f32> {
+        (0..=u16::MAX)
+            .map(|sample| self.evaluate(sample as f64 / u16::MAX as f64) as f32)
+            .collect()
+    }
+
+    fn evaluate(&self, x: f64) -> f64 {
+        let y = match self {
+            Self::Gamma(gamma) => x.powf(*gamma),
+            Self::Table(table) => {
+                let position = x * (table.len() - 1) as f64;
+                let index = (position as usize).min(table.len() - 2);
+                let fraction = position - index as f64;
+                let (low, high) = (table[index] as f64, table[index + 1] as f64);
+                (low + (high - low) * fraction) / u16::MAX as f64
+            }
+            Self::Parametric(function, [g, a, b, c, d, e, f]) => match function {
+                0 => x.powf(*g),
+                1 if x >= -b / a => (a * x + b).powf(*g),
+                1 => 0.0,
+                2 if x >= -b / a => (a * x + b).powf(*g) + c,
+                2 => *c,
+                3 if x >= *d => (a * x + b).powf(*g),
+                3 => c * x,
+                _ if x >= *d => (a * x + b).powf(*g) + e,
+                _ => c * x + f,
+            },
+        };
+        // Negative bases give NaN, which clamp keeps.
+        if y.is_nan() {
+            0.0
+        } else {
+            y.clamp(0.0, 1.0)
+        }
+    }
+}
+
+/// A tag's signature and data.
+type Tag<'a> = ([u8; 4], &'a [u8]);
+
+/// Every tag in the profile.
+fn parse_tag_table(profile: &[u8]) -> Result<Vec<Tag<'_>>, DecodeError> {
+    let (mut input, tag_count) = be_u32(&profile[128..]).map_err(invalid)?;
+    let mut tags = vec![];
+    for _ in 0..tag_count {
+        let (rest, (signature, offset, size)) =
+            tuple((take(4usize), be_u32, be_u32))(input).map_err(invalid)?;
+        input = rest;
+        let data = (offset as usize)
+            .checked_add(size as usize)
+            .and_then(|end| profile.get(offset as usize..end))
+            .ok_or(DecodeError::InvalidIccProfile)?;
+        tags.push((signature.try_into().expect("4 bytes were taken"), data));
+    }
+    Ok(tags)
+}
+
+/// Reads an XYZType tag holding a single XYZ value.
+fn parse_xyz(data: &[u8]) -> Result<[f64; 3], DecodeError> {
+    let (_, (_, _, xyz)) =
+        tuple((tag(b"XYZ "), take(4usize), count(s15_fixed16, 3)))(data).map_err(invalid)?;
+    Ok([xyz[0], xyz[1], xyz[2]])
+}
+
+/// Reads a curveType or parametricCurveType tag.
+fn parse_curve(data: &[u8]) -> Result<Curve, DecodeError> {
+    let (rest, (signature, _)) = tuple((take(4usize), take(4usize)))(data).map_err(invalid)?;
+    match signature {
+        b"curv" => {
+            let (rest, entries) = be_u32(rest).map_err(invalid)?;
+            match entries {
+                0 => Ok(Curve::Gamma(1.0)),
+                1 => {
+                    let (_, gamma) = be_u16(rest).map_err(invalid)?;
+                    Ok(Curve::Gamma(gamma as f64 / 256.0))
+                }
+                _ => {
+                    let (_, table) = count(be_u16, entries as usize)(rest).map_err(invalid)?;
+                    Ok(Curve::Table(table))
+                }
+            }
+        }
+        b"para" => {
+            let (rest, (function, _)) = tuple((be_u16, be_u16))(rest).map_err(invalid)?;
+            let parameter_count = match function {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return Err(DecodeError::InvalidIccProfile),
+            };
+            let (_, values) = count(s15_fixed16, parameter_count)(rest).map_err(invalid)?;
+            let mut parameters = [0.0; 7];
+            parameters[..parameter_count].copy_from_slice(&values);
+            Ok(Curve::Parametric(function, parameters))
+        }
+        _ => Err(DecodeError::InvalidIccProfile),
+    }
+}
+
+fn s15_fixed16(input: &[u8]) -> IResult<&[u8], f64> {
+    let (rest, value) = be_i32(input)?;
+    Ok((rest, value as f64 / 65536.0))
+}
+
+fn encode_srgb(linear: f64) -> u16 {
+    (linear_to_srgb(linear.clamp(0.0, 1.0)) * u16::MAX as f64).round() as u16
+}
+
+fn multiply(a: [[f64; 3]; 3], b: [[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    [0, 1, 2].map(|row| [0, 1, 2].map(|col| (0..3).map(|i| a[row][i] * b[i][col]).sum()))
+}
+
+/// Inverts a 3x3 matrix, or returns `None` if it's singular.
+fn invert(m: [[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor = |row: usize, col: usize| {
+        let (r1, r2) = ((row + 1) % 3, (row + 2) % 3);
+        let (c1, c2) = ((col + 1) % 3, (col + 2) % 3);
+        m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+    };
+    let determinant = (0..3).map(|col| m[0][col] * cofactor(0, col)).sum::<f64>();
+    if determinant.abs() < f64::EPSILON {
+        return None;
+    }
+    Some([0, 1, 2].map(|row| [0, 1, 2].map(|col| cofactor(col, row) / determinant)))
+}
+
+fn invalid(_: nom::Err<nom::error::Error<&[u8]>>) -> DecodeError {
+    DecodeError::InvalidIccProfile
+}
+
+fn unsupported(reason: &'static str) -> DecodeError {
+    DecodeError::UnsupportedIccProfile { reason }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{invert, multiply, Curve, IccProfile, Transform, SRGB_TO_XYZ};
+    use crate::{chunks::RenderingIntent, ColorSpace, PNG};
+
+    /// A greyscale profile whose only tag is a kTRC curve that leaves samples as they are.
+    fn linear_grey_profile() -> Vec<u8> {
+        let mut profile = vec![0; 156];
+        profile[..4].copy_from_slice(&156u32.to_be_bytes());
+        profile[8] = 4;
+        profile[12..24].copy_from_slice(b"mntrGRAYXYZ ");
+        profile[36..40].copy_from_slice(b"acsp");
+        // Relative colorimetric rendering intent.
+        profile[67] = 1;
+        // One tag, the kTRC curve at offset 144, with no entries.
+        profile[131] = 1;
+        profile[132..144].copy_from_slice(&[b'k', b'T', b'R', b'C', 0, 0, 0, 144, 0, 0, 0, 12]);
+        profile[144..148].copy_from_slice(b"curv");
+        profile
+    }
+
+    #[test]
+    fn test_image_holds_linear_profile() {
+        // tests/decoding/test-icc.png is basn0g08 with the linear profile in an iCCP chunk.
+        let data = std::fs::read("tests/decoding/test-icc.png").unwrap();
+        let image = PNG::decode(&data).unwrap();
+        let ColorSpace::IccProfile(iccp) = image.color_space() else {
+            panic!("{:?}", image.color_space());
+        };
+        assert_eq!(iccp.profile(), linear_grey_profile());
+        let profile = IccProfile::parse(iccp.profile()).unwrap();
+        assert_eq!(
+            profile,
+            IccProfile {
+                rendering_intent: RenderingIntent::RelativeColorimetric,
+                transform: Transform::Grey(Curve::Gamma(1.0)),
+            }
+        );
+        let original = std::fs::read("tests/png-suite/basn0g08.png").unwrap();
+        assert_eq!(image.pixels, PNG::decode(&original).unwrap().pixels);
+    }
+
+    #[test]
+    fn matrix_inverse_works() {
+        let inverse = invert(SRGB_TO_XYZ).unwrap();
+        let identity = multiply(SRGB_TO_XYZ, inverse);
+        for (row, values) in identity.iter().enumerate() {
+            for (col, value) in values.iter().enumerate() {
+                let expected = if row == col { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-9, "{identity:?}");
+            }
+        }
+        assert_eq!(
+            invert([[1.0, 2.0, 3.0], [2.0, 4.0, 6.0], [0.0, 0.0, 1.0]]),
+            None
+        );
+    }
+
+    #[test]
+    fn curves_evaluate() {
+        assert_eq!(Curve::Gamma(1.0).evaluate(0.25), 0.25);
+        assert_eq!(Curve::Gamma(2.0).evaluate(0.5), 0.25);
+        assert_eq!(Curve::Table(vec![0, u16::MAX]).evaluate(0.25), 0.25);
+        assert_eq!(Curve::Table(vec![0, 0, u16::MAX]).evaluate(0.75), 0.5);
+        // The sRGB transfer function as a parametric curve.
+        let srgb = Curve::Parametric(
+            3,
+            [
+                2.4,
+                1.0 / 1.055,
+                0.055 / 1.055,
+                1.0 / 12.92,
+                0.04045,
+                0.0,
+                0.0,
+            ],
+        );
+        assert!((srgb.evaluate(0.5) - 0.214041).abs() < 1e-6);
+        assert!((srgb.evaluate(0.02) - 0.02 / 12.92).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,200 @@
+use crate::{
+    chunks::{
+        ihdr::{ColorType, IHDRChunk, Interlacing},
+        plte::{PLTEChunk, PaletteEntry},
+        Chunk,
+    },
+    depth::scale,
+    encoding::{serialize_indices, SerializedImage},
+    error::{DecodeError, EncodeError},
+    limits::DecodeLimits,
+    native::{NativeImage, PixelBuffer},
+    options::EncoderOptions,
+    pixel::Pixel,
+    png::{write_png, PNG},
+    significant_bits::significant_bits_chunk,
+};
+
+/// An indexed color image kept as its palette and palette indices, so it can be edited and
+/// encoded again without the palette being rebuilt or reordered.
+///
+/// ```
+/// use simple_png::IndexedImage;
+///
+/// let data = std::fs::read("tests/png-suite/basn3p04.png")?;
+/// let mut image = IndexedImage::decode(&data)?;
+/// image.palette[0] = [255, 0, 0];
+/// let encoded = image.encode()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedImage<'a> {
+    /// The IHDR metadata for the image. Its color type is always indexed color.
+    pub header: IHDRChunk,
+    /// The red, green and blue values of each palette entry, in the order they're stored.
+    pub palette: Vec<[u8; 3]>,
+    /// The alpha value of each palette entry, from the tRNS chunk. Entries past the end of this
+    /// are fully opaque, and values past the end of the palette are ignored.
+    pub alpha: Vec<u8>,
+    /// The palette index of every pixel, top left first.
+    pub indices: Vec<u8>,
+    /// Any PNG chunks that the library doesn't interpret.
+    pub extra_chunks: Vec<Chunk<'a>>,
+}
+impl<'a> IndexedImage<'a> {
+    /// Creates an 8 bit indexed color image without transparency.
+    ///
+    /// The indices should have a length of at least height * width, anything after that is
+    /// ignored.
+    pub fn new(width: u32, height: u32, palette: Vec<[u8; 3]>, indices: Vec<u8>) -> Self {
+        Self {
+            header: IHDRChunk::new(width, height, 8, ColorType::IndexedColor, Interlacing::None),
+            palette,
+            alpha: vec![],
+            indices,
+            extra_chunks: vec![],
+        }
+    }
+
+    /// Decodes an indexed color PNG without expanding its palette, failing with
+    /// [`DecodeError::UnsupportedColorType`] for other color types. The default
+    /// [`DecodeLimits`] apply.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::decode_with_limits(bytes, &DecodeLimits::new())
+    }
+
+    /// Decodes an indexed color PNG like [`decode`](Self::decode), failing with
+    /// [`DecodeError::LimitExceeded`] if the image goes over `limits`.
+    pub fn decode_with_limits(bytes: &'a [u8], limits: &DecodeLimits) -> Result<Self, DecodeError> {
+        let NativeImage {
+            header,
+            palette,
+            transparency,
+            extra_chunks,
+            pixels,
+        } = NativeImage::decode_with_limits(bytes, limits)?;
+        let (PixelBuffer::Indexed(indices), Some(palette)) = (pixels, palette) else {
+            return Err(DecodeError::UnsupportedColorType(header.color_type));
+        };
+        Ok(Self {
+            header,
+            palette: palette
+                .colors()
+                .iter()
+                .map(|PaletteEntry(r, g, b)| [*r, *g, *b])
+                .collect(),
+            alpha: transparency.unwrap_or_default(),
+            indices,
+            extra_chunks,
+        })
+    }
+
+    /// Encodes the image with its palette and alpha values written out in order, using the bit
+    /// depth and interlacing from its header.
+    pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
+        self.encode_with(&EncoderOptions::new())
+    }
+
+    /// Encodes the image like [`encode`](Self::encode), with control over compression,
+    /// filtering and chunking through `options`. [`EncoderOptions::optimize`] picks the lowest
+    /// bit depth that fits the palette, instead of a different color type.
+    pub fn encode_with(&self, options: &EncoderOptions) -> Result<Vec<u8>, EncodeError> {
+        let bit_depth = if options.optimize {
+            [1, 2, 4, 8]
+                .into_iter()
+                .find(|depth| self.palette.len() <= 1 << depth)
+                .unwrap_or(8)
+        } else {
+            self.header.bit_depth
+        };
+        let color_type = ColorType::IndexedColor;
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(EncodeError::InvalidBitDepth {
+                color_type,
+                bit_depth,
+            });
+        }
+        let max = 1usize << bit_depth;
+        if self.palette.len() > max {
+            return Err(EncodeError::TooManyColors { max });
+        }
+        let count = self.header.width as usize * self.header.height as usize;
+        let indices = self
+            .indices
+            .get(..count)
+            .ok_or(EncodeError::NotEnoughPixels {
+                expected: count,
+                actual: self.indices.len(),
+            })?;
+        if let Some(index) = indices
+            .iter()
+            .find(|index| **index as usize >= self.palette.len())
+        {
+            return Err(EncodeError::PaletteIndexOutOfRange(*index));
+        }
+        let header = IHDRChunk::new(
+            self.header.width,
+            self.header.height,
+            bit_depth,
+            color_type,
+            options.interlacing.unwrap_or(self.header.interlace_method),
+        );
+        let palette = self
+            .palette
+            .iter()
+            .map(|[r, g, b]| PaletteEntry(*r, *g, *b))
+            .collect();
+        let alpha = &self.alpha[..self.alpha.len().min(self.palette.len())];
+        let image = SerializedImage {
+            data: serialize_indices(indices, &header),
+            palette: Some(PLTEChunk::new(palette)),
+            transparency: (!alpha.is_empty()).then(|| alpha.to_vec()),
+        };
+        // The palette is written as it is, so the background's palette index stays valid.
+        let background = self.extra_chunks.iter().find_map(|chunk| match chunk {
+            Chunk::bKGD(bkgd) => Some(bkgd.clone()),
+            _ => None,
+        });
+        let significant_bits = significant_bits_chunk(
+            options.significant_bits,
+            &self.extra_chunks,
+            &self.header,
+            &header,
+        );
+        Ok(write_png(
+            &header,
+            &self.extra_chunks,
+            significant_bits,
+            background,
+            image,
+            options,
+        ))
+    }
+
+    /// Expands every index to its 16 bit RGBA palette color. This gives the same pixels as
+    /// [`PNG::decode`](crate::PNG::decode).
+    pub fn to_pixels(&self) -> Result<Vec<Pixel>, DecodeError> {
+        self.indices
+            .iter()
+            .map(|index| {
+                let [r, g, b] = self
+                    .palette
+                    .get(*index as usize)
+                    .ok_or(DecodeError::PaletteIndexOutOfRange(*index))?;
+                let a = self.alpha.get(*index as usize).unwrap_or(&u8::MAX);
+                let [r, g, b, a] = [*r, *g, *b, *a].map(|s| scale(s as u16, 8));
+                Ok(Pixel::new(r, g, b, a))
+            })
+            .collect()
+    }
+
+    /// Expands the image to 16 bit RGBA like [`to_pixels`](Self::to_pixels). Encoding the
+    /// result builds a new palette, which may be in a different order.
+    pub fn into_png(self) -> Result<PNG<'a, Vec<Pixel>>, DecodeError> {
+        Ok(PNG {
+            pixels: self.to_pixels()?,
+            header: self.header,
+            extra_chunks: self.extra_chunks,
+        })
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{
+    chunks::{
+        ihdr::{ColorType, IHDRChunk, Interlacing},
+        iter_chunks, Chunk,
+    },
+    error::DecodeError,
+    limits::DecodeLimits,
+    png::parse_signature,
+};
+
+/// An image's dimensions and format, read without decoding its pixels.
+///
+/// Returned by [`PNG::probe`](crate::PNG::probe) and [`PNG::read_info`](crate::PNG::read_info).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// Width of the image in pixels.
+    pub width: u32,
+    /// Height of the image in pixels.
+    pub height: u32,
+    /// Number of bits per sample, or per palette index for indexed images.
+    pub bit_depth: u8,
+    /// How the samples of each pixel are laid out.
+    pub color_type: ColorType,
+    /// The compression method, always 0 (zlib) in valid files.
+    pub compression_method: u8,
+    /// The filter method, always 0 (adaptive filtering) in valid files.
+    pub filter_method: u8,
+    /// Whether the image data is stored interlaced.
+    pub interlace_method: Interlacing,
+    /// The type and data length of every chunk in the file, in order, starting with IHDR. Only
+    /// holds the IHDR chunk when returned by [`PNG::probe`](crate::PNG::probe).
+    pub chunks: Vec<ChunkInfo>,
+}
+
+/// The type and size of a chunk, see [`ImageInfo::chunks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkInfo {
+    /// The four byte chunk type, e.g. `b"IDAT"`.
+    pub chunk_type: [u8; 4],
+    /// Length of the chunk's data in bytes, not counting its length, type and CRC.
+    pub length: u32,
+}
+
+/// Validates the signature and IHDR chunk, then validates and lists every following chunk up to
+/// IEND if `all_chunks` is set, failing once they go over `limits`. Image data is never
+/// decompressed.
+pub(crate) fn read_info(
+    bytes: &[u8],
+    all_chunks: bool,
+    limits: &DecodeLimits,
+) -> Result<ImageInfo, DecodeError> {
+    let (rest, _) = parse_signature(bytes).or(Err(DecodeError::InvalidSignature))?;
+    let mut chunks = iter_chunks(rest, *limits);
+    let mut infos = vec![];
+    let mut header = None;
+    loop {
+        let remaining = chunks.remaining();
+        let chunk = match chunks.next() {
+            Some(chunk) => chunk?,
+            None => break,
+        };
+        // A chunk was parsed, so its length and type are there.
+        infos.push(ChunkInfo {
+            chunk_type: remaining[4..8].try_into().expect("chunk was parsed"),
+            length: u32::from_be_bytes(remaining[..4].try_into().expect("chunk was parsed")),
+        });
+        match (chunk, &header) {
+            (Chunk::IHDR(ihdr), None) => {
+                if !all_chunks {
+                    header = Some(ihdr);
+                    break;
+                }
+                limits.check_header(&ihdr)?;
+                header = Some(ihdr);
+            }
+            (_, None) | (Chunk::IHDR(_), Some(_)) => return Err(DecodeError::MissingHeader),
+            _ => {}
+        }
+    }
+    let IHDRChunk {
+        width,
+        height,
+        bit_depth,
+        color_type,
+        compression_method,
+        filter_method,
+        interlace_method,
+    } = header.ok_or(DecodeError::MissingHeader)?;
+    Ok(ImageInfo {
+        width,
+        height,
+        bit_depth,
+        color_type,
+        compression_method,
+        filter_method,
+        interlace_method,
+        chunks: infos,
+    })
+}
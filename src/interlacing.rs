@@ -50,7 +50,7 @@ impl Iterator for Adam7Iter {
                 pixel_indices: PixelIndicesIter::new(
                     (Self::STARTING_ROW[pass]..self.height).step_by(Self::ROW_INCREMENT[pass]),
                     (Self::STARTING_COL[pass]..self.width).step_by(Self::COL_INCREMENT[pass]),
-                    self.height,
+                    self.width,
                 ),
             });
         }
@@ -72,13 +72,13 @@ pub(crate) struct PixelIndicesIter {
     current_row: Option<usize>,
     orig_columns: StepBy<Range<usize>>,
     columns: StepBy<Range<usize>>,
-    height: usize,
+    width: usize,
 }
 impl PixelIndicesIter {
     pub fn new(
         mut rows: StepBy<Range<usize>>,
         columns: StepBy<Range<usize>>,
-        height: usize,
+        width: usize,
     ) -> Self {
         let current_row = rows.next();
         Self {
@@ -86,7 +86,7 @@ impl PixelIndicesIter {
             current_row,
             orig_columns: columns.clone(),
             columns,
-            height,
+            width,
         }
     }
 }
@@ -94,11 +94,11 @@ impl Iterator for PixelIndicesIter {
     type Item = usize;
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(column) = self.columns.next() {
-            return Some(self.current_row? * self.height + column);
+            return Some(self.current_row? * self.width + column);
         }
         self.current_row = Some(self.rows.next()?);
         self.columns = self.orig_columns.clone();
-        Some(self.current_row? * self.height + self.columns.next()?)
+        Some(self.current_row? * self.width + self.columns.next()?)
     }
 }
 
@@ -169,4 +169,20 @@ mod tests {
             assert_eq!(pass.pixel_indices.count(), expected);
         }
     }
+
+    #[test]
+    fn adam7iter_handles_non_square_images() {
+        let adam7 = Adam7Iter::new(3, 2);
+        let expected_indices: [&[usize]; 4] = [&[0], &[2], &[1], &[3, 4, 5]];
+        for (pass, expected) in adam7.zip(expected_indices) {
+            assert_eq!(pass.pixel_indices.collect::<Vec<_>>(), expected);
+        }
+
+        // Every pixel is visited exactly once.
+        let mut indices: Vec<_> = Adam7Iter::new(13, 5)
+            .flat_map(|pass| pass.pixel_indices)
+            .collect();
+        indices.sort();
+        assert_eq!(indices, (0..65).collect::<Vec<_>>());
+    }
 }
@@ -0,0 +1,181 @@
+use crate::{
+    chunks::ihdr::{IHDRChunk, Interlacing},
+    depth::unscale,
+    error::DecodeError,
+    limits::DecodeLimits,
+    pixel::{parse_scanline_pixels, Pixel},
+    png::{inflate_image_data, read_image_chunks, ImageChunks},
+    scanlines::{Adam7ScanlineIter, NormalScanline, ScanlineIterator},
+};
+
+/// How the pixels written by [`PNG::decode_into`](crate::PNG::decode_into) are laid out in
+/// memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PixelFormat {
+    /// 8 bit red, green, blue and alpha samples.
+    #[default]
+    Rgba8,
+    /// 8 bit blue, green, red and alpha samples.
+    Bgra8,
+    /// 8 bit red, green and blue samples, dropping alpha.
+    Rgb8,
+    /// A single 8 bit grey sample, using the Rec. 709 luma of colored pixels and dropping alpha.
+    Gray8,
+    /// 16 bit red, green, blue and alpha samples, most significant byte first.
+    Rgba16Be,
+    /// 16 bit red, green, blue and alpha samples, least significant byte first.
+    Rgba16Le,
+}
+impl PixelFormat {
+    /// The number of bytes each pixel takes up.
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            Self::Rgba8 | Self::Bgra8 => 4,
+            Self::Rgb8 => 3,
+            Self::Gray8 => 1,
+            Self::Rgba16Be | Self::Rgba16Le => 8,
+        }
+    }
+
+    /// Writes `pixel` to the start of `out`, which holds at least one pixel.
+    fn write(&self, pixel: &Pixel, out: &mut [u8]) {
+        let byte = |sample| unscale(sample, 8) as u8;
+        let rgba = [pixel.red, pixel.green, pixel.blue, pixel.alpha];
+        match self {
+            Self::Rgba8 => out[..4].copy_from_slice(&rgba.map(byte)),
+            Self::Bgra8 => out[..4]
+                .copy_from_slice(&[pixel.blue, pixel.green, pixel.red, pixel.alpha].map(byte)),
+            Self::Rgb8 => out[..3].copy_from_slice(&rgba.map(byte)[..3]),
+            Self::Gray8 => out[0] = byte(pixel.luma()),
+            Self::Rgba16Be => {
+                for (out, sample) in out.chunks_exact_mut(2).zip(rgba) {
+                    out.copy_from_slice(&sample.to_be_bytes());
+                }
+            }
+            Self::Rgba16Le => {
+                for (out, sample) in out.chunks_exact_mut(2).zip(rgba) {
+                    out.copy_from_slice(&sample.to_le_bytes());
+                }
+            }
+        }
+    }
+}
+
+/// The format and row stride of the pixels written by [`PNG::decode_into`].
+///
+/// ```
+/// use simple_png::{PixelFormat, PixelLayout, PNG};
+///
+/// let data = std::fs::read("tests/png-suite/basn2c08.png")?;
+/// let info = PNG::probe(&data)?;
+/// // Rows padded to a multiple of 256 bytes.
+/// let stride = (info.width as usize * 4).next_multiple_of(256);
+/// let layout = PixelLayout::new(PixelFormat::Bgra8).stride(stride);
+/// let mut buffer = vec![0; layout.buffer_len(info.width, info.height)];
+/// PNG::decode_into(&data, &mut buffer, layout)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// [`PNG::decode_into`]: crate::PNG::decode_into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PixelLayout {
+    pub(crate) format: PixelFormat,
+    pub(crate) stride: Option<usize>,
+}
+impl PixelLayout {
+    /// Pixels in `format`, with rows packed tightly.
+    pub fn new(format: PixelFormat) -> Self {
+        Self {
+            format,
+            stride: None,
+        }
+    }
+
+    /// Sets the number of bytes from the start of one row to the start of the next, which has
+    /// to be at least a row of pixels. Bytes between rows are left as they are. Defaults to the
+    /// length of a row.
+    pub fn stride(mut self, stride: usize) -> Self {
+        self.stride = Some(stride);
+        self
+    }
+
+    /// The format of each pixel.
+    pub fn format(&self) -> PixelFormat {
+        self.format
+    }
+
+    /// The number of bytes a buffer needs to hold an image of the given size. The last row
+    /// doesn't need padding up to the stride. Sizes too big for a `usize` give `usize::MAX`,
+    /// which no buffer can hold.
+    pub fn buffer_len(&self, width: u32, height: u32) -> usize {
+        let row_len = (width as usize).saturating_mul(self.format.bytes_per_pixel());
+        match height {
+            0 => 0,
+            _ => self
+                .stride
+                .unwrap_or(row_len)
+                .max(row_len)
+                .checked_mul(height as usize - 1)
+                .and_then(|len| len.checked_add(row_len))
+                .unwrap_or(usize::MAX),
+        }
+    }
+}
+
+/// Decodes an image straight into `buffer` in the given layout, one scanline at a time, failing
+/// if the image goes over `limits`.
+pub(crate) fn decode_into(
+    bytes: &[u8],
+    buffer: &mut [u8],
+    layout: PixelLayout,
+    limits: &DecodeLimits,
+) -> Result<IHDRChunk, DecodeError> {
+    let ImageChunks {
+        header,
+        palette,
+        transparency,
+        data,
+        ..
+    } = read_image_chunks(bytes, limits)?;
+    let pixel_len = layout.format.bytes_per_pixel();
+    let row_len = (header.width as usize).saturating_mul(pixel_len);
+    let stride = layout.stride.unwrap_or(row_len);
+    if stride < row_len {
+        return Err(DecodeError::StrideTooSmall {
+            expected: row_len,
+            actual: stride,
+        });
+    }
+    let expected = layout.buffer_len(header.width, header.height);
+    if buffer.len() < expected {
+        return Err(DecodeError::BufferTooSmall {
+            expected,
+            actual: buffer.len(),
+        });
+    }
+    let data = inflate_image_data(&data, &header)?;
+    let mut write_scanlines = |scanlines: &mut dyn ScanlineIterator| {
+        let width = header.width as usize;
+        for (scanline, pixel_indices) in scanlines {
+            let pixels = parse_scanline_pixels(
+                scanline,
+                header.color_type,
+                header.bit_depth,
+                palette.as_ref(),
+                transparency.as_ref(),
+            )?;
+            for (index, pixel) in pixel_indices.into_iter().zip(pixels) {
+                let start = index / width * stride + index % width * pixel_len;
+                layout
+                    .format
+                    .write(&pixel, &mut buffer[start..start + pixel_len]);
+            }
+        }
+        Ok(())
+    };
+    match header.interlace_method {
+        Interlacing::None => write_scanlines(&mut NormalScanline::new(&data, &header))?,
+        Interlacing::Adam7 => write_scanlines(&mut Adam7ScanlineIter::new(&data, &header))?,
+    }
+    Ok(header)
+}
@@ -8,16 +8,48 @@
 //!
 //! let png_data = std::fs::read("tests/png-suite/basn0g01.png")?;
 //! let image = PNG::decode(&png_data)?;
-//! std::fs::write("./new-image.png", image.encode())?;
+//! std::fs::write(std::env::temp_dir().join("new-image.png"), image.encode()?)?;
 //! # Ok::<(), anyhow::Error>(())
 //! ```
-mod chunks;
+pub mod animation;
+pub mod chunks;
+mod color_space;
+pub mod depth;
+mod encoding;
+mod error;
+mod exif;
 mod filters;
+mod gamma;
+mod icc;
+mod indexed;
+mod info;
 mod interlacing;
+mod layout;
+mod limits;
+mod native;
+mod options;
 mod pixel;
 mod png;
 mod scanlines;
+mod significant_bits;
+mod streaming;
 mod utils;
 
+pub use chunks::{ColorType, Interlacing};
+pub use color_space::ColorSpace;
+pub use depth::Dither;
+pub use error::{DecodeError, EncodeError};
+pub use exif::{Exif, Orientation};
+pub use filters::{Filter, FilterStrategy};
+pub use gamma::DisplayGamma;
+pub use icc::IccProfile;
+pub use indexed::IndexedImage;
+pub use info::{ChunkInfo, ImageInfo};
+pub use layout::{PixelFormat, PixelLayout};
+pub use limits::DecodeLimits;
+pub use native::{NativeImage, PixelBuffer};
+pub use options::{CompressionStrategy, DecoderOptions, EncoderOptions, TimeStamp};
 pub use pixel::Pixel;
 pub use png::PNG;
+pub use significant_bits::SampleScaling;
+pub use streaming::{StreamingDecoder, StreamingEncoder};
@@ -0,0 +1,197 @@
+use crate::{chunks::ihdr::IHDRChunk, error::DecodeError, scanlines::image_data_length};
+
+/// Bounds on how much work and memory decoding an image may take, so that small malicious
+/// files can't make the decoder allocate huge amounts of memory.
+///
+/// Going over any limit fails decoding with [`DecodeError::LimitExceeded`]. The defaults allow
+/// images up to 2^28 pixels, which is 2 GiB once decoded into [`Pixel`](crate::Pixel)s.
+///
+/// ```
+/// use simple_png::{DecodeError, DecodeLimits, PNG};
+///
+/// let data = std::fs::read("tests/png-suite/basn0g01.png")?;
+/// let limits = DecodeLimits::new().max_width(16);
+/// assert_eq!(
+///     PNG::decode_with_limits(&data, &limits).unwrap_err(),
+///     DecodeError::LimitExceeded { limit: "max_width" }
+/// );
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeLimits {
+    pub(crate) max_width: u32,
+    pub(crate) max_height: u32,
+    pub(crate) max_pixels: u64,
+    pub(crate) max_decompressed_bytes: usize,
+    pub(crate) max_chunk_bytes: usize,
+    pub(crate) max_chunks: usize,
+    pub(crate) max_text_chunk_size: usize,
+    pub(crate) max_icc_profile_size: usize,
+}
+impl DecodeLimits {
+    /// The limits [`PNG::decode`](crate::PNG::decode) uses.
+    pub fn new() -> Self {
+        Self {
+            max_width: 1 << 20,
+            max_height: 1 << 20,
+            max_pixels: 1 << 28,
+            max_decompressed_bytes: 1 << 30,
+            max_chunk_bytes: 1 << 30,
+            max_chunks: 1 << 20,
+            max_text_chunk_size: 8 << 20,
+            max_icc_profile_size: 8 << 20,
+        }
+    }
+
+    /// Limits that never stop an image from being decoded. Only use these for trusted input.
+    pub fn unlimited() -> Self {
+        Self {
+            max_width: u32::MAX,
+            max_height: u32::MAX,
+            max_pixels: u64::MAX,
+            max_decompressed_bytes: usize::MAX,
+            max_chunk_bytes: usize::MAX,
+            max_chunks: usize::MAX,
+            max_text_chunk_size: usize::MAX,
+            max_icc_profile_size: usize::MAX,
+        }
+    }
+
+    /// Sets the widest image that can be decoded. Defaults to 2^20.
+    pub fn max_width(mut self, width: u32) -> Self {
+        self.max_width = width;
+        self
+    }
+
+    /// Sets the tallest image that can be decoded. Defaults to 2^20.
+    pub fn max_height(mut self, height: u32) -> Self {
+        self.max_height = height;
+        self
+    }
+
+    /// Sets the most pixels, width * height, an image can have. For an animation this counts
+    /// every frame and the default image together. Defaults to 2^28.
+    pub fn max_pixels(mut self, pixels: u64) -> Self {
+        self.max_pixels = pixels;
+        self
+    }
+
+    /// Sets the most bytes the image data can decompress to. For an animation this counts every
+    /// frame and the default image together. Defaults to 1 GiB.
+    pub fn max_decompressed_bytes(mut self, bytes: usize) -> Self {
+        self.max_decompressed_bytes = bytes;
+        self
+    }
+
+    /// Sets the most bytes of chunk data, summed over every chunk, a file can have. Defaults to
+    /// 1 GiB.
+    pub fn max_chunk_bytes(mut self, bytes: usize) -> Self {
+        self.max_chunk_bytes = bytes;
+        self
+    }
+
+    /// Sets the most chunks a file can have. Defaults to 2^20.
+    pub fn max_chunks(mut self, chunks: usize) -> Self {
+        self.max_chunks = chunks;
+        self
+    }
+
+    /// Sets the most bytes of data a single tEXt, zTXt or iTXt chunk can have. Defaults to
+    /// 8 MiB.
+    pub fn max_text_chunk_size(mut self, bytes: usize) -> Self {
+        self.max_text_chunk_size = bytes;
+        self
+    }
+
+    /// Sets the most bytes an iCCP chunk's ICC profile can decompress to. Defaults to 8 MiB.
+    pub fn max_icc_profile_size(mut self, bytes: usize) -> Self {
+        self.max_icc_profile_size = bytes;
+        self
+    }
+
+    /// Checks the image described by `header` fits within the limits.
+    pub(crate) fn check_header(&self, header: &IHDRChunk) -> Result<(), DecodeError> {
+        if header.width > self.max_width {
+            return Err(exceeded("max_width"));
+        }
+        if header.height > self.max_height {
+            return Err(exceeded("max_height"));
+        }
+        if header.width as u64 * header.height as u64 > self.max_pixels {
+            return Err(exceeded("max_pixels"));
+        }
+        if image_data_length(header) > self.max_decompressed_bytes {
+            return Err(exceeded("max_decompressed_bytes"));
+        }
+        Ok(())
+    }
+
+    /// Checks the image described by `header` fits within the limits like
+    /// [`check_header`](Self::check_header), and counts it towards the limits on the pixels and
+    /// decompressed bytes of every image in the file together.
+    pub(crate) fn check_image(
+        &self,
+        tally: &mut ImageTally,
+        header: &IHDRChunk,
+    ) -> Result<(), DecodeError> {
+        self.check_header(header)?;
+        tally.pixels = tally
+            .pixels
+            .saturating_add(header.width as u64 * header.height as u64);
+        tally.bytes = tally.bytes.saturating_add(image_data_length(header));
+        if tally.pixels > self.max_pixels {
+            return Err(exceeded("max_pixels"));
+        }
+        if tally.bytes > self.max_decompressed_bytes {
+            return Err(exceeded("max_decompressed_bytes"));
+        }
+        Ok(())
+    }
+
+    /// Counts a chunk with the given type and data length towards the limits, before its data is
+    /// read.
+    pub(crate) fn check_chunk(
+        &self,
+        tally: &mut ChunkTally,
+        chunk_type: &[u8; 4],
+        length: u32,
+    ) -> Result<(), DecodeError> {
+        tally.count += 1;
+        tally.bytes = tally.bytes.saturating_add(length as usize);
+        if tally.count > self.max_chunks {
+            return Err(exceeded("max_chunks"));
+        }
+        if tally.bytes > self.max_chunk_bytes {
+            return Err(exceeded("max_chunk_bytes"));
+        }
+        if matches!(chunk_type, b"tEXt" | b"zTXt" | b"iTXt")
+            && length as usize > self.max_text_chunk_size
+        {
+            return Err(exceeded("max_text_chunk_size"));
+        }
+        Ok(())
+    }
+}
+impl Default for DecodeLimits {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The chunks read so far, for checking against [`DecodeLimits`].
+#[derive(Debug, Default)]
+pub(crate) struct ChunkTally {
+    count: usize,
+    bytes: usize,
+}
+
+/// The images decoded so far, for checking against [`DecodeLimits`].
+#[derive(Debug, Default)]
+pub(crate) struct ImageTally {
+    pixels: u64,
+    bytes: usize,
+}
+
+fn exceeded(limit: &'static str) -> DecodeError {
+    DecodeError::LimitExceeded { limit }
+}
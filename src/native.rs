@@ -0,0 +1,277 @@
+use crate::{
+    chunks::{
+        ihdr::{ColorType, IHDRChunk, Interlacing},
+        plte::PLTEChunk,
+        trns::tRNSChunk,
+        Chunk,
+    },
+    encoding::{serialize_pixels, SerializedImage},
+    error::{DecodeError, EncodeError},
+    limits::DecodeLimits,
+    pixel::{samples_to_pixels, unpack_samples, Pixel},
+    png::{inflate_image_data, read_image_chunks, ImageChunks, PNG},
+    scanlines::{Adam7ScanlineIter, NormalScanline, ScanlineIterator},
+};
+
+/// An image's pixels in the color type and bit depth they're stored in, instead of expanded to
+/// 16 bit RGBA [`Pixel`]s.
+///
+/// Samples are kept as they're stored, so greyscale images with a bit depth below 8 hold values
+/// up to 2^bit_depth - 1, and transparency from a tRNS chunk isn't applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PixelBuffer {
+    /// Greyscale samples of 1, 2, 4 or 8 bits.
+    Gray8(Vec<u8>),
+    /// 16 bit greyscale samples.
+    Gray16(Vec<u16>),
+    /// 8 bit greyscale and alpha samples.
+    GrayAlpha8(Vec<[u8; 2]>),
+    /// 16 bit greyscale and alpha samples.
+    GrayAlpha16(Vec<[u16; 2]>),
+    /// 8 bit red, green and blue samples.
+    Rgb8(Vec<[u8; 3]>),
+    /// 8 bit red, green, blue and alpha samples.
+    Rgba8(Vec<[u8; 4]>),
+    /// 16 bit red, green and blue samples.
+    Rgb16(Vec<[u16; 3]>),
+    /// 16 bit red, green, blue and alpha samples.
+    Rgba16(Vec<[u16; 4]>),
+    /// Palette indices of 1, 2, 4 or 8 bits.
+    Indexed(Vec<u8>),
+}
+impl PixelBuffer {
+    /// The number of pixels in the buffer.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Gray8(pixels) | Self::Indexed(pixels) => pixels.len(),
+            Self::Gray16(pixels) => pixels.len(),
+            Self::GrayAlpha8(pixels) => pixels.len(),
+            Self::GrayAlpha16(pixels) => pixels.len(),
+            Self::Rgb8(pixels) => pixels.len(),
+            Self::Rgba8(pixels) => pixels.len(),
+            Self::Rgb16(pixels) => pixels.len(),
+            Self::Rgba16(pixels) => pixels.len(),
+        }
+    }
+
+    /// Whether the buffer has no pixels.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads the pixels of an image described by `header` from its reconstructed scanlines.
+    fn read<'a, S: ScanlineIterator<'a>>(scanlines: S, header: &IHDRChunk) -> Self {
+        let count = header.width as usize * header.height as usize;
+        let depth = header.bit_depth;
+        let byte = |s: u16| s as u8;
+        let word = |s: u16| s;
+        match (header.color_type, depth) {
+            (ColorType::Greyscale, 16) => Self::Gray16(
+                read_pixels(scanlines, count, depth, word)
+                    .map(|[g]| g)
+                    .collect(),
+            ),
+            (ColorType::Greyscale, _) => Self::Gray8(
+                read_pixels(scanlines, count, depth, byte)
+                    .map(|[g]| g)
+                    .collect(),
+            ),
+            (ColorType::GreyscaleWithAlpha, 16) => {
+                Self::GrayAlpha16(read_pixels(scanlines, count, depth, word).collect())
+            }
+            (ColorType::GreyscaleWithAlpha, _) => {
+                Self::GrayAlpha8(read_pixels(scanlines, count, depth, byte).collect())
+            }
+            (ColorType::Truecolor, 16) => {
+                Self::Rgb16(read_pixels(scanlines, count, depth, word).collect())
+            }
+            (ColorType::Truecolor, _) => {
+                Self::Rgb8(read_pixels(scanlines, count, depth, byte).collect())
+            }
+            (ColorType::TruecolorWithAlpha, 16) => {
+                Self::Rgba16(read_pixels(scanlines, count, depth, word).collect())
+            }
+            (ColorType::TruecolorWithAlpha, _) => {
+                Self::Rgba8(read_pixels(scanlines, count, depth, byte).collect())
+            }
+            (ColorType::IndexedColor, _) => Self::Indexed(
+                read_pixels(scanlines, count, depth, byte)
+                    .map(|[i]| i)
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// A PNG image with its pixels in the layout they're stored in, see [`PixelBuffer`].
+///
+/// ```
+/// use simple_png::{NativeImage, PixelBuffer};
+///
+/// let data = std::fs::read("tests/png-suite/basn0g01.png")?;
+/// let image = NativeImage::decode(&data)?;
+/// assert!(matches!(image.pixels, PixelBuffer::Gray8(_)));
+/// let pixels = image.to_pixels()?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeImage<'a> {
+    /// The IHDR metadata for the image, which gives the layout of its pixels.
+    pub header: IHDRChunk,
+    /// The palette of an indexed color image, or a suggested palette for other color types.
+    pub palette: Option<PLTEChunk>,
+    /// The contents of the image's tRNS chunk, see [`tRNSChunk`].
+    pub transparency: Option<Vec<u8>>,
+    /// Any PNG chunks that the library doesn't interpret.
+    pub extra_chunks: Vec<Chunk<'a>>,
+    /// The pixels, top left first, in the color type and bit depth from the header.
+    pub pixels: PixelBuffer,
+}
+impl<'a> NativeImage<'a> {
+    /// Decodes a series of bytes as a PNG without converting its pixels, returning an error if a
+    /// problem was found with the data. The default [`DecodeLimits`] apply.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::decode_with_limits(bytes, &DecodeLimits::new())
+    }
+
+    /// Decodes a series of bytes like [`decode`](Self::decode), failing with
+    /// [`DecodeError::LimitExceeded`] if the image goes over `limits`.
+    pub fn decode_with_limits(bytes: &'a [u8], limits: &DecodeLimits) -> Result<Self, DecodeError> {
+        let ImageChunks {
+            header,
+            palette,
+            transparency,
+            data,
+            extra_chunks,
+        } = read_image_chunks(bytes, limits)?;
+        if header.color_type == ColorType::IndexedColor && palette.is_none() {
+            return Err(DecodeError::MissingPalette);
+        }
+        let data = inflate_image_data(&data, &header)?;
+        let pixels = match header.interlace_method {
+            Interlacing::None => PixelBuffer::read(NormalScanline::new(&data, &header), &header),
+            Interlacing::Adam7 => {
+                PixelBuffer::read(Adam7ScanlineIter::new(&data, &header), &header)
+            }
+        };
+        Ok(Self {
+            header,
+            palette,
+            transparency: transparency.map(|trns| trns.data().to_vec()),
+            extra_chunks,
+            pixels,
+        })
+    }
+
+    /// Converts `pixels` to the given color type and bit depth, the same way
+    /// [`PNG::encode`](crate::PNG::encode) does. Only the first width * height pixels are used.
+    pub fn from_pixels(
+        width: u32,
+        height: u32,
+        pixels: &[Pixel],
+        color_type: ColorType,
+        bit_depth: u8,
+    ) -> Result<Self, EncodeError> {
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(EncodeError::InvalidBitDepth {
+                color_type,
+                bit_depth,
+            });
+        }
+        let count = width as usize * height as usize;
+        let pixels = pixels.get(..count).ok_or(EncodeError::NotEnoughPixels {
+            expected: count,
+            actual: pixels.len(),
+        })?;
+        let header = IHDRChunk::new(width, height, bit_depth, color_type, Interlacing::None);
+        let SerializedImage {
+            data,
+            palette,
+            transparency,
+        } = serialize_pixels(pixels, &header)?;
+        Ok(Self {
+            pixels: PixelBuffer::read(NormalScanline::new(&data, &header), &header),
+            header,
+            palette,
+            transparency,
+            extra_chunks: vec![],
+        })
+    }
+
+    /// Converts an image's pixels to the color type and bit depth in its header, like
+    /// [`from_pixels`](Self::from_pixels).
+    pub fn from_png<T: AsRef<[Pixel]>>(png: &PNG<'a, T>) -> Result<Self, EncodeError> {
+        let header = &png.header;
+        let mut image = Self::from_pixels(
+            header.width,
+            header.height,
+            png.pixels.as_ref(),
+            header.color_type,
+            header.bit_depth,
+        )?;
+        image.header.interlace_method = header.interlace_method;
+        image.extra_chunks = png.extra_chunks.clone();
+        Ok(image)
+    }
+
+    /// Expands every pixel to 16 bit RGBA, applying the palette and tRNS chunk. This gives the
+    /// same pixels as [`PNG::decode`](crate::PNG::decode).
+    pub fn to_pixels(&self) -> Result<Vec<Pixel>, DecodeError> {
+        let (samples, bit_depth) = match &self.pixels {
+            PixelBuffer::Gray8(pixels) | PixelBuffer::Indexed(pixels) => (
+                pixels.iter().map(|&s| s.into()).collect(),
+                self.header.bit_depth.min(8),
+            ),
+            PixelBuffer::Gray16(pixels) => (pixels.clone(), 16),
+            PixelBuffer::GrayAlpha8(pixels) => (widen(pixels), 8),
+            PixelBuffer::GrayAlpha16(pixels) => (pixels.concat(), 16),
+            PixelBuffer::Rgb8(pixels) => (widen(pixels), 8),
+            PixelBuffer::Rgb16(pixels) => (pixels.concat(), 16),
+            PixelBuffer::Rgba8(pixels) => (widen(pixels), 8),
+            PixelBuffer::Rgba16(pixels) => (pixels.concat(), 16),
+        };
+        let transparency = self.transparency.as_deref().map(tRNSChunk::new);
+        samples_to_pixels(
+            &samples,
+            self.header.color_type,
+            bit_depth,
+            self.palette.as_ref(),
+            transparency.as_ref(),
+        )
+    }
+
+    /// Expands the image to 16 bit RGBA like [`to_pixels`](Self::to_pixels). The palette and
+    /// tRNS chunk aren't kept, as [`PNG::encode`](crate::PNG::encode) recreates them.
+    pub fn into_png(self) -> Result<PNG<'a, Vec<Pixel>>, DecodeError> {
+        Ok(PNG {
+            pixels: self.to_pixels()?,
+            header: self.header,
+            extra_chunks: self.extra_chunks,
+        })
+    }
+}
+
+/// Reads every pixel's `N` samples from reconstructed scanlines, placing them by the pixel
+/// indices the scanline iterator gives and converting each sample with `convert`.
+fn read_pixels<'a, const N: usize, T: Copy + Default>(
+    scanlines: impl ScanlineIterator<'a>,
+    count: usize,
+    bit_depth: u8,
+    convert: impl Fn(u16) -> T,
+) -> impl Iterator<Item = [T; N]> {
+    let mut pixels = vec![[T::default(); N]; count];
+    for (scanline, indices) in scanlines {
+        let samples = unpack_samples(&scanline[1..], bit_depth);
+        for (index, samples) in indices.into_iter().zip(samples.chunks_exact(N)) {
+            for (sample, value) in pixels[index].iter_mut().zip(samples) {
+                *sample = convert(*value);
+            }
+        }
+    }
+    pixels.into_iter()
+}
+
+/// The 8 bit samples of every pixel, one after the other.
+fn widen<const N: usize>(pixels: &[[u8; N]]) -> Vec<u16> {
+    pixels.iter().flatten().map(|&s| s.into()).collect()
+}
@@ -0,0 +1,236 @@
+use miniz_oxide::deflate::core;
+use time::OffsetDateTime;
+
+use crate::{
+    chunks::{ihdr::Interlacing, tIMEChunk},
+    depth::Dither,
+    filters::FilterStrategy,
+    gamma::DisplayGamma,
+    limits::DecodeLimits,
+    significant_bits::SampleScaling,
+};
+
+/// Settings that control how an image is encoded, used with [`PNG::encode_with`].
+///
+/// ```
+/// use simple_png::{CompressionStrategy, EncoderOptions, FilterStrategy, Pixel, PNG};
+///
+/// let pixels = vec![Pixel::new(0, 0, 0, u16::MAX); 4];
+/// let options = EncoderOptions::new()
+///     .compression_level(1)
+///     .compression_strategy(CompressionStrategy::Rle)
+///     .filter_strategy(FilterStrategy::Entropy);
+/// let data = PNG::new(2, 2, pixels).encode_with(&options)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// [`PNG::encode_with`]: crate::PNG::encode_with
+#[derive(Debug, Clone)]
+pub struct EncoderOptions {
+    pub(crate) compression_level: u8,
+    pub(crate) compression_strategy: CompressionStrategy,
+    pub(crate) filter_strategy: Option<FilterStrategy>,
+    pub(crate) max_idat_size: usize,
+    pub(crate) interlacing: Option<Interlacing>,
+    pub(crate) optimize: bool,
+    pub(crate) significant_bits: Option<u8>,
+    pub(crate) dither: Dither,
+    pub(crate) time_stamp: Option<TimeStamp>,
+}
+impl EncoderOptions {
+    /// The options [`PNG::encode`](crate::PNG::encode) uses.
+    pub fn new() -> Self {
+        Self {
+            compression_level: 8,
+            compression_strategy: CompressionStrategy::Default,
+            filter_strategy: None,
+            max_idat_size: 8192,
+            interlacing: None,
+            optimize: false,
+            significant_bits: None,
+            dither: Dither::None,
+            time_stamp: None,
+        }
+    }
+
+    /// Sets the deflate compression level, from 0 (no compression) to 10 (slowest, smallest).
+    /// Values above 10 are treated as 10. Defaults to 8.
+    pub fn compression_level(mut self, level: u8) -> Self {
+        self.compression_level = level.min(10);
+        self
+    }
+
+    /// Sets the zlib strategy. Defaults to [`CompressionStrategy::Default`].
+    pub fn compression_strategy(mut self, strategy: CompressionStrategy) -> Self {
+        self.compression_strategy = strategy;
+        self
+    }
+
+    /// Sets how a filter is picked for each scanline. Defaults to the strategy the spec
+    /// recommends for the image's color type and bit depth.
+    pub fn filter_strategy(mut self, strategy: FilterStrategy) -> Self {
+        self.filter_strategy = Some(strategy);
+        self
+    }
+
+    /// Sets the most compressed bytes written to a single IDAT chunk, larger image data is split
+    /// over several chunks. Clamped to between 1 and 2^31 - 1, the largest chunk the spec
+    /// allows. Defaults to 8192.
+    pub fn max_idat_size(mut self, size: usize) -> Self {
+        self.max_idat_size = size.clamp(1, i32::MAX as usize);
+        self
+    }
+
+    /// Sets whether the image is stored interlaced. Defaults to the interlace method in the
+    /// image's header.
+    pub fn interlacing(mut self, interlacing: Interlacing) -> Self {
+        self.interlacing = Some(interlacing);
+        self
+    }
+
+    /// Whether to store the image in the smallest lossless color type and bit depth instead of
+    /// the ones in its header, see [`PNG::encode_optimized`](crate::PNG::encode_optimized).
+    /// Defaults to false.
+    pub fn optimize(mut self, optimize: bool) -> Self {
+        self.optimize = optimize;
+        self
+    }
+
+    /// Declares how many of the high bits of every sample are significant, e.g. 10 for 10 bit
+    /// camera data stored at 16 bits, by writing an sBIT chunk. Clamped to between 1 and the bit
+    /// depth the image is stored at, or 8 for indexed color. Defaults to keeping the image's own
+    /// sBIT chunk, converted to the color type and bit depth it's stored at.
+    pub fn significant_bits(mut self, bits: u8) -> Self {
+        self.significant_bits = Some(bits.clamp(1, 16));
+        self
+    }
+
+    /// Sets how samples are dithered when they're stored at a lower bit depth than they can be
+    /// represented in exactly. Indexed color images aren't dithered. Defaults to
+    /// [`Dither::None`], which rounds every sample to the nearest value.
+    pub fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Writes a tIME chunk with the given modification time, replacing any the image has.
+    /// Defaults to keeping the image's own tIME chunk as it is.
+    pub fn time_stamp(mut self, time_stamp: TimeStamp) -> Self {
+        self.time_stamp = Some(time_stamp);
+        self
+    }
+}
+impl Default for EncoderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Settings that control how an image is decoded, used with [`PNG::decode_with`].
+///
+/// ```
+/// use simple_png::{DecoderOptions, DisplayGamma, PNG};
+///
+/// let data = std::fs::read("tests/png-suite/g25n2c08.png")?;
+/// let options = DecoderOptions::new().gamma_correction(DisplayGamma::Srgb);
+/// let image = PNG::decode_with(&data, &options)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// [`PNG::decode_with`]: crate::PNG::decode_with
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecoderOptions {
+    pub(crate) limits: DecodeLimits,
+    pub(crate) gamma_correction: Option<DisplayGamma>,
+    pub(crate) convert_to_srgb: bool,
+    pub(crate) sample_scaling: Option<SampleScaling>,
+}
+impl DecoderOptions {
+    /// The options [`PNG::decode`](crate::PNG::decode) uses.
+    pub fn new() -> Self {
+        Self {
+            limits: DecodeLimits::new(),
+            gamma_correction: None,
+            convert_to_srgb: false,
+            sample_scaling: None,
+        }
+    }
+
+    /// Sets the limits decoding has to stay within. Defaults to [`DecodeLimits::new`].
+    pub fn limits(mut self, limits: DecodeLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Converts the color channels of the decoded pixels from the gamma given by the image's
+    /// sRGB or gAMA chunk to the gamma of `display`. Alpha is left as it is, and so are images
+    /// with neither chunk. Defaults to returning the samples as they're stored.
+    pub fn gamma_correction(mut self, display: DisplayGamma) -> Self {
+        self.gamma_correction = Some(display);
+        self
+    }
+
+    /// Whether to convert images with an iCCP chunk to sRGB using their ICC profile, see
+    /// [`IccProfile`](crate::IccProfile). The converted image's iCCP, gAMA and cHRM chunks are
+    /// replaced by an sRGB chunk, and decoding fails if the profile isn't supported. Gamma
+    /// correction happens after the conversion. Defaults to false.
+    pub fn convert_to_srgb(mut self, convert: bool) -> Self {
+        self.convert_to_srgb = convert;
+        self
+    }
+
+    /// Adjusts every sample to the significant bits given by the image's sBIT chunk, see
+    /// [`SampleScaling`]. This happens after any color conversion or gamma correction, which
+    /// expect full range samples. Defaults to returning the samples as they're stored.
+    pub fn sample_scaling(mut self, scaling: SampleScaling) -> Self {
+        self.sample_scaling = Some(scaling);
+        self
+    }
+}
+impl Default for DecoderOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The modification time [`EncoderOptions::time_stamp`] writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeStamp {
+    /// The time the image is encoded at.
+    Now,
+    /// A fixed time, so that encoding the same image always gives the same file.
+    Fixed(tIMEChunk),
+}
+impl TimeStamp {
+    pub(crate) fn chunk(&self) -> tIMEChunk {
+        match self {
+            Self::Now => tIMEChunk::from_date_time(OffsetDateTime::now_utc())
+                .expect("the current year fits in a tIME chunk"),
+            Self::Fixed(chunk) => *chunk,
+        }
+    }
+}
+
+/// The zlib strategies used when compressing image data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompressionStrategy {
+    /// Normal deflate compression.
+    #[default]
+    Default,
+    /// Only use matches of at least 5 bytes, which suits filtered image data.
+    Filtered,
+    /// Only look for runs of repeated bytes. Fast, and good for images with large flat areas.
+    Rle,
+    /// Don't look for matches at all, only Huffman encode the bytes. The fastest option.
+    HuffmanOnly,
+}
+impl From<CompressionStrategy> for core::CompressionStrategy {
+    fn from(value: CompressionStrategy) -> Self {
+        match value {
+            CompressionStrategy::Default => Self::Default,
+            CompressionStrategy::Filtered => Self::Filtered,
+            CompressionStrategy::Rle => Self::RLE,
+            CompressionStrategy::HuffmanOnly => Self::HuffmanOnly,
+        }
+    }
+}
@@ -1,26 +1,19 @@
 use crate::{
     chunks::{
         ihdr::{ColorType, IHDRChunk},
-        plte::{Entry, PLTEChunk},
+        plte::{PLTEChunk, PaletteEntry},
         trns::tRNSChunk,
     },
+    depth::scale,
+    error::DecodeError,
     scanlines::ScanlineIterator,
 };
-use anyhow::anyhow;
-use nom::{
-    bits::{bits, complete::take},
-    combinator::map,
-    error::Error,
-    multi::many0,
-    sequence::tuple,
-    IResult,
-};
 
 /// A Pixel of an image.
 ///
-/// Currently the struct offers the highest possible precision PNG supports, RGB + alpha at 16 bit
-/// depth. This should hopefully be replaced with an enum that captures the different variations
-/// of pixel types.
+/// The struct offers the highest possible precision PNG supports, RGB + alpha at 16 bit depth,
+/// so every image can be decoded to it. [`NativeImage`](crate::NativeImage) keeps pixels in the
+/// smaller layout they're stored in instead.
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
 pub struct Pixel {
     /// The red channel of the Pixel. For greyscale images, this will equal both green and blue.
@@ -42,91 +35,143 @@ impl Pixel {
             alpha,
         }
     }
+
+    /// The pixel's brightness as a single grey sample, using the Rec. 709 luma coefficients.
+    /// Pixels that are already grey keep their exact value.
+    pub(crate) fn luma(&self) -> u16 {
+        if self.red == self.green && self.green == self.blue {
+            return self.red;
+        }
+        let weighted = 2126 * self.red as u32 + 7152 * self.green as u32 + 722 * self.blue as u32;
+        ((weighted + 5000) / 10000) as u16
+    }
+
+    /// Alpha composites the pixel over `background`.
+    pub(crate) fn over(self, background: Pixel) -> Pixel {
+        const MAX: u64 = u16::MAX as u64;
+        let (src_alpha, dst_alpha) = (self.alpha as u64, background.alpha as u64);
+        // Both alphas scaled by MAX, to keep the precision of the multiplication.
+        let dst_weight = dst_alpha * (MAX - src_alpha);
+        let alpha = src_alpha * MAX + dst_weight;
+        if alpha == 0 {
+            return Pixel::new(0, 0, 0, 0);
+        }
+        let blend = |s: u16, d: u16| {
+            ((s as u64 * src_alpha * MAX + d as u64 * dst_weight + alpha / 2) / alpha) as u16
+        };
+        Pixel {
+            red: blend(self.red, background.red),
+            green: blend(self.green, background.green),
+            blue: blend(self.blue, background.blue),
+            alpha: ((alpha + MAX / 2) / MAX) as u16,
+        }
+    }
 }
 
-#[derive(Debug)]
-struct IndexedPixel(u8);
-impl IndexedPixel {
-    fn to_pixel(
-        &self,
-        palette: &PLTEChunk,
-        transparency: Option<&tRNSChunk>,
-    ) -> Result<Pixel, anyhow::Error> {
-        let Entry(red, green, blue) = palette
-            .get_color(self.0)
-            .ok_or(anyhow!("color could not be found in palette"))?;
-        Ok(Pixel {
-            red: scale(*red as u16, 8),
-            green: scale(*green as u16, 8),
-            blue: scale(*blue as u16, 8),
-            alpha: transparency.map_or(u16::MAX, |trns| scale(trns.as_palette(self.0).into(), 8)),
-        })
+/// Splits a scanline's bytes into samples of `bit_depth` bits, most significant bits first.
+/// Padding bits at the end of the scanline come out as extra samples.
+pub(crate) fn unpack_samples(bytes: &[u8], bit_depth: u8) -> Vec<u16> {
+    if bit_depth == 16 {
+        return bytes
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
     }
+    let mask = ((1u16 << bit_depth) - 1) as u8;
+    bytes
+        .iter()
+        .flat_map(|byte| {
+            (1..=8 / bit_depth).map(move |i| ((byte >> (8 - i * bit_depth)) & mask) as u16)
+        })
+        .collect()
 }
 
-pub(crate) fn parse_scanline_pixels(
-    scanline: &[u8],
+/// Expands samples of `bit_depth` bits, as they're stored for `color_type`, to pixels, looking
+/// up palette indices in `palette` and applying `transparency`. A tRNS chunk of the wrong length
+/// for `color_type` makes no pixel transparent, the same as having none.
+pub(crate) fn samples_to_pixels(
+    samples: &[u16],
     color_type: ColorType,
     bit_depth: u8,
     palette: Option<&PLTEChunk>,
     transparency: Option<&tRNSChunk>,
-) -> anyhow::Result<Vec<Pixel>> {
+) -> Result<Vec<Pixel>, DecodeError> {
+    let scaled = |sample: u16| scale(sample, bit_depth);
+    let opaque_unless = |transparent: bool| if transparent { 0 } else { u16::MAX };
     let pixels = match color_type {
         ColorType::Greyscale => {
-            let transparent = transparency.map(tRNSChunk::as_greyscale);
-            bits::<_, _, Error<(&[u8], usize)>, Error<&[u8]>, _>(many0(parse_greyscale(
-                bit_depth,
-                transparent,
-            )))(&scanline[1..])
-            .map_err(|e| e.to_owned())?
-            .1
-        }
-        ColorType::IndexedColor => bits::<_, _, Error<(&[u8], usize)>, Error<&[u8]>, _>(many0(
-            parse_indexed_color(bit_depth),
-        ))(&scanline[1..])
-        .map_err(|e| e.to_owned())?
-        .1
-        .into_iter()
-        .map(|p| {
-            // log::info!("{:?}", p);
-            palette
-                .ok_or(anyhow!("A pLTe chunk is needed for IndexedColor type"))
-                .and_then(|plte| p.to_pixel(plte, transparency))
-        })
-        .collect::<anyhow::Result<Vec<_>>>()?,
-        ColorType::GreyscaleWithAlpha => {
-            bits::<_, _, Error<(&[u8], usize)>, Error<&[u8]>, _>(many0(parse_greyscale_with_alpha(
-                bit_depth,
-            )))(&scanline[1..])
-            .map_err(|e| e.to_owned())?
-            .1
+            let key = transparency.and_then(|trns| trns.as_greyscale().ok());
+            samples
+                .iter()
+                .map(|&grey| {
+                    let alpha = opaque_unless(key == Some(grey));
+                    let grey = scaled(grey);
+                    Pixel::new(grey, grey, grey, alpha)
+                })
+                .collect()
         }
         ColorType::Truecolor => {
-            let transparent_sample = transparency.map(tRNSChunk::as_truecolor);
-            bits::<_, _, Error<(&[u8], usize)>, Error<&[u8]>, _>(many0(parse_truecolor(
-                bit_depth,
-                transparent_sample,
-            )))(&scanline[1..])
-            .map_err(|e| e.to_owned())?
-            .1
+            let key = transparency.and_then(|trns| trns.as_truecolor().ok());
+            samples
+                .chunks_exact(3)
+                .map(|rgb| {
+                    let (red, green, blue) = (rgb[0], rgb[1], rgb[2]);
+                    let alpha = opaque_unless(key == Some((red, green, blue)));
+                    Pixel::new(scaled(red), scaled(green), scaled(blue), alpha)
+                })
+                .collect()
         }
-        ColorType::TruecolorWithAlpha => {
-            bits::<_, _, Error<(&[u8], usize)>, Error<&[u8]>, _>(many0(parse_truecolor_with_alpha(
-                bit_depth,
-            )))(&scanline[1..])
-            .map_err(|e| e.to_owned())?
-            .1
+        ColorType::IndexedColor => {
+            let palette = palette.ok_or(DecodeError::MissingPalette)?;
+            samples
+                .iter()
+                .map(|&index| {
+                    let index = index as u8;
+                    let PaletteEntry(red, green, blue) = palette
+                        .get_color(index)
+                        .ok_or(DecodeError::PaletteIndexOutOfRange(index))?;
+                    let alpha = transparency.map_or(u8::MAX, |trns| trns.as_palette(index));
+                    let [red, green, blue, alpha] =
+                        [*red, *green, *blue, alpha].map(|sample| scale(sample.into(), 8));
+                    Ok(Pixel::new(red, green, blue, alpha))
+                })
+                .collect::<Result<_, _>>()?
         }
+        ColorType::GreyscaleWithAlpha => samples
+            .chunks_exact(2)
+            .map(|grey_alpha| {
+                let grey = scaled(grey_alpha[0]);
+                Pixel::new(grey, grey, grey, scaled(grey_alpha[1]))
+            })
+            .collect(),
+        ColorType::TruecolorWithAlpha => samples
+            .chunks_exact(4)
+            .map(|rgba| {
+                let [red, green, blue, alpha] = [rgba[0], rgba[1], rgba[2], rgba[3]].map(scaled);
+                Pixel::new(red, green, blue, alpha)
+            })
+            .collect(),
     };
     Ok(pixels)
 }
 
+pub(crate) fn parse_scanline_pixels(
+    scanline: &[u8],
+    color_type: ColorType,
+    bit_depth: u8,
+    palette: Option<&PLTEChunk>,
+    transparency: Option<&tRNSChunk>,
+) -> Result<Vec<Pixel>, DecodeError> {
+    let samples = unpack_samples(&scanline[1..], bit_depth);
+    samples_to_pixels(&samples, color_type, bit_depth, palette, transparency)
+}
+
 pub(crate) fn parse_pixels<'a, S: ScanlineIterator<'a>>(
     iterator: S,
     header: &IHDRChunk,
     palette: Option<&PLTEChunk>,
     transparency: Option<&tRNSChunk>,
-) -> anyhow::Result<Vec<Pixel>> {
+) -> Result<Vec<Pixel>, DecodeError> {
     let mut total = vec![Pixel::default(); header.width as usize * header.height as usize];
     for (scanline, pixel_indices) in iterator {
         let pixels = parse_scanline_pixels(
@@ -136,126 +181,9 @@ pub(crate) fn parse_pixels<'a, S: ScanlineIterator<'a>>(
             palette,
             transparency,
         )?;
-        for (index, pixel) in pixel_indices.into_iter().zip(pixels.into_iter()) {
+        for (index, pixel) in pixel_indices.into_iter().zip(pixels) {
             total[index] = pixel;
         }
     }
     Ok(total)
 }
-
-fn parse_greyscale(
-    bit_depth: u8,
-    transparent: Option<u16>,
-) -> impl Fn((&[u8], usize)) -> IResult<(&[u8], usize), Pixel> {
-    move |(input, bit_offset): (&[u8], usize)| {
-        let (rest, intensity) = take(bit_depth)((input, bit_offset))?;
-        let alpha = transparent.map_or(u16::MAX, |transparent| {
-            if intensity == transparent {
-                0
-            } else {
-                u16::MAX
-            }
-        });
-        Ok((
-            rest,
-            Pixel {
-                red: scale(intensity, bit_depth),
-                green: scale(intensity, bit_depth),
-                blue: scale(intensity, bit_depth),
-                alpha,
-            },
-        ))
-    }
-}
-
-fn parse_indexed_color(
-    bit_depth: u8,
-) -> impl Fn((&[u8], usize)) -> IResult<(&[u8], usize), IndexedPixel> {
-    move |(input, bit_offset): (&[u8], usize)| {
-        let (rest, pixel) = take(bit_depth)((input, bit_offset))?;
-        Ok((rest, IndexedPixel(pixel)))
-    }
-}
-
-fn parse_greyscale_with_alpha(
-    bit_depth: u8,
-) -> impl Fn((&[u8], usize)) -> IResult<(&[u8], usize), Pixel> {
-    move |(input, bit_offset): (&[u8], usize)| {
-        let (rest, (intensity, alpha)) =
-            tuple((take_scaled(bit_depth), take_scaled(bit_depth)))((input, bit_offset))?;
-
-        Ok((
-            rest,
-            Pixel {
-                red: intensity,
-                green: intensity,
-                blue: intensity,
-                alpha,
-            },
-        ))
-    }
-}
-
-fn parse_truecolor(
-    bit_depth: u8,
-    transparent: Option<(u16, u16, u16)>,
-) -> impl Fn((&[u8], usize)) -> IResult<(&[u8], usize), Pixel> {
-    move |(input, bit_offset): (&[u8], usize)| {
-        let (rest, (red, green, blue)) =
-            tuple((take(bit_depth), take(bit_depth), take(bit_depth)))((input, bit_offset))?;
-
-        let alpha = transparent.map_or(u16::MAX, |samples| {
-            if (red, green, blue) == samples {
-                0
-            } else {
-                u16::MAX
-            }
-        });
-        Ok((
-            rest,
-            Pixel {
-                red: scale(red, bit_depth),
-                green: scale(green, bit_depth),
-                blue: scale(blue, bit_depth),
-                alpha,
-            },
-        ))
-    }
-}
-
-fn parse_truecolor_with_alpha(
-    bit_depth: u8,
-) -> impl Fn((&[u8], usize)) -> IResult<(&[u8], usize), Pixel> {
-    move |(input, bit_offset): (&[u8], usize)| {
-        let (rest, (red, green, blue, alpha)) = tuple((
-            take_scaled(bit_depth),
-            take_scaled(bit_depth),
-            take_scaled(bit_depth),
-            take_scaled(bit_depth),
-        ))((input, bit_offset))?;
-
-        Ok((
-            rest,
-            Pixel {
-                red,
-                green,
-                blue,
-                alpha,
-            },
-        ))
-    }
-}
-
-fn take_scaled<'a>(
-    bit_depth: u8,
-) -> impl FnMut((&'a [u8], usize)) -> IResult<(&'a [u8], usize), u16> {
-    map(take(bit_depth), move |v: u16| scale(v, bit_depth))
-}
-
-fn scale(value: u16, from_bit_depth: u8) -> u16 {
-    if from_bit_depth == 16 {
-        value
-    } else {
-        value * (u16::MAX / (2u16.pow(from_bit_depth as u32) - 1))
-    }
-}
@@ -1,20 +1,38 @@
 use crate::{
     chunks::{
+        bKGDChunk, eXIfChunk,
         idat::IDATChunk,
         iend,
         ihdr::{ColorType, IHDRChunk, Interlacing},
-        iter_chunks, Chunk, ParseableChunk,
+        iter_chunks, pHYsChunk,
+        plte::{PLTEChunk, PaletteEntry},
+        sBITChunk, sRGBChunk, tIMEChunk,
+        trns::tRNSChunk,
+        Background, Chunk, ParseableChunk, TextEntry,
     },
-    filters::{filter_scanlines, reconstruct_scanlines},
+    color_space::ColorSpace,
+    depth::{dither_image, scale, unscale},
+    encoding::{compress_zlib, optimal_format, serialize_pixels, SerializedImage},
+    error::{DecodeError, EncodeError},
+    exif::{set_orientation, Exif, Orientation},
+    filters::{filter_scanlines, reconstruct_scanlines, FilterStrategy},
+    gamma::correct_gamma,
+    icc::IccProfile,
+    info::{read_info, ImageInfo},
+    layout::{decode_into, PixelLayout},
+    limits::DecodeLimits,
+    options::{DecoderOptions, EncoderOptions},
     pixel::{parse_pixels, Pixel},
-    scanlines::{Adam7ScanlineIter, NormalScanline},
+    scanlines::{image_data_length, Adam7ScanlineIter, NormalScanline},
+    significant_bits::{scale_significant_bits, significant_bits_chunk},
 };
-use anyhow::{anyhow, Context};
-use miniz_oxide::{deflate::compress_to_vec_zlib, inflate::decompress_to_vec_zlib};
+use miniz_oxide::inflate::{decompress_to_vec_zlib_with_limit, TINFLStatus};
 use nom::{bytes::complete::tag, IResult};
 
-fn parse_signature(input: &[u8]) -> IResult<&[u8], &[u8]> {
-    tag(b"\x89PNG\x0d\x0a\x1a\x0a")(input)
+pub(crate) const SIGNATURE: &[u8; 8] = b"\x89PNG\x0d\x0a\x1a\x0a";
+
+pub(crate) fn parse_signature(input: &[u8]) -> IResult<&[u8], &[u8]> {
+    tag(SIGNATURE)(input)
 }
 
 /// A PNG image, broken down and interpreted.
@@ -40,15 +58,13 @@ where
     /// The pixel collection should have a length of at least height * width, anything after
     /// that is ignored.
     pub fn new(height: u32, width: u32, pixels: T) -> Self {
-        let ihdr = IHDRChunk {
-            height,
+        let ihdr = IHDRChunk::new(
             width,
-            bit_depth: 8,
-            color_type: ColorType::TruecolorWithAlpha,
-            filter_method: 0,
-            compression_method: 0,
-            interlace_method: Interlacing::None,
-        };
+            height,
+            8,
+            ColorType::TruecolorWithAlpha,
+            Interlacing::None,
+        );
         Self {
             header: ihdr,
             extra_chunks: vec![],
@@ -57,82 +73,298 @@ where
     }
 
     /// Encodes the PNG into bytes that can then be saved to disk or transferred over network.
-    pub fn encode(&self) -> Vec<u8> {
-        let header = IHDRChunk {
-            height: self.header.height,
-            width: self.header.width,
-            bit_depth: 16,
-            color_type: ColorType::TruecolorWithAlpha,
-            filter_method: 0,
-            compression_method: 0,
-            interlace_method: Interlacing::None,
+    ///
+    /// The image is written with the color type, bit depth and interlacing from its header.
+    /// Samples are rounded to the header's bit depth, and indexed images get a palette built
+    /// from the pixels' distinct colors.
+    pub fn encode(&self) -> Result<Vec<u8>, EncodeError> {
+        self.encode_with(&EncoderOptions::new())
+    }
+
+    /// Encodes the PNG using the smallest color type and bit depth that can hold its pixels
+    /// without losing any information, ignoring the color type and bit depth in its header.
+    ///
+    /// Alpha is dropped when every pixel is opaque, or replaced by a tRNS chunk when a single
+    /// fully transparent color is used. Color is dropped when every pixel is grey, samples are
+    /// reduced to the lowest bit depth that represents them exactly, and images with at most 256
+    /// distinct colors are stored as a palette if that takes fewer bits per pixel.
+    pub fn encode_optimized(&self) -> Result<Vec<u8>, EncodeError> {
+        self.encode_with(&EncoderOptions::new().optimize(true))
+    }
+
+    /// Encodes the PNG like [`encode`](Self::encode), with control over compression, filtering
+    /// and chunking through `options`.
+    pub fn encode_with(&self, options: &EncoderOptions) -> Result<Vec<u8>, EncodeError> {
+        let pixels = self.image_pixels()?;
+        let (color_type, bit_depth) = if options.optimize {
+            optimal_format(pixels)
+        } else {
+            (self.header.color_type, self.header.bit_depth)
         };
-        let mut data =
-            Vec::with_capacity((header.height + header.height * header.width * 4) as usize);
-        for line in self.pixels.as_ref().chunks(header.width as usize) {
-            data.push(0);
-            for p in line {
-                data.extend(p.red.to_be_bytes());
-                data.extend(p.green.to_be_bytes());
-                data.extend(p.blue.to_be_bytes());
-                data.extend(p.alpha.to_be_bytes());
-            }
+        let header = IHDRChunk::new(
+            self.header.width,
+            self.header.height,
+            bit_depth,
+            color_type,
+            options.interlacing.unwrap_or(self.header.interlace_method),
+        );
+        if !color_type.allowed_bit_depths().contains(&bit_depth) {
+            return Err(EncodeError::InvalidBitDepth {
+                color_type,
+                bit_depth,
+            });
+        }
+        let pixels = dither_image(pixels, header.width, &header, options.dither);
+        let mut image = serialize_pixels(&pixels, &header)?;
+        let background = self
+            .background()
+            .and_then(|color| background_chunk(color, &header, image.palette.as_mut()));
+        let significant_bits = significant_bits_chunk(
+            options.significant_bits,
+            &self.extra_chunks,
+            &self.header,
+            &header,
+        );
+        Ok(write_png(
+            &header,
+            &self.extra_chunks,
+            significant_bits,
+            background,
+            image,
+            options,
+        ))
+    }
+
+    /// Every piece of textual metadata in the image's tEXt, zTXt and iTXt chunks, in the order
+    /// the chunks appear in [`extra_chunks`](Self::extra_chunks).
+    pub fn text(&self) -> Vec<TextEntry<'_>> {
+        self.extra_chunks
+            .iter()
+            .filter_map(|chunk| match chunk {
+                Chunk::tEXt(chunk) => Some(TextEntry {
+                    keyword: chunk.keyword(),
+                    text: chunk.text(),
+                    language_tag: None,
+                    translated_keyword: None,
+                }),
+                Chunk::zTXt(chunk) => Some(TextEntry {
+                    keyword: chunk.keyword(),
+                    text: chunk.text(),
+                    language_tag: None,
+                    translated_keyword: None,
+                }),
+                Chunk::iTXt(chunk) => Some(TextEntry {
+                    keyword: chunk.keyword(),
+                    text: chunk.text(),
+                    language_tag: Some(chunk.language_tag()),
+                    translated_keyword: Some(chunk.translated_keyword()),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The color space the image's samples are in, following the spec's precedence: an iCCP
+    /// chunk overrides an sRGB chunk, which overrides gAMA and cHRM chunks.
+    pub fn color_space(&self) -> ColorSpace<'_> {
+        ColorSpace::from_chunks(&self.extra_chunks)
+    }
+
+    /// The background color from the image's bKGD chunk, if it has one.
+    pub fn background(&self) -> Option<Pixel> {
+        self.extra_chunks.iter().find_map(|chunk| match chunk {
+            Chunk::bKGD(bkgd) => background_color(bkgd.background(), &self.header),
+            _ => None,
+        })
+    }
+
+    /// When the image was last modified, from its tIME chunk.
+    pub fn modification_time(&self) -> Option<&tIMEChunk> {
+        self.extra_chunks.iter().find_map(|chunk| match chunk {
+            Chunk::tIME(time) => Some(time),
+            _ => None,
+        })
+    }
+
+    /// The image's eXIf chunk, if it has one. Its data can be read with [`Exif::parse`].
+    pub fn exif(&self) -> Option<&eXIfChunk> {
+        self.extra_chunks.iter().find_map(|chunk| match chunk {
+            Chunk::eXIf(exif) => Some(exif),
+            _ => None,
+        })
+    }
+
+    /// Replaces the image's eXIf chunk with `exif`, or adds it if there isn't one. `None`
+    /// removes the chunk, e.g. to strip camera details before sharing the image.
+    pub fn set_exif(&mut self, exif: Option<eXIfChunk>) {
+        let position = self
+            .extra_chunks
+            .iter()
+            .position(|chunk| matches!(chunk, Chunk::eXIf(_)));
+        match (position, exif) {
+            (Some(position), Some(exif)) => self.extra_chunks[position] = Chunk::eXIf(exif),
+            (None, Some(exif)) => self.extra_chunks.push(Chunk::eXIf(exif)),
+            (_, None) => self
+                .extra_chunks
+                .retain(|chunk| !matches!(chunk, Chunk::eXIf(_))),
+        }
+    }
+
+    /// The first width * height pixels, erroring if there aren't enough of them.
+    fn image_pixels(&self) -> Result<&[Pixel], EncodeError> {
+        let pixel_count = self.header.width as usize * self.header.height as usize;
+        let pixels = self.pixels.as_ref();
+        pixels
+            .get(..pixel_count)
+            .ok_or(EncodeError::NotEnoughPixels {
+                expected: pixel_count,
+                actual: pixels.len(),
+            })
+    }
+}
+impl<'a, T> PNG<'a, T>
+where
+    T: AsRef<[Pixel]> + AsMut<[Pixel]>,
+{
+    /// Alpha composites every pixel over `background`, or over the color from the image's bKGD
+    /// chunk if it's `None`. With an opaque background every pixel ends up opaque, and
+    /// [`encode_optimized`](Self::encode_optimized) drops the alpha channel.
+    ///
+    /// Returns false, leaving the pixels as they are, if no background was given and the image
+    /// has no bKGD chunk.
+    pub fn flatten(&mut self, background: Option<Pixel>) -> bool {
+        let Some(background) = background.or_else(|| self.background()) else {
+            return false;
+        };
+        for pixel in self.pixels.as_mut() {
+            *pixel = pixel.over(background);
         }
-        filter_scanlines(&mut data, &header);
-        let compressed_data = compress_to_vec_zlib(&data, 8);
-        let idat = IDATChunk {
-            data: &compressed_data,
+        true
+    }
+
+    /// Turns the pixels the right way up as the Orientation tag in the image's eXIf chunk says.
+    /// The tag is then set to [`Orientation::Normal`] so it isn't applied twice, and the
+    /// header's dimensions and any pHYs chunk are swapped if the image was turned on its side.
+    ///
+    /// Returns false, leaving the image as it is, if the image has no Orientation tag, the
+    /// orientation is already normal or there aren't width * height pixels.
+    pub fn apply_orientation(&mut self) -> bool {
+        let orientation = self
+            .exif()
+            .and_then(|exif| Exif::parse(exif.data()).ok())
+            .and_then(|exif| exif.orientation);
+        let (width, height) = (self.header.width, self.header.height);
+        let pixels = self.pixels.as_mut();
+        let count = width as usize * height as usize;
+        let (Some(orientation), Some(pixels)) = (orientation, pixels.get_mut(..count)) else {
+            return false;
         };
-        let mut png_data = b"\x89PNG\x0d\x0a\x1a\x0a".to_vec();
-        png_data.extend(header.to_bytes());
-        for chunk in self.extra_chunks.iter() {
-            png_data.extend(chunk.to_bytes());
+        if orientation == Orientation::Normal {
+            return false;
+        }
+        let turned = orientation.apply(pixels, width, height);
+        pixels.copy_from_slice(&turned);
+        if orientation.swaps_dimensions() {
+            (self.header.width, self.header.height) = (height, width);
+        }
+        for chunk in self.extra_chunks.iter_mut() {
+            match chunk {
+                Chunk::eXIf(exif) => {
+                    set_orientation(exif.data_mut(), Orientation::Normal);
+                }
+                Chunk::pHYs(phys) if orientation.swaps_dimensions() => {
+                    *phys = pHYsChunk::new(phys.y_axis_ppu(), phys.x_axis_ppu(), phys.unit());
+                }
+                _ => {}
+            }
         }
-        png_data.extend(idat.to_bytes());
-        png_data.extend(iend::IENDChunk.to_bytes());
-        png_data
+        true
     }
 }
 impl<'a> PNG<'a, Vec<Pixel>> {
+    /// Reads an image's dimensions and format from its signature and IHDR chunk, without
+    /// looking at the rest of the file.
+    pub fn probe(bytes: &[u8]) -> Result<ImageInfo, DecodeError> {
+        read_info(bytes, false, &DecodeLimits::new())
+    }
+
+    /// Reads an image's dimensions and format like [`probe`](Self::probe), then checks every
+    /// following chunk up to IEND and lists their types and sizes. Image data is checked against
+    /// its CRC, but not decompressed. The default [`DecodeLimits`] apply.
+    pub fn read_info(bytes: &[u8]) -> Result<ImageInfo, DecodeError> {
+        Self::read_info_with_limits(bytes, &DecodeLimits::new())
+    }
+
+    /// Reads an image's format and chunks like [`read_info`](Self::read_info), failing with
+    /// [`DecodeError::LimitExceeded`] wherever [`decode_with_limits`](Self::decode_with_limits)
+    /// would.
+    pub fn read_info_with_limits(
+        bytes: &[u8],
+        limits: &DecodeLimits,
+    ) -> Result<ImageInfo, DecodeError> {
+        read_info(bytes, true, limits)
+    }
+
+    /// Decodes a PNG straight into `buffer`, with its pixels converted to `layout`, returning
+    /// the image's header. This skips building a [`Pixel`] for every pixel, which saves a pass
+    /// and an allocation when the pixels are only going to be copied elsewhere.
+    ///
+    /// The buffer has to hold at least [`PixelLayout::buffer_len`] bytes, use
+    /// [`probe`](Self::probe) to find the image's size first. The default [`DecodeLimits`]
+    /// apply.
+    pub fn decode_into(
+        bytes: &[u8],
+        buffer: &mut [u8],
+        layout: PixelLayout,
+    ) -> Result<IHDRChunk, DecodeError> {
+        Self::decode_into_with_limits(bytes, buffer, layout, &DecodeLimits::new())
+    }
+
+    /// Decodes a PNG straight into `buffer` like [`decode_into`](Self::decode_into), failing
+    /// with [`DecodeError::LimitExceeded`] if the image goes over `limits`.
+    pub fn decode_into_with_limits(
+        bytes: &[u8],
+        buffer: &mut [u8],
+        layout: PixelLayout,
+        limits: &DecodeLimits,
+    ) -> Result<IHDRChunk, DecodeError> {
+        decode_into(bytes, buffer, layout, limits)
+    }
+
     /// Decodes a series of bytes as a PNG, returning an error if a problem was found with the
-    /// data.
-    pub fn decode(bytes: &'a [u8]) -> anyhow::Result<Self> {
-        let (rest, _) = parse_signature(bytes)
-            .or(Err(anyhow!("Data doesn't start with expected signature")))?;
-        let mut header = IHDRChunk::default();
-        let mut palette = None;
-        let mut transparency = None;
-        let mut data = vec![];
-        let mut extra_chunks = vec![];
-        for chunk in iter_chunks(rest) {
-            log::info!("Found chunk: {:?}", chunk);
-            match chunk? {
-                Chunk::IHDR(ihdr) => header = ihdr,
-                Chunk::PLTE(plte) => palette = Some(plte),
-                Chunk::tRNS(trns) => transparency = Some(trns),
-                Chunk::IDAT(idat) => data.extend(idat.data),
-                Chunk::IEND => break,
-                c => extra_chunks.push(c),
-            }
+    /// data. The default [`DecodeLimits`] apply.
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, DecodeError> {
+        Self::decode_with_limits(bytes, &DecodeLimits::new())
+    }
+
+    /// Decodes a series of bytes as a PNG like [`decode`](Self::decode), failing with
+    /// [`DecodeError::LimitExceeded`] if the image goes over `limits`.
+    pub fn decode_with_limits(bytes: &'a [u8], limits: &DecodeLimits) -> Result<Self, DecodeError> {
+        Self::decode_with(bytes, &DecoderOptions::new().limits(*limits))
+    }
+
+    /// Decodes a series of bytes as a PNG like [`decode`](Self::decode), with control over
+    /// limits and gamma correction through `options`.
+    pub fn decode_with(bytes: &'a [u8], options: &DecoderOptions) -> Result<Self, DecodeError> {
+        let ImageChunks {
+            header,
+            palette,
+            transparency,
+            data,
+            mut extra_chunks,
+        } = read_image_chunks(bytes, &options.limits)?;
+        resolve_background(&mut extra_chunks, palette.as_ref());
+        let mut pixels =
+            decode_image_data(&data, &header, palette.as_ref(), transparency.as_ref())?;
+        if options.convert_to_srgb {
+            convert_to_srgb([pixels.as_mut_slice()], &mut extra_chunks)?;
+        }
+        if let Some(display) = options.gamma_correction {
+            correct_gamma(&mut pixels, &extra_chunks, display);
+        }
+        if let Some(scaling) = options.sample_scaling {
+            scale_significant_bits(&mut pixels, &extra_chunks, &header, scaling);
         }
-        let mut decompressed_data =
-            decompress_to_vec_zlib(&data).context("Failed to decompress image data.")?;
-        reconstruct_scanlines(&mut decompressed_data, &header);
-        let pixels = match header.interlace_method {
-            Interlacing::None => parse_pixels(
-                NormalScanline::new(&decompressed_data, &header),
-                &header,
-                palette.as_ref(),
-                transparency.as_ref(),
-            )?,
-            Interlacing::Adam7 => parse_pixels(
-                Adam7ScanlineIter::new(&decompressed_data, &header),
-                &header,
-                palette.as_ref(),
-                transparency.as_ref(),
-            )?,
-        };
-        log::info!("Processed pixels: {:?}", &pixels[0..header.width as usize]);
         Ok(PNG {
             header,
             extra_chunks,
@@ -140,3 +372,273 @@ impl<'a> PNG<'a, Vec<Pixel>> {
         })
     }
 }
+
+/// The color a bKGD chunk's `background` stands for in an image with the given header. Palette
+/// indices can't be resolved, as the palette isn't kept.
+pub(crate) fn background_color(background: Background, header: &IHDRChunk) -> Option<Pixel> {
+    // Palette colors are always 8 bit, see `resolve_background`.
+    let bit_depth = match header.color_type {
+        ColorType::IndexedColor => 8,
+        _ => header.bit_depth,
+    };
+    let sample = |s: u16| (s as u32) < 1 << bit_depth;
+    match background {
+        Background::Grey(grey) if sample(grey) => {
+            let grey = scale(grey, bit_depth);
+            Some(Pixel::new(grey, grey, grey, u16::MAX))
+        }
+        Background::Rgb(r, g, b) if [r, g, b].into_iter().all(sample) => {
+            let [r, g, b] = [r, g, b].map(|s| scale(s, bit_depth));
+            Some(Pixel::new(r, g, b, u16::MAX))
+        }
+        _ => None,
+    }
+}
+
+/// Replaces a bKGD chunk's palette index with the color it refers to, as decoding an indexed
+/// image expands its palette.
+pub(crate) fn resolve_background(chunks: &mut [Chunk], palette: Option<&PLTEChunk>) {
+    for chunk in chunks.iter_mut() {
+        if let Chunk::bKGD(bkgd) = chunk {
+            if let Background::PaletteIndex(index) = bkgd.background() {
+                if let Some(PaletteEntry(r, g, b)) = palette.and_then(|p| p.get_color(index)) {
+                    let rgb = Background::Rgb(*r as u16, *g as u16, *b as u16);
+                    *bkgd = bKGDChunk::new(rgb);
+                }
+            }
+        }
+    }
+}
+
+/// A bKGD chunk holding `color` in the layout of an image stored as `header` with `palette`.
+/// For indexed images the color is added to the palette if it isn't there and there's room,
+/// otherwise there's no chunk.
+pub(crate) fn background_chunk(
+    color: Pixel,
+    header: &IHDRChunk,
+    palette: Option<&mut PLTEChunk>,
+) -> Option<bKGDChunk> {
+    let depth = header.bit_depth;
+    let background = match header.color_type {
+        ColorType::Greyscale | ColorType::GreyscaleWithAlpha => {
+            Background::Grey(unscale(color.luma(), depth))
+        }
+        ColorType::Truecolor | ColorType::TruecolorWithAlpha => Background::Rgb(
+            unscale(color.red, depth),
+            unscale(color.green, depth),
+            unscale(color.blue, depth),
+        ),
+        ColorType::IndexedColor => {
+            let [r, g, b] = [color.red, color.green, color.blue].map(|s| unscale(s, 8) as u8);
+            let palette = palette?;
+            let entry = PaletteEntry(r, g, b);
+            let colors = palette.colors();
+            let index = match colors.iter().position(|c| *c == entry) {
+                Some(index) => index,
+                None if colors.len() < 1 << depth => {
+                    let index = colors.len();
+                    *palette = PLTEChunk::new([colors, &[entry]].concat());
+                    index
+                }
+                None => return None,
+            };
+            Background::PaletteIndex(index as u8)
+        }
+    };
+    Some(bKGDChunk::new(background))
+}
+
+/// Filters and compresses `image`, and writes it out as a PNG with `extra_chunks` between the
+/// header and the palette. The `significant_bits` and `background` chunks, which depend on how
+/// the image is stored, replace any sBIT and bKGD chunks in `extra_chunks`, and a tIME chunk
+/// from `options` replaces the image's own. Animation chunks are left out, since their frames
+/// can't be kept in step with the re-encoded image.
+pub(crate) fn write_png(
+    header: &IHDRChunk,
+    extra_chunks: &[Chunk],
+    mut significant_bits: Option<sBITChunk>,
+    background: Option<bKGDChunk>,
+    image: SerializedImage,
+    options: &EncoderOptions,
+) -> Vec<u8> {
+    let SerializedImage {
+        mut data,
+        palette,
+        transparency,
+    } = image;
+    let filter_strategy = options
+        .filter_strategy
+        .unwrap_or_else(|| FilterStrategy::recommended(header));
+    filter_scanlines(&mut data, header, filter_strategy);
+    let compressed_data = compress_zlib(
+        &data,
+        options.compression_level,
+        options.compression_strategy,
+    );
+    let mut png_data = SIGNATURE.to_vec();
+    png_data.extend(header.to_bytes());
+    let mut time = options.time_stamp.map(|time_stamp| time_stamp.chunk());
+    for chunk in extra_chunks.iter() {
+        match chunk {
+            // Kept in the same place, so the chunk order doesn't change on a round trip.
+            Chunk::sBIT(_) => {
+                if let Some(sbit) = significant_bits.take() {
+                    png_data.extend(sbit.to_bytes());
+                }
+            }
+            Chunk::tIME(_) if options.time_stamp.is_some() => {
+                if let Some(time) = time.take() {
+                    png_data.extend(time.to_bytes());
+                }
+            }
+            Chunk::acTL(_) | Chunk::fcTL(_) | Chunk::fdAT(_) | Chunk::bKGD(_) => {}
+            Chunk::Unknown(chunk) if chunk.is_malformed() => {}
+            chunk => png_data.extend(chunk.to_bytes()),
+        }
+    }
+    if let Some(time) = time {
+        png_data.extend(time.to_bytes());
+    }
+    if let Some(sbit) = significant_bits {
+        png_data.extend(sbit.to_bytes());
+    }
+    if let Some(plte) = palette {
+        png_data.extend(plte.to_bytes());
+    }
+    if let Some(trns) = transparency {
+        png_data.extend(tRNSChunk::new(&trns).to_bytes());
+    }
+    if let Some(bkgd) = background {
+        png_data.extend(bkgd.to_bytes());
+    }
+    for data in compressed_data.chunks(options.max_idat_size) {
+        png_data.extend(IDATChunk { data }.to_bytes());
+    }
+    png_data.extend(iend::IENDChunk.to_bytes());
+    png_data
+}
+
+/// The chunks of a still image, with the data of its IDAT chunks joined together.
+pub(crate) struct ImageChunks<'a> {
+    pub(crate) header: IHDRChunk,
+    pub(crate) palette: Option<PLTEChunk>,
+    pub(crate) transparency: Option<tRNSChunk<'a>>,
+    pub(crate) data: Vec<u8>,
+    pub(crate) extra_chunks: Vec<Chunk<'a>>,
+}
+
+/// Reads every chunk of a still image up to IEND, checking its header against `limits`.
+pub(crate) fn read_image_chunks<'a>(
+    bytes: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<ImageChunks<'a>, DecodeError> {
+    let (rest, _) = parse_signature(bytes).or(Err(DecodeError::InvalidSignature))?;
+    let mut chunks = iter_chunks(rest, *limits);
+    let header = match chunks.next() {
+        Some(Ok(Chunk::IHDR(ihdr))) => ihdr,
+        Some(Err(e)) => return Err(e),
+        _ => return Err(DecodeError::MissingHeader),
+    };
+    limits.check_header(&header)?;
+    let mut palette = None;
+    let mut transparency = None;
+    let mut data = vec![];
+    let mut found_image_data = false;
+    let mut extra_chunks = vec![];
+    for chunk in chunks {
+        log::info!("Found chunk: {:?}", chunk);
+        match chunk? {
+            Chunk::IHDR(_) => return Err(DecodeError::MissingHeader),
+            Chunk::PLTE(plte) => palette = Some(plte),
+            Chunk::tRNS(trns) => transparency = Some(trns),
+            Chunk::IDAT(idat) => {
+                found_image_data = true;
+                data.extend(idat.data)
+            }
+            Chunk::IEND => break,
+            c => extra_chunks.push(c),
+        }
+    }
+    if !found_image_data {
+        return Err(DecodeError::MissingImageData);
+    }
+    Ok(ImageChunks {
+        header,
+        palette,
+        transparency,
+        data,
+        extra_chunks,
+    })
+}
+
+/// Decompresses, reconstructs and parses the pixels of an image described by `header` from its
+/// compressed `data`.
+pub(crate) fn decode_image_data(
+    data: &[u8],
+    header: &IHDRChunk,
+    palette: Option<&PLTEChunk>,
+    transparency: Option<&tRNSChunk>,
+) -> Result<Vec<Pixel>, DecodeError> {
+    let data = inflate_image_data(data, header)?;
+    match header.interlace_method {
+        Interlacing::None => parse_pixels(
+            NormalScanline::new(&data, header),
+            header,
+            palette,
+            transparency,
+        ),
+        Interlacing::Adam7 => parse_pixels(
+            Adam7ScanlineIter::new(&data, header),
+            header,
+            palette,
+            transparency,
+        ),
+    }
+}
+
+/// Decompresses an image's `data` and reconstructs its filtered scanlines.
+pub(crate) fn inflate_image_data(data: &[u8], header: &IHDRChunk) -> Result<Vec<u8>, DecodeError> {
+    // Anything past the expected length is ignored, so there's no need to inflate it.
+    let expected_length = image_data_length(header);
+    let mut decompressed_data =
+        decompress_to_vec_zlib_with_limit(data, expected_length).or_else(|e| match e.status {
+            TINFLStatus::HasMoreOutput => Ok(e.output),
+            TINFLStatus::FailedCannotMakeProgress | TINFLStatus::NeedsMoreInput => {
+                Err(DecodeError::TruncatedZlibStream)
+            }
+            _ => Err(DecodeError::InvalidZlibStream),
+        })?;
+    if decompressed_data.len() < expected_length {
+        return Err(DecodeError::UnexpectedImageDataLength {
+            expected: expected_length,
+            actual: decompressed_data.len(),
+        });
+    }
+    reconstruct_scanlines(&mut decompressed_data, header)?;
+    Ok(decompressed_data)
+}
+
+/// Converts `images` to sRGB with the profile in the iCCP chunk among `chunks`, if there is one,
+/// and updates the color space chunks to match.
+pub(crate) fn convert_to_srgb<'p>(
+    images: impl IntoIterator<Item = &'p mut [Pixel]>,
+    chunks: &mut Vec<Chunk>,
+) -> Result<(), DecodeError> {
+    let Some(profile) = chunks.iter().find_map(|chunk| match chunk {
+        Chunk::iCCP(iccp) => Some(iccp.profile()),
+        _ => None,
+    }) else {
+        return Ok(());
+    };
+    let profile = IccProfile::parse(profile)?;
+    for pixels in images {
+        profile.convert_to_srgb(pixels);
+    }
+    chunks.retain(|chunk| !matches!(chunk, Chunk::gAMA(_) | Chunk::cHRM(_)));
+    for chunk in chunks.iter_mut() {
+        if matches!(chunk, Chunk::iCCP(_)) {
+            *chunk = Chunk::sRGB(sRGBChunk::new(profile.rendering_intent()));
+        }
+    }
+    Ok(())
+}
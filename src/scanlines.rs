@@ -1,7 +1,7 @@
 use std::ops::RangeFrom;
 
 use crate::{
-    chunks::ihdr::IHDRChunk,
+    chunks::ihdr::{IHDRChunk, Interlacing},
     interlacing::{Adam7Iter, PixelIndicesIter},
     utils::div_ceil,
 };
@@ -111,6 +111,22 @@ impl<T> IteratorExt<T> for T {
     }
 }
 
-const fn calculate_scanline_width(image_width: u32, pixel_width: u8) -> usize {
+pub(crate) const fn calculate_scanline_width(image_width: u32, pixel_width: u8) -> usize {
     div_ceil(image_width as usize * pixel_width as usize, 8) + 1
 }
+
+/// The number of bytes of filtered image data, filter type bytes included, needed for the image
+/// described by `header`.
+pub(crate) fn image_data_length(header: &IHDRChunk) -> usize {
+    // Saturate rather than overflow, the largest headers describe more data than fits in a usize.
+    match header.interlace_method {
+        Interlacing::None => calculate_scanline_width(header.width, header.pixel_width())
+            .saturating_mul(header.height as usize),
+        Interlacing::Adam7 => Adam7Iter::new(header.width as usize, header.height as usize)
+            .map(|sub_image| {
+                calculate_scanline_width(sub_image.width as u32, header.pixel_width())
+                    .saturating_mul(sub_image.height)
+            })
+            .fold(0, usize::saturating_add),
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{
+    chunks::{
+        ihdr::{ColorType, IHDRChunk},
+        sBITChunk, Chunk, SignificantBits,
+    },
+    depth::unscale,
+    pixel::Pixel,
+};
+
+/// How decoded samples are adjusted to the number of significant bits given by the image's sBIT
+/// chunk, used with [`DecoderOptions::sample_scaling`].
+///
+/// Images without an sBIT chunk are treated as if every bit of their bit depth is significant.
+/// Indexed color images use the significant bits of their 8 bit palette.
+///
+/// [`DecoderOptions::sample_scaling`]: crate::DecoderOptions::sample_scaling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleScaling {
+    /// Samples hold just their significant bits, e.g. values up to 1023 for 10 bit data stored
+    /// at 16 bits. Alpha is left as it is for images without an alpha channel.
+    Reduced,
+    /// Samples are rescaled from their significant bits to the full 16 bit range, so the
+    /// largest significant value becomes `u16::MAX` without the stored low bits affecting it.
+    Rescaled,
+}
+
+/// Adjusts the samples of `pixels`, decoded from an image described by `header`, to the
+/// significant bits from the sBIT chunk among `chunks`.
+pub(crate) fn scale_significant_bits(
+    pixels: &mut [Pixel],
+    chunks: &[Chunk],
+    header: &IHDRChunk,
+    scaling: SampleScaling,
+) {
+    let depth = sample_depth(header);
+    let bits = channel_bits(chunks, header).map(|bits| bits.unwrap_or(depth).min(depth));
+    let adjust = |sample: &mut u16, bits: u8| {
+        let significant = unscale(*sample, depth) >> (depth - bits);
+        *sample = match scaling {
+            SampleScaling::Reduced => significant,
+            SampleScaling::Rescaled if bits == depth => *sample,
+            SampleScaling::Rescaled => {
+                let max = (1u32 << bits) - 1;
+                ((significant as u32 * u16::MAX as u32 + max / 2) / max) as u16
+            }
+        };
+    };
+    for pixel in pixels {
+        adjust(&mut pixel.red, bits[0]);
+        adjust(&mut pixel.green, bits[1]);
+        adjust(&mut pixel.blue, bits[2]);
+        if has_alpha(header) {
+            adjust(&mut pixel.alpha, bits[3]);
+        }
+    }
+}
+
+/// The sBIT chunk for an image encoded as `header`. The significant bits are `declared` for
+/// every channel if given, and otherwise come from the sBIT chunk among `chunks`, which is in
+/// the layout for `source`. Bits are capped to the encoded bit depth, and channels without a
+/// known number of significant bits use all of it.
+pub(crate) fn significant_bits_chunk(
+    declared: Option<u8>,
+    chunks: &[Chunk],
+    source: &IHDRChunk,
+    header: &IHDRChunk,
+) -> Option<sBITChunk> {
+    let bits = match declared {
+        Some(bits) => [Some(bits); 4],
+        None => {
+            let bits = channel_bits(chunks, source);
+            if bits == [None; 4] {
+                return None;
+            }
+            bits
+        }
+    };
+    let depth = sample_depth(header);
+    let [red, green, blue, alpha] = bits.map(|bits| bits.unwrap_or(depth).clamp(1, depth));
+    let grey = red.max(green).max(blue);
+    Some(sBITChunk::new(match header.color_type {
+        ColorType::Greyscale => SignificantBits::Grey(grey),
+        ColorType::GreyscaleWithAlpha => SignificantBits::GreyAlpha(grey, alpha),
+        ColorType::Truecolor | ColorType::IndexedColor => SignificantBits::Rgb(red, green, blue),
+        ColorType::TruecolorWithAlpha => SignificantBits::Rgba(red, green, blue, alpha),
+    }))
+}
+
+/// The significant bits of the red, green, blue and alpha channels from the sBIT chunk among
+/// `chunks`, if there is one.
+fn channel_bits(chunks: &[Chunk], header: &IHDRChunk) -> [Option<u8>; 4] {
+    let mut bits = chunks
+        .iter()
+        .find_map(|chunk| match chunk {
+            Chunk::sBIT(sbit) => Some(sbit.significant_bits().channels()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    if !has_alpha(header) {
+        bits[3] = None;
+    }
+    bits
+}
+
+/// The bit depth of the samples the sBIT chunk describes, which is 8 for a palette.
+fn sample_depth(header: &IHDRChunk) -> u8 {
+    match header.color_type {
+        ColorType::IndexedColor => 8,
+        _ => header.bit_depth,
+    }
+}
+
+fn has_alpha(header: &IHDRChunk) -> bool {
+    matches!(
+        header.color_type,
+        ColorType::GreyscaleWithAlpha | ColorType::TruecolorWithAlpha
+    )
+}
@@ -0,0 +1,360 @@
+use std::io::{self, Read};
+
+use miniz_oxide::{
+    inflate::stream::{inflate, InflateState},
+    DataFormat, MZError, MZFlush, MZStatus,
+};
+
+use crate::{
+    chunks::{
+        crc::update_crc,
+        ihdr::{ColorType, IHDRChunk, Interlacing},
+        plte::PLTEChunk,
+        trns::tRNSChunk,
+        ParseableChunk,
+    },
+    error::DecodeError,
+    filters::reconstruct_scanline,
+    limits::{ChunkTally, DecodeLimits},
+    pixel::{parse_scanline_pixels, Pixel},
+    png::SIGNATURE,
+    scanlines::{calculate_scanline_width, image_data_length},
+};
+
+/// How many bytes are read from IDAT chunks, and inflated, at a time.
+const BUFFER_SIZE: usize = 32 * 1024;
+
+/// Decodes a non-interlaced PNG one row at a time from any [`Read`].
+///
+/// Only the previous row and a small amount of compressed and decompressed data are kept in
+/// memory, so images far larger than the available memory can be processed.
+///
+/// ```
+/// use simple_png::StreamingDecoder;
+///
+/// let file = std::fs::File::open("tests/png-suite/basn2c08.png")?;
+/// let decoder = StreamingDecoder::new(std::io::BufReader::new(file))?;
+/// let width = decoder.header().width as usize;
+/// decoder.for_each_row(|row, pixels| {
+///     assert_eq!(pixels.len(), width);
+///     println!("row {row} starts with {:?}", pixels[0]);
+/// })?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+pub struct StreamingDecoder<R> {
+    reader: R,
+    /// Number of bytes read from `reader` so far.
+    offset: usize,
+    /// Offset of the chunk currently being read, for error reporting.
+    chunk_offset: usize,
+    limits: DecodeLimits,
+    tally: ChunkTally,
+    header: IHDRChunk,
+    palette: Option<PLTEChunk>,
+    transparency: Option<Vec<u8>>,
+    /// Bytes left to read in the current IDAT chunk, and the CRC of what has been read so far.
+    idat_remaining: u32,
+    idat_crc: u32,
+    /// The chunk that followed the last IDAT chunk, once it has been reached.
+    trailing_chunk: Option<(u32, [u8; 4])>,
+    inflater: Box<InflateState>,
+    input: Vec<u8>,
+    input_position: usize,
+    output: Vec<u8>,
+    inflated: Vec<u8>,
+    inflated_total: usize,
+    stream_ended: bool,
+    previous: Vec<u8>,
+    current: Vec<u8>,
+    row: u32,
+    finished: bool,
+    failed: bool,
+}
+impl<R: Read> StreamingDecoder<R> {
+    /// Reads the signature and every chunk up to the first IDAT chunk.
+    ///
+    /// Fails with [`DecodeError::UnsupportedInterlacing`] for interlaced images, which can't be
+    /// decoded a row at a time. The default [`DecodeLimits`] apply.
+    pub fn new(reader: R) -> Result<Self, DecodeError> {
+        Self::with_limits(reader, &DecodeLimits::new())
+    }
+
+    /// Reads the signature and every chunk up to the first IDAT chunk like [`new`](Self::new),
+    /// failing with [`DecodeError::LimitExceeded`] if the image goes over `limits`.
+    pub fn with_limits(reader: R, limits: &DecodeLimits) -> Result<Self, DecodeError> {
+        let mut decoder = Self {
+            reader,
+            offset: 0,
+            chunk_offset: 0,
+            limits: *limits,
+            tally: ChunkTally::default(),
+            header: IHDRChunk::default(),
+            palette: None,
+            transparency: None,
+            idat_remaining: 0,
+            idat_crc: 0,
+            trailing_chunk: None,
+            inflater: InflateState::new_boxed(DataFormat::Zlib),
+            input: Vec::with_capacity(BUFFER_SIZE),
+            input_position: 0,
+            output: vec![0; BUFFER_SIZE],
+            inflated: Vec::new(),
+            inflated_total: 0,
+            stream_ended: false,
+            previous: Vec::new(),
+            current: Vec::new(),
+            row: 0,
+            finished: false,
+            failed: false,
+        };
+        let mut signature = [0; 8];
+        decoder.read_exact(&mut signature)?;
+        if &signature != SIGNATURE {
+            return Err(DecodeError::InvalidSignature);
+        }
+        let (length, chunk_type) = decoder.read_chunk_header()?;
+        if &chunk_type != IHDRChunk::HEADER {
+            return Err(DecodeError::MissingHeader);
+        }
+        decoder.header = IHDRChunk::from_bytes(&decoder.read_chunk_data(length, chunk_type)?)?;
+        decoder.limits.check_header(&decoder.header)?;
+        if decoder.header.interlace_method == Interlacing::Adam7 {
+            return Err(DecodeError::UnsupportedInterlacing);
+        }
+        loop {
+            let (length, chunk_type) = decoder.read_chunk_header()?;
+            match &chunk_type {
+                b"IDAT" => {
+                    decoder.start_idat(length, chunk_type);
+                    break;
+                }
+                b"IEND" => return Err(DecodeError::MissingImageData),
+                b"IHDR" => return Err(DecodeError::MissingHeader),
+                b"PLTE" => {
+                    let data = decoder.read_chunk_data(length, chunk_type)?;
+                    decoder.palette = Some(PLTEChunk::from_bytes(&data)?);
+                }
+                b"tRNS" => {
+                    decoder.transparency = Some(decoder.read_chunk_data(length, chunk_type)?);
+                }
+                _ => {
+                    decoder.read_chunk_data(length, chunk_type)?;
+                }
+            }
+        }
+        if decoder.header.color_type == ColorType::IndexedColor && decoder.palette.is_none() {
+            return Err(DecodeError::MissingPalette);
+        }
+        let scanline_length =
+            calculate_scanline_width(decoder.header.width, decoder.header.pixel_width());
+        decoder.previous = vec![0; scanline_length];
+        decoder.current = vec![0; scanline_length];
+        Ok(decoder)
+    }
+
+    /// The IHDR metadata for the image.
+    pub fn header(&self) -> &IHDRChunk {
+        &self.header
+    }
+
+    /// Decodes the next row of pixels, or returns `None` once every row has been decoded and the
+    /// rest of the file has been checked.
+    pub fn next_row(&mut self) -> Result<Option<Vec<Pixel>>, DecodeError> {
+        if self.row == self.header.height {
+            self.finish()?;
+            return Ok(None);
+        }
+        let scanline_length = self.current.len();
+        while self.inflated.len() < scanline_length {
+            self.inflate_more()?;
+        }
+        self.current
+            .copy_from_slice(&self.inflated[..scanline_length]);
+        self.inflated.drain(..scanline_length);
+        reconstruct_scanline(
+            &mut self.current,
+            &self.previous,
+            self.header.filter_width() as usize,
+        )?;
+        let transparency = self.transparency.as_deref().map(tRNSChunk::new);
+        let mut pixels = parse_scanline_pixels(
+            &self.current,
+            self.header.color_type,
+            self.header.bit_depth,
+            self.palette.as_ref(),
+            transparency.as_ref(),
+        )?;
+        // Padding bits at the end of the scanline can be parsed as extra pixels.
+        pixels.truncate(self.header.width as usize);
+        std::mem::swap(&mut self.previous, &mut self.current);
+        self.row += 1;
+        Ok(Some(pixels))
+    }
+
+    /// Calls `f` with the index and pixels of every row, from top to bottom.
+    pub fn for_each_row(mut self, mut f: impl FnMut(u32, &[Pixel])) -> Result<(), DecodeError> {
+        while let Some(pixels) = self.next_row()? {
+            f(self.row - 1, &pixels);
+        }
+        Ok(())
+    }
+
+    /// Decompresses more image data into `inflated`.
+    fn inflate_more(&mut self) -> Result<(), DecodeError> {
+        if self.stream_ended {
+            return Err(DecodeError::UnexpectedImageDataLength {
+                expected: image_data_length(&self.header),
+                actual: self.inflated_total,
+            });
+        }
+        if !self.fill_input()? {
+            return Err(DecodeError::TruncatedZlibStream);
+        }
+        let result = inflate(
+            &mut self.inflater,
+            &self.input[self.input_position..],
+            &mut self.output,
+            MZFlush::None,
+        );
+        self.input_position += result.bytes_consumed;
+        self.inflated
+            .extend_from_slice(&self.output[..result.bytes_written]);
+        self.inflated_total += result.bytes_written;
+        match result.status {
+            Ok(MZStatus::StreamEnd) => self.stream_ended = true,
+            Ok(_) | Err(MZError::Buf) => {}
+            Err(_) => return Err(DecodeError::InvalidZlibStream),
+        }
+        Ok(())
+    }
+
+    /// Makes sure there is unconsumed compressed data in `input`, reading from the following
+    /// IDAT chunks as needed. Returns false once the last IDAT chunk has been read in full.
+    fn fill_input(&mut self) -> Result<bool, DecodeError> {
+        if self.input_position < self.input.len() {
+            return Ok(true);
+        }
+        while self.idat_remaining == 0 {
+            if self.trailing_chunk.is_some() {
+                return Ok(false);
+            }
+            self.read_crc(*b"IDAT", self.idat_crc ^ 0xffffffff)?;
+            let (length, chunk_type) = self.read_chunk_header()?;
+            if &chunk_type == b"IDAT" {
+                self.start_idat(length, chunk_type);
+            } else {
+                self.trailing_chunk = Some((length, chunk_type));
+                return Ok(false);
+            }
+        }
+        let length = usize::min(self.idat_remaining as usize, BUFFER_SIZE);
+        let mut input = std::mem::take(&mut self.input);
+        input.resize(length, 0);
+        self.read_exact(&mut input)?;
+        self.idat_crc = update_crc(self.idat_crc, input.iter().copied());
+        self.idat_remaining -= length as u32;
+        self.input = input;
+        self.input_position = 0;
+        Ok(true)
+    }
+
+    /// Skips any image data that's left and checks the chunks after it, up to IEND.
+    fn finish(&mut self) -> Result<(), DecodeError> {
+        if self.finished {
+            return Ok(());
+        }
+        while self.fill_input()? {
+            self.input_position = self.input.len();
+        }
+        while let Some((length, chunk_type)) = self.trailing_chunk.take() {
+            self.read_chunk_data(length, chunk_type)?;
+            if &chunk_type != b"IEND" {
+                self.trailing_chunk = Some(self.read_chunk_header()?);
+            }
+        }
+        self.finished = true;
+        Ok(())
+    }
+
+    fn start_idat(&mut self, length: u32, chunk_type: [u8; 4]) {
+        self.idat_remaining = length;
+        self.idat_crc = update_crc(0xffffffff, chunk_type);
+    }
+
+    fn read_chunk_header(&mut self) -> Result<(u32, [u8; 4]), DecodeError> {
+        self.chunk_offset = self.offset;
+        let mut header = [0; 8];
+        self.read_exact(&mut header)?;
+        let (length, chunk_type) = header.split_at(4);
+        let length = u32::from_be_bytes(length.try_into().expect("4 bytes were read"));
+        let chunk_type = chunk_type.try_into().expect("4 bytes were read");
+        self.limits
+            .check_chunk(&mut self.tally, &chunk_type, length)?;
+        Ok((length, chunk_type))
+    }
+
+    /// Reads the data and CRC of a chunk whose header has just been read.
+    fn read_chunk_data(
+        &mut self,
+        length: u32,
+        chunk_type: [u8; 4],
+    ) -> Result<Vec<u8>, DecodeError> {
+        let mut data = Vec::new();
+        let read = (&mut self.reader)
+            .take(length.into())
+            .read_to_end(&mut data)
+            .map_err(|e| self.io_error(e))?;
+        self.offset += read;
+        if read < length as usize {
+            return Err(DecodeError::UnexpectedEndOfFile {
+                offset: self.chunk_offset,
+            });
+        }
+        let crc = update_crc(update_crc(0xffffffff, chunk_type), data.iter().copied());
+        self.read_crc(chunk_type, crc ^ 0xffffffff)?;
+        Ok(data)
+    }
+
+    fn read_crc(&mut self, chunk_type: [u8; 4], actual: u32) -> Result<(), DecodeError> {
+        let mut crc = [0; 4];
+        self.read_exact(&mut crc)?;
+        let expected = u32::from_be_bytes(crc);
+        if expected != actual {
+            return Err(DecodeError::CrcMismatch {
+                chunk_type,
+                offset: self.chunk_offset,
+                expected,
+                actual,
+            });
+        }
+        Ok(())
+    }
+
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), DecodeError> {
+        self.reader
+            .read_exact(buffer)
+            .map_err(|e| self.io_error(e))?;
+        self.offset += buffer.len();
+        Ok(())
+    }
+
+    fn io_error(&self, error: io::Error) -> DecodeError {
+        match error.kind() {
+            io::ErrorKind::UnexpectedEof => DecodeError::UnexpectedEndOfFile {
+                offset: self.chunk_offset,
+            },
+            kind => DecodeError::Io(kind),
+        }
+    }
+}
+impl<R: Read> Iterator for StreamingDecoder<R> {
+    type Item = Result<Vec<Pixel>, DecodeError>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let row = self.next_row().transpose();
+        self.failed = matches!(row, Some(Err(_)));
+        row
+    }
+}
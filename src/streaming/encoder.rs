@@ -0,0 +1,202 @@
+use std::io::{self, Write};
+
+use miniz_oxide::deflate::core::{compress_to_output, CompressorOxide, TDEFLFlush, TDEFLStatus};
+
+use crate::{
+    chunks::{
+        idat::IDATChunk,
+        iend::IENDChunk,
+        ihdr::{ColorType, IHDRChunk, Interlacing},
+        ParseableChunk,
+    },
+    depth::Ditherer,
+    encoding::{serialize_pixels, zlib_compressor},
+    error::EncodeError,
+    filters::{FilterStrategy, ScanlineFilter},
+    options::EncoderOptions,
+    pixel::Pixel,
+    png::SIGNATURE,
+    scanlines::calculate_scanline_width,
+};
+
+/// Encodes a non-interlaced PNG one row at a time to any [`Write`].
+///
+/// The signature and IHDR chunk are written as soon as the encoder is created, and compressed
+/// image data is written in IDAT chunks as it fills them, so only about one chunk and one row
+/// are kept in memory.
+///
+/// Rows are stored in the header's color type and bit depth like [`PNG::encode`] does, except
+/// that alpha is always dropped for color types without an alpha channel. Indexed color images
+/// can't be written, since their palette has to come before any rows.
+///
+/// ```
+/// use simple_png::{ColorType, Pixel, StreamingEncoder, PNG};
+///
+/// let mut header = PNG::new(16, 32, vec![]).header;
+/// header.color_type = ColorType::Greyscale;
+/// let mut encoder = StreamingEncoder::new(Vec::new(), header)?;
+/// for y in 0..16 {
+///     let grey = y * 4096;
+///     encoder.write_row(&vec![Pixel::new(grey, grey, grey, u16::MAX); 32])?;
+/// }
+/// let data = encoder.finish()?;
+/// assert_eq!(PNG::decode(&data)?.header.height, 16);
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+///
+/// [`PNG::encode`]: crate::PNG::encode
+pub struct StreamingEncoder<W: Write> {
+    writer: W,
+    header: IHDRChunk,
+    /// The header for a single row, used to serialize rows one at a time.
+    row_header: IHDRChunk,
+    compressor: Box<CompressorOxide>,
+    ditherer: Ditherer,
+    filter: ScanlineFilter,
+    /// Compressed data that hasn't filled an IDAT chunk yet.
+    compressed: Vec<u8>,
+    max_idat_size: usize,
+    rows: u32,
+}
+impl<W: Write> StreamingEncoder<W> {
+    /// Writes the signature and IHDR chunk for an image described by `header`, using the
+    /// default [`EncoderOptions`].
+    pub fn new(writer: W, header: IHDRChunk) -> Result<Self, EncodeError> {
+        Self::with_options(writer, header, &EncoderOptions::new())
+    }
+
+    /// Writes the signature and IHDR chunk for an image described by `header`, compressing and
+    /// chunking as `options` says. A tIME chunk follows the header if `options` has a
+    /// [`time_stamp`](EncoderOptions::time_stamp). [`EncoderOptions::optimize`] is ignored, since
+    /// the best format can't be known before every row has been seen.
+    pub fn with_options(
+        mut writer: W,
+        header: IHDRChunk,
+        options: &EncoderOptions,
+    ) -> Result<Self, EncodeError> {
+        let header = IHDRChunk {
+            filter_method: 0,
+            compression_method: 0,
+            interlace_method: options.interlacing.unwrap_or(header.interlace_method),
+            ..header
+        };
+        if !header
+            .color_type
+            .allowed_bit_depths()
+            .contains(&header.bit_depth)
+        {
+            return Err(EncodeError::InvalidBitDepth {
+                color_type: header.color_type,
+                bit_depth: header.bit_depth,
+            });
+        }
+        if header.color_type == ColorType::IndexedColor {
+            return Err(EncodeError::UnsupportedColorType(header.color_type));
+        }
+        if header.interlace_method == Interlacing::Adam7 {
+            return Err(EncodeError::UnsupportedInterlacing);
+        }
+        writer.write_all(SIGNATURE).map_err(io_error)?;
+        writer.write_all(&header.to_bytes()).map_err(io_error)?;
+        if let Some(time_stamp) = options.time_stamp {
+            writer
+                .write_all(&time_stamp.chunk().to_bytes())
+                .map_err(io_error)?;
+        }
+        let filter_strategy = options
+            .filter_strategy
+            .unwrap_or_else(|| FilterStrategy::recommended(&header));
+        Ok(Self {
+            writer,
+            row_header: IHDRChunk {
+                height: 1,
+                ..header
+            },
+            compressor: zlib_compressor(options.compression_level, options.compression_strategy),
+            ditherer: Ditherer::for_header(options.dither, header.width, &header),
+            filter: ScanlineFilter::new(
+                calculate_scanline_width(header.width, header.pixel_width()),
+                header.filter_width() as usize,
+                filter_strategy,
+            ),
+            compressed: Vec::with_capacity(options.max_idat_size.min(1 << 20)),
+            max_idat_size: options.max_idat_size,
+            header,
+            rows: 0,
+        })
+    }
+
+    /// The header the image is being written with.
+    pub fn header(&self) -> &IHDRChunk {
+        &self.header
+    }
+
+    /// Filters, compresses and writes the next row. `pixels` should hold at least width pixels,
+    /// anything after that is ignored.
+    pub fn write_row(&mut self, pixels: &[Pixel]) -> Result<(), EncodeError> {
+        if self.rows == self.header.height {
+            return Err(EncodeError::TooManyRows {
+                height: self.header.height,
+            });
+        }
+        let width = self.header.width as usize;
+        let pixels = pixels.get(..width).ok_or(EncodeError::NotEnoughPixels {
+            expected: width,
+            actual: pixels.len(),
+        })?;
+        let mut pixels = pixels.to_vec();
+        self.ditherer.dither_row(&mut pixels);
+        let mut scanline = serialize_pixels(&pixels, &self.row_header)?.data;
+        self.filter.filter(&mut scanline);
+        self.compress(&scanline, TDEFLFlush::None);
+        self.rows += 1;
+        while self.compressed.len() >= self.max_idat_size {
+            let rest = self.compressed.split_off(self.max_idat_size);
+            write_idat(&mut self.writer, &self.compressed)?;
+            self.compressed = rest;
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining image data and the IEND chunk, returning the writer. Fails if fewer
+    /// rows were written than the header's height.
+    pub fn finish(mut self) -> Result<W, EncodeError> {
+        if self.rows < self.header.height {
+            return Err(EncodeError::MissingRows {
+                expected: self.header.height,
+                actual: self.rows,
+            });
+        }
+        self.compress(&[], TDEFLFlush::Finish);
+        for data in self.compressed.chunks(self.max_idat_size) {
+            write_idat(&mut self.writer, data)?;
+        }
+        self.writer
+            .write_all(&IENDChunk.to_bytes())
+            .map_err(io_error)?;
+        self.writer.flush().map_err(io_error)?;
+        Ok(self.writer)
+    }
+
+    fn compress(&mut self, data: &[u8], flush: TDEFLFlush) {
+        let compressed = &mut self.compressed;
+        let (status, _) = compress_to_output(&mut self.compressor, data, flush, |bytes| {
+            compressed.extend_from_slice(bytes);
+            true
+        });
+        assert!(
+            matches!(status, TDEFLStatus::Okay | TDEFLStatus::Done),
+            "compressing to a Vec can't fail"
+        );
+    }
+}
+
+fn write_idat(writer: &mut impl Write, data: &[u8]) -> Result<(), EncodeError> {
+    writer
+        .write_all(&IDATChunk { data }.to_bytes())
+        .map_err(io_error)
+}
+
+fn io_error(error: io::Error) -> EncodeError {
+    EncodeError::Io(error.kind())
+}
@@ -0,0 +1,6 @@
+//! Decoding and encoding images without holding all of their data in memory at once.
+mod decoder;
+mod encoder;
+
+pub use decoder::StreamingDecoder;
+pub use encoder::StreamingEncoder;
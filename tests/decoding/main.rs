@@ -1,4 +1,16 @@
-use simple_png::{Pixel, PNG};
+use simple_png::{
+    animation::Animation,
+    chunks::{
+        acTLChunk, bKGDChunk, cHRMChunk, eXIfChunk, fcTLChunk, fdATChunk, gAMAChunk, iCCPChunk,
+        iTXtChunk, pHYsChunk, read_chunks, sBITChunk, sRGBChunk, tEXtChunk, tIMEChunk, tRNSChunk,
+        write_chunks, zTXtChunk, Background, BlendOp, Chunk, DisposeOp, IDATChunk, IHDRChunk,
+        PLTEChunk, PaletteEntry, RawChunk, RenderingIntent, SignificantBits, Unit,
+    },
+    ChunkInfo, ColorSpace, ColorType, DecodeError, DecodeLimits, DecoderOptions, DisplayGamma,
+    EncodeError, EncoderOptions, Exif, IccProfile, Interlacing, NativeImage, Orientation, Pixel,
+    PixelBuffer, PixelFormat, PixelLayout, SampleScaling, StreamingDecoder, StreamingEncoder,
+    TimeStamp, PNG,
+};
 
 const FILE: &[u8] = include_bytes!("test-2.png");
 
@@ -26,19 +38,21 @@ macro_rules! png_suite {
 macro_rules! png_suite_fail {
     ($($file:ident),*) => {
         mod png_suite_failures {
-            use simple_png::PNG;
+            use simple_png::{StreamingDecoder, PNG};
 
             $(
             #[test]
-            #[should_panic]
             fn $file() {
                 let input = std::fs::read(concat!(
-                    "tests/decoding/png-suite/",
+                    "tests/png-suite/",
                     stringify!($file),
                     ".png"
                 ))
                 .unwrap();
-                PNG::decode(&input).unwrap();
+                assert!(PNG::decode(&input).is_err());
+                let streamed = StreamingDecoder::new(&input[..])
+                    .and_then(|decoder| decoder.collect::<Result<Vec<_>, _>>());
+                assert!(streamed.is_err());
             }
             )*
         }
@@ -58,7 +72,7 @@ fn test_round_trip() {
         Pixel::new(u16::MAX, u16::MAX, u16::MAX, u16::MAX),
         Pixel::new(0, 0, 0, u16::MAX),
     ];
-    let data = PNG::new(2, 2, pixels.clone()).encode();
+    let data = PNG::new(2, 2, pixels.clone()).encode().unwrap();
     let p = PNG::decode(&data).unwrap();
     assert_eq!(pixels, p.pixels);
 }
@@ -69,10 +83,116 @@ fn test_round_trip() {
 #[should_panic]
 fn test_round_trip_2() {
     let p = PNG::decode(FILE).unwrap();
-    let data = PNG::new(p.header.height, p.header.width, &p.pixels).encode();
+    let data = PNG::new(p.header.height, p.header.width, &p.pixels)
+        .encode()
+        .unwrap();
     assert_eq!(FILE, data);
 }
 
+#[test]
+fn test_decode_errors() {
+    let decode = |file: &str| {
+        let input = std::fs::read(format!("tests/png-suite/{file}.png")).unwrap();
+        PNG::decode(&input).unwrap_err()
+    };
+    assert_eq!(decode("xs1n0g01"), DecodeError::InvalidSignature);
+    assert_eq!(
+        decode("xc1n0g08"),
+        DecodeError::InvalidHeaderField {
+            field: "color_type",
+            value: 1
+        }
+    );
+    assert_eq!(
+        decode("xd3n2c08"),
+        DecodeError::InvalidHeaderField {
+            field: "bit_depth",
+            value: 3
+        }
+    );
+    assert!(matches!(
+        decode("xcsn0g01"),
+        DecodeError::CrcMismatch {
+            chunk_type: [b'I', b'D', b'A', b'T'],
+            ..
+        }
+    ));
+    assert!(matches!(
+        decode("xhdn0g08"),
+        DecodeError::CrcMismatch {
+            chunk_type: [b'I', b'H', b'D', b'R'],
+            offset: 8,
+            ..
+        }
+    ));
+    assert_eq!(decode("xdtn0g01"), DecodeError::MissingImageData);
+}
+
+#[test]
+fn test_truncated_data_is_an_error() {
+    for length in 0..FILE.len() {
+        assert!(PNG::decode(&FILE[..length]).is_err());
+    }
+}
+
+/// Reads at most one byte at a time, to exercise the streaming decoder's buffering.
+struct ByteReader<'a>(&'a [u8]);
+impl std::io::Read for ByteReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match (self.0.split_first(), buf.first_mut()) {
+            (Some((byte, rest)), Some(out)) => {
+                *out = *byte;
+                self.0 = rest;
+                Ok(1)
+            }
+            _ => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn test_streaming_matches_decode() {
+    let mut paths: Vec<_> = std::fs::read_dir("tests/png-suite/")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            name.ends_with(".png") && !name.starts_with('x')
+        })
+        .collect();
+    paths.sort();
+    for path in paths {
+        let input = std::fs::read(&path).unwrap();
+        let image = PNG::decode(&input).unwrap();
+        let decoder = StreamingDecoder::new(ByteReader(&input));
+        if image.header.interlace_method == simple_png::Interlacing::Adam7 {
+            assert_eq!(decoder.err(), Some(DecodeError::UnsupportedInterlacing));
+            continue;
+        }
+        let rows: Vec<Vec<Pixel>> = decoder.unwrap().map(Result::unwrap).collect();
+        assert_eq!(rows.len(), image.header.height as usize, "{path:?}");
+        assert_eq!(rows.concat(), image.pixels, "{path:?}");
+    }
+}
+
+#[test]
+fn test_streaming_rows_are_numbered() {
+    let decoder = StreamingDecoder::new(FILE).unwrap();
+    let height = decoder.header().height;
+    let mut rows = vec![];
+    decoder.for_each_row(|row, _| rows.push(row)).unwrap();
+    assert_eq!(rows, (0..height).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_streaming_truncated_data_is_an_error() {
+    for length in 0..FILE.len() {
+        let streamed = StreamingDecoder::new(&FILE[..length])
+            .and_then(|decoder| decoder.collect::<Result<Vec<_>, _>>());
+        assert!(streamed.is_err(), "{length}");
+    }
+}
+
 png_suite!(
     basn0g01, basn0g02, basn0g04, basn0g08, basn0g16, basn2c08, basn2c16, basn3p01, basn3p02,
     basn3p04, basn3p08, basn4a08, basn4a16, basn6a08, basn6a16, bgan6a08, bgan6a16, bgbn4a08,
@@ -98,3 +218,1748 @@ png_suite_fail!(
     xc1n0g08, xc9n2c08, xcrn0g04, xcsn0g01, xd0n2c08, xd3n2c08, xd9n2c08, xdtn0g01, xhdn0g08,
     xlfn0g04, xs1n0g01, xs2n0g01, xs4n0g01, xs7n0g01
 );
+
+#[test]
+fn test_decode_limits() {
+    let input = std::fs::read("tests/png-suite/ct1n0g04.png").unwrap();
+    let cases = [
+        (DecodeLimits::new().max_width(31), "max_width"),
+        (DecodeLimits::new().max_height(31), "max_height"),
+        (DecodeLimits::new().max_pixels(32 * 32 - 1), "max_pixels"),
+        (
+            DecodeLimits::new().max_decompressed_bytes(100),
+            "max_decompressed_bytes",
+        ),
+        (DecodeLimits::new().max_chunk_bytes(100), "max_chunk_bytes"),
+        (DecodeLimits::new().max_chunks(3), "max_chunks"),
+        (
+            DecodeLimits::new().max_text_chunk_size(10),
+            "max_text_chunk_size",
+        ),
+    ];
+    for (limits, limit) in cases {
+        let expected = DecodeError::LimitExceeded { limit };
+        assert_eq!(
+            PNG::decode_with_limits(&input, &limits).unwrap_err(),
+            expected
+        );
+        assert_eq!(
+            PNG::read_info_with_limits(&input, &limits).unwrap_err(),
+            expected
+        );
+        let streamed = StreamingDecoder::with_limits(&input[..], &limits)
+            .and_then(|decoder| decoder.collect::<Result<Vec<_>, _>>());
+        assert_eq!(streamed.unwrap_err(), expected);
+    }
+    PNG::decode_with_limits(&input, &DecodeLimits::unlimited()).unwrap();
+    PNG::decode_with_limits(&input, &DecodeLimits::new().max_width(32).max_height(32)).unwrap();
+}
+
+#[test]
+fn test_huge_dimensions_are_rejected_before_allocating() {
+    // A valid header claiming a 2^30 x 2^30 image, followed by the image data of a tiny one.
+    let mut header = PNG::new(1, 1, vec![]).header;
+    header.width = 1 << 30;
+    header.height = 1 << 30;
+    let mut input = vec![];
+    // Creating the encoder writes the signature and header straight away.
+    drop(StreamingEncoder::new(&mut input, header).unwrap());
+    let tiny = PNG::new(1, 1, vec![Pixel::default()]).encode().unwrap();
+    input.extend_from_slice(&tiny[8 + 25..]);
+    assert_eq!(
+        PNG::decode(&input).unwrap_err(),
+        DecodeError::LimitExceeded { limit: "max_width" }
+    );
+    let limits = DecodeLimits::new().max_width(u32::MAX).max_height(u32::MAX);
+    assert_eq!(
+        PNG::decode_with_limits(&input, &limits).unwrap_err(),
+        DecodeError::LimitExceeded {
+            limit: "max_pixels"
+        }
+    );
+    assert!(matches!(
+        StreamingDecoder::with_limits(&input[..], &limits),
+        Err(DecodeError::LimitExceeded {
+            limit: "max_pixels"
+        })
+    )); // The header alone is enough to tell the image is too big.
+    assert_eq!(
+        PNG::read_info(&input).unwrap_err(),
+        DecodeError::LimitExceeded { limit: "max_width" }
+    );
+    assert_eq!(PNG::probe(&input).unwrap().width, 1 << 30);
+}
+
+#[test]
+fn test_probe_reads_header_only() {
+    let input = std::fs::read("tests/png-suite/basi3p02.png").unwrap();
+    let info = PNG::probe(&input).unwrap();
+    assert_eq!((info.width, info.height), (32, 32));
+    assert_eq!(info.color_type, ColorType::IndexedColor);
+    assert_eq!(info.bit_depth, 2);
+    assert_eq!(info.interlace_method, Interlacing::Adam7);
+    assert_eq!(
+        info.chunks,
+        [ChunkInfo {
+            chunk_type: *b"IHDR",
+            length: 13
+        }]
+    );
+    // Only the signature and header need to be there.
+    assert_eq!(PNG::probe(&input[..33]).unwrap(), info);
+    assert_eq!(
+        PNG::probe(&input[..32]).unwrap_err(),
+        DecodeError::UnexpectedEndOfFile { offset: 8 }
+    );
+    assert_eq!(
+        PNG::probe(&input[1..]).unwrap_err(),
+        DecodeError::InvalidSignature
+    );
+}
+
+#[test]
+fn test_read_info_lists_chunks() {
+    let input = std::fs::read("tests/png-suite/basn3p02.png").unwrap();
+    let info = PNG::read_info(&input).unwrap();
+    let types: Vec<_> = info.chunks.iter().map(|c| &c.chunk_type).collect();
+    assert_eq!(
+        types,
+        [b"IHDR", b"gAMA", b"sBIT", b"PLTE", b"IDAT", b"IEND"]
+    );
+    // Every chunk takes 12 bytes besides its data.
+    let total: usize = info.chunks.iter().map(|c| c.length as usize + 12).sum();
+    assert_eq!(total + 8, input.len());
+    assert_eq!(info.chunks[0].length, 13);
+    assert_eq!(info.chunks[3].length, 12);
+
+    // Problems after the header only show up when every chunk is read.
+    let mut corrupt = input.clone();
+    let idat = corrupt.len() - 20;
+    corrupt[idat] ^= 1;
+    assert!(PNG::probe(&corrupt).is_ok());
+    assert!(matches!(
+        PNG::read_info(&corrupt),
+        Err(DecodeError::CrcMismatch { .. })
+    ));
+}
+
+#[test]
+fn test_png_suite_chunks_round_trip() {
+    let mut paths: Vec<_> = std::fs::read_dir("tests/png-suite/")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            name.ends_with(".png") && !name.starts_with('x')
+        })
+        .collect();
+    paths.sort();
+    for path in paths {
+        let input = std::fs::read(&path).unwrap();
+        let chunks = read_chunks(&input).unwrap();
+        for chunk in chunks.iter() {
+            let bytes = chunk.to_bytes();
+            let data = &bytes[8..bytes.len() - 4];
+            assert_eq!(&bytes[4..8], chunk.chunk_type(), "{path:?}");
+            assert_eq!(
+                Chunk::parse(chunk.chunk_type(), data).unwrap(),
+                *chunk,
+                "{path:?}"
+            );
+        }
+        assert_eq!(write_chunks(&chunks), input, "{path:?}");
+    }
+}
+
+#[test]
+fn test_chunks_can_be_built() {
+    let header = IHDRChunk::new(3, 1, 2, ColorType::IndexedColor, Interlacing::None);
+    let palette = PLTEChunk::new(vec![
+        PaletteEntry(255, 0, 0),
+        PaletteEntry(0, 255, 0),
+        PaletteEntry(0, 0, 255),
+    ]);
+    let alphas = [255, 128, 0];
+    let image = PNG::new(1, 1, vec![Pixel::default()]).encode().unwrap();
+    let image_data = match &read_chunks(&image).unwrap()[1] {
+        Chunk::IDAT(idat) => idat.data().to_vec(),
+        c => panic!("{c:?}"),
+    };
+    let chunks = [
+        Chunk::IHDR(header.clone()),
+        Chunk::pHYs(pHYsChunk::new(2835, 2835, Unit::Meter)),
+        Chunk::PLTE(palette.clone()),
+        Chunk::tRNS(tRNSChunk::new(&alphas)),
+        Chunk::Unknown(RawChunk::new(b"prIv", b"hello")),
+        Chunk::IDAT(IDATChunk::new(&image_data)),
+        Chunk::IEND,
+    ];
+    let bytes = write_chunks(&chunks);
+    let read = read_chunks(&bytes).unwrap();
+    assert_eq!(read, chunks);
+    match &read[1] {
+        Chunk::pHYs(phys) => {
+            assert_eq!((phys.x_axis_ppu(), phys.y_axis_ppu()), (2835, 2835));
+            assert_eq!(phys.unit(), Unit::Meter);
+        }
+        c => panic!("{c:?}"),
+    }
+    match &read[3] {
+        Chunk::tRNS(trns) => assert_eq!(trns.as_palette(1), 128),
+        c => panic!("{c:?}"),
+    }
+    assert_eq!(palette.get_color(2), Some(&PaletteEntry(0, 0, 255)));
+    assert_eq!(header.compression_method(), 0);
+    match &read[4] {
+        Chunk::Unknown(raw) => assert_eq!((raw.chunk_type(), raw.data()), (b"prIv", &b"hello"[..])),
+        c => panic!("{c:?}"),
+    }
+}
+
+#[test]
+fn test_png_suite_text() {
+    for name in [
+        "ct0n0g04", "ct1n0g04", "ctzn0g04", "cten0g04", "ctfn0g04", "ctgn0g04", "cthn0g04",
+        "ctjn0g04",
+    ] {
+        let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+        let image = PNG::decode(&input).unwrap();
+        insta::assert_debug_snapshot!(format!("text_{name}"), image.text());
+    }
+}
+
+#[test]
+fn test_text_chunks_round_trip() {
+    let chunks = [
+        Chunk::tEXt(tEXtChunk::new("Title", "Caf\u{e9}").unwrap()),
+        Chunk::zTXt(zTXtChunk::new("Description", &"long text ".repeat(100)).unwrap()),
+        Chunk::iTXt(iTXtChunk::new("Author", "el", "\u{3a3}", "\u{3a3}\u{3c5}", false).unwrap()),
+        Chunk::iTXt(iTXtChunk::new("Copyright", "", "", "\u{a9} 2023", true).unwrap()),
+    ];
+    let mut image = PNG::new(1, 1, vec![Pixel::default()]);
+    image.extra_chunks.extend(chunks.iter().cloned());
+    let encoded = image.encode().unwrap();
+    let decoded = PNG::decode(&encoded).unwrap();
+    assert_eq!(decoded.extra_chunks, chunks);
+    let text = decoded.text();
+    assert_eq!(text[0].text, "Caf\u{e9}");
+    assert_eq!(text[1].text.len(), 1000);
+    assert_eq!(text[2].language_tag, Some("el"));
+    assert_eq!(text[2].translated_keyword, Some("\u{3a3}"));
+    assert_eq!(text[3].text, "\u{a9} 2023");
+    // tEXt stores Latin-1, so "é" is a single byte.
+    assert!(encoded.windows(10).any(|w| w == b"Title\0Caf\xe9"));
+}
+
+#[test]
+fn test_invalid_text_is_rejected() {
+    for keyword in [
+        "",
+        " Title",
+        "Title ",
+        "Two  spaces",
+        &"k".repeat(80),
+        "\u{3a3}",
+    ] {
+        assert_eq!(
+            tEXtChunk::new(keyword, "text"),
+            Err(EncodeError::InvalidKeyword(keyword.to_owned()))
+        );
+    }
+    assert!(tEXtChunk::new(&"k".repeat(79), "text").is_ok());
+    assert_eq!(
+        zTXtChunk::new("Title", "\u{3a3}"),
+        Err(EncodeError::NotLatin1)
+    );
+    assert_eq!(
+        iTXtChunk::new("Title", "not a tag!", "", "", false),
+        Err(EncodeError::InvalidLanguageTag("not a tag!".to_owned()))
+    );
+    assert!(iTXtChunk::new("Title", "en-GB", "", "\u{3a3}", false).is_ok());
+
+    assert_eq!(
+        Chunk::parse(b"tEXt", b" Title\0text"),
+        Err(DecodeError::MalformedChunk {
+            chunk_type: *b"tEXt"
+        })
+    );
+    assert_eq!(
+        Chunk::parse(b"zTXt", b"Title\0\0not zlib"),
+        Err(DecodeError::MalformedChunk {
+            chunk_type: *b"zTXt"
+        })
+    );
+}
+
+/// The PngSuite image `name` with a chunk of type `chunk_type` holding `chunk_data` added after
+/// the header.
+fn with_chunk(name: &str, chunk_type: &[u8; 4], chunk_data: &[u8]) -> Vec<u8> {
+    let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+    let mut chunks = read_chunks(&input).unwrap();
+    chunks.insert(1, Chunk::Unknown(RawChunk::new(chunk_type, chunk_data)));
+    write_chunks(&chunks)
+}
+
+#[test]
+fn test_wrong_length_transparency_is_ignored() {
+    let input = std::fs::read("tests/png-suite/basn2c08.png").unwrap();
+    let expected = PNG::decode(&input).unwrap();
+    let input = with_chunk("basn2c08", b"tRNS", &[0]);
+    let image = PNG::decode(&input).unwrap();
+    assert_eq!(image.pixels, expected.pixels);
+    let native = NativeImage::decode(&input).unwrap();
+    assert_eq!(native.to_pixels().unwrap(), expected.pixels);
+    // Every pixel is opaque, so the re-encoded image has no tRNS chunk at all.
+    let encoded = image.encode().unwrap();
+    assert!(!read_chunks(&encoded)
+        .unwrap()
+        .iter()
+        .any(|chunk| matches!(chunk, Chunk::tRNS(_))));
+}
+
+#[test]
+fn test_invalid_text_doesnt_stop_decoding() {
+    let input = std::fs::read("tests/png-suite/basn0g01.png").unwrap();
+    let expected = PNG::decode(&input).unwrap();
+    for (chunk_type, chunk_data) in [
+        (b"tEXt", &b"Title \0text"[..]),
+        (b"zTXt", b"Title\0\0not zlib"),
+        (b"iTXt", b"Title\0\x02\0\0\0text"),
+    ] {
+        let input = with_chunk("basn0g01", chunk_type, chunk_data);
+        let image = PNG::decode(&input).unwrap();
+        assert_eq!(image.pixels, expected.pixels);
+        assert_eq!(image.text(), vec![]);
+        assert_eq!(
+            image.extra_chunks[0],
+            Chunk::Unknown(RawChunk::new(chunk_type, chunk_data))
+        );
+        // Malformed chunks are left out when the image is encoded again.
+        let encoded = image.encode().unwrap();
+        assert!(read_chunks(&encoded)
+            .unwrap()
+            .iter()
+            .all(|chunk| chunk.chunk_type() != chunk_type));
+    }
+
+    // Going over a limit still fails, and so do malformed critical chunks.
+    let compressed = Chunk::zTXt(zTXtChunk::new("Title", &"a".repeat(2000)).unwrap()).to_bytes();
+    let input = with_chunk("basn0g01", b"zTXt", &compressed[8..compressed.len() - 4]);
+    let limits = DecodeLimits::new().max_text_chunk_size(1000);
+    assert_eq!(
+        PNG::decode_with_limits(&input, &limits).unwrap_err(),
+        DecodeError::LimitExceeded {
+            limit: "max_text_chunk_size"
+        }
+    );
+    assert_eq!(
+        PNG::decode(&with_chunk("basn0g01", b"PLTE", &[0, 0])).unwrap_err(),
+        DecodeError::MalformedChunk {
+            chunk_type: *b"PLTE"
+        }
+    );
+}
+
+#[test]
+fn test_compressed_text_respects_limits() {
+    let mut image = PNG::new(1, 1, vec![Pixel::default()]);
+    let text = "a".repeat(10_000);
+    image
+        .extra_chunks
+        .push(Chunk::zTXt(zTXtChunk::new("Comment", &text).unwrap()));
+    let encoded = image.encode().unwrap();
+    // The compressed chunk is small, but its text isn't.
+    let limits = DecodeLimits::new().max_text_chunk_size(1000);
+    assert_eq!(
+        PNG::decode_with_limits(&encoded, &limits).unwrap_err(),
+        DecodeError::LimitExceeded {
+            limit: "max_text_chunk_size"
+        }
+    );
+    assert_eq!(PNG::decode(&encoded).unwrap().text()[0].text, text);
+}
+
+#[test]
+fn test_png_suite_gamma() {
+    for (name, gamma) in [
+        ("g03n0g16", 0.35),
+        ("g04n2c08", 0.45),
+        ("g05n3p04", 0.55),
+        ("g07n0g16", 0.7),
+        ("g10n2c08", 1.0),
+        ("g25n3p04", 2.5),
+    ] {
+        let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+        let image = PNG::decode(&input).unwrap();
+        assert_eq!(
+            image.color_space(),
+            ColorSpace::Calibrated {
+                gamma: Some(gamma),
+                chromaticities: None
+            },
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn test_png_suite_chromaticities() {
+    let input = std::fs::read("tests/png-suite/ccwn2c08.png").unwrap();
+    let image = PNG::decode(&input).unwrap();
+    let ColorSpace::Calibrated {
+        gamma,
+        chromaticities: Some(chromaticities),
+    } = image.color_space()
+    else {
+        panic!("expected a cHRM chunk, got {:?}", image.color_space());
+    };
+    assert_eq!(gamma, Some(1.0));
+    assert_eq!(chromaticities.white_point(), (0.3127, 0.329));
+    assert_eq!(chromaticities.red(), (0.64, 0.33));
+    assert_eq!(chromaticities.green(), (0.3, 0.6));
+    assert_eq!(chromaticities.blue(), (0.15, 0.06));
+}
+
+#[test]
+fn test_color_space_chunks_round_trip() {
+    let profile = b"not really an ICC profile, but the library doesn't look inside".repeat(10);
+    let chunks = [
+        Chunk::gAMA(gAMAChunk::new(45455)),
+        Chunk::cHRM(cHRMChunk::new(
+            (0.3127, 0.329),
+            (0.64, 0.33),
+            (0.3, 0.6),
+            (0.15, 0.06),
+        )),
+        Chunk::sRGB(sRGBChunk::new(RenderingIntent::Saturation)),
+        Chunk::iCCP(iCCPChunk::new("Display P3", &profile).unwrap()),
+    ];
+    for chunk in chunks {
+        let bytes = chunk.to_bytes();
+        let parsed =
+            Chunk::parse(bytes[4..8].try_into().unwrap(), &bytes[8..bytes.len() - 4]).unwrap();
+        assert_eq!(parsed, chunk);
+    }
+    assert_eq!(
+        iCCPChunk::new("", &profile).unwrap_err(),
+        EncodeError::InvalidKeyword("".to_owned())
+    );
+    // Rendering intents only go up to 3.
+    assert_eq!(
+        Chunk::parse(b"sRGB", &[4]).unwrap_err(),
+        DecodeError::MalformedChunk {
+            chunk_type: *b"sRGB"
+        }
+    );
+}
+
+#[test]
+fn test_color_space_precedence() {
+    let profile = iCCPChunk::new("Profile", b"profile").unwrap();
+    let mut image = PNG::new(1, 1, vec![Pixel::default()]);
+    assert_eq!(image.color_space(), ColorSpace::Unknown);
+    image
+        .extra_chunks
+        .push(Chunk::gAMA(gAMAChunk::new(100_000)));
+    assert_eq!(
+        image.color_space(),
+        ColorSpace::Calibrated {
+            gamma: Some(1.0),
+            chromaticities: None
+        }
+    );
+    image
+        .extra_chunks
+        .push(Chunk::sRGB(sRGBChunk::new(RenderingIntent::Perceptual)));
+    assert_eq!(
+        image.color_space(),
+        ColorSpace::Srgb(RenderingIntent::Perceptual)
+    );
+    image.extra_chunks.push(Chunk::iCCP(profile.clone()));
+    assert_eq!(image.color_space(), ColorSpace::IccProfile(&profile));
+
+    let encoded = image.encode().unwrap();
+    let decoded = PNG::decode(&encoded).unwrap();
+    assert_eq!(decoded.color_space(), ColorSpace::IccProfile(&profile));
+    assert_eq!(
+        PNG::decode_with_limits(&encoded, &DecodeLimits::new().max_icc_profile_size(4))
+            .unwrap_err(),
+        DecodeError::LimitExceeded {
+            limit: "max_icc_profile_size"
+        }
+    );
+}
+
+#[test]
+fn test_png_suite_gamma_correction() {
+    let linear = DecoderOptions::new().gamma_correction(DisplayGamma::Exponent(1.0));
+    // The gamma each image was made for, from its name.
+    let gammas = [
+        ("03", 0.35),
+        ("04", 0.45),
+        ("05", 0.55),
+        ("07", 0.7),
+        ("10", 1.0),
+        ("25", 2.5),
+    ];
+    for format in ["n0g16", "n2c08", "n3p04"] {
+        for (name, gamma) in gammas {
+            let input = std::fs::read(format!("tests/png-suite/g{name}{format}.png")).unwrap();
+            let image = PNG::decode(&input).unwrap();
+            let ColorSpace::Calibrated {
+                gamma: Some(file_gamma),
+                ..
+            } = image.color_space()
+            else {
+                panic!("g{name}{format} has no gamma");
+            };
+            assert!((file_gamma - gamma).abs() < 1e-5, "g{name}{format}");
+            let corrected = PNG::decode_with(&input, &linear).unwrap();
+            for (original, corrected) in image.pixels.iter().zip(&corrected.pixels) {
+                assert_eq!(original.alpha, corrected.alpha);
+                for (sample, corrected) in [
+                    (original.red, corrected.red),
+                    (original.green, corrected.green),
+                    (original.blue, corrected.blue),
+                ] {
+                    let expected = (sample as f64 / 65535.0).powf(1.0 / gamma) * 65535.0;
+                    let difference = (corrected as f64 - expected).abs();
+                    assert!(
+                        difference <= 0.5,
+                        "g{name}{format}: {sample} became {corrected}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_invalid_color_space_chunks_are_ignored() {
+    let input = std::fs::read("tests/png-suite/basn2c08.png").unwrap();
+    let linear = DecoderOptions::new().gamma_correction(DisplayGamma::Exponent(1.0));
+    let expected = PNG::decode_with(&input, &linear).unwrap();
+    let original = PNG::decode(&input).unwrap();
+    for (chunk_type, chunk_data) in [
+        (b"gAMA", &[0, 0, 1][..]),
+        (b"cHRM", &[0; 31]),
+        (b"sRGB", &[9]),
+    ] {
+        let input = with_chunk("basn2c08", chunk_type, chunk_data);
+        let image = PNG::decode(&input).unwrap();
+        assert_eq!(image.color_space(), original.color_space());
+        let image = PNG::decode_with(&input, &linear).unwrap();
+        assert_eq!(image.pixels, expected.pixels);
+    }
+}
+
+#[test]
+fn test_gamma_correction_leaves_alpha_alone() {
+    let pixels = vec![
+        Pixel::new(0, 0, 0, 0),
+        Pixel::new(u16::MAX / 2, 1000, 60000, 1000),
+        Pixel::new(u16::MAX, u16::MAX, u16::MAX, u16::MAX / 2),
+    ];
+    let mut image = PNG::new(1, 3, pixels.clone());
+    image.header.bit_depth = 16;
+    let uncorrected = image.encode().unwrap();
+    let options = DecoderOptions::new().gamma_correction(DisplayGamma::Exponent(2.2));
+    // Without a gAMA or sRGB chunk there's nothing to correct from.
+    assert_eq!(
+        PNG::decode_with(&uncorrected, &options).unwrap().pixels,
+        pixels
+    );
+
+    image
+        .extra_chunks
+        .push(Chunk::gAMA(gAMAChunk::new(100_000)));
+    let encoded = image.encode().unwrap();
+    let corrected = PNG::decode_with(&encoded, &options).unwrap().pixels;
+    assert_eq!(corrected[0], pixels[0]);
+    assert_eq!(corrected[1].red, 47_823);
+    assert_eq!(corrected[1].alpha, 1000);
+    assert_eq!(corrected[2], pixels[2]);
+    // An sRGB display is close to, but not exactly, a gamma 2.2 display.
+    let srgb = DecoderOptions::new().gamma_correction(DisplayGamma::Srgb);
+    let corrected = PNG::decode_with(&encoded, &srgb).unwrap().pixels;
+    assert_eq!(corrected[1].red, 48_191);
+    assert_eq!(corrected[1].alpha, 1000);
+}
+
+/// Builds an ICC profile with the given color space and tags.
+fn icc_profile(color_space: &[u8; 4], tags: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut header = vec![0; 128];
+    header[8] = 4;
+    header[12..16].copy_from_slice(b"mntr");
+    header[16..20].copy_from_slice(color_space);
+    header[20..24].copy_from_slice(b"XYZ ");
+    header[36..40].copy_from_slice(b"acsp");
+    header[64..68].copy_from_slice(&1u32.to_be_bytes());
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = vec![];
+    let data_start = header.len() + 4 + tags.len() * 12;
+    for (signature, tag) in tags {
+        table.extend(*signature);
+        table.extend(((data_start + data.len()) as u32).to_be_bytes());
+        table.extend((tag.len() as u32).to_be_bytes());
+        data.extend(tag);
+    }
+    let mut profile = [header, table, data].concat();
+    let size = (profile.len() as u32).to_be_bytes();
+    profile[..4].copy_from_slice(&size);
+    profile
+}
+
+fn s15_fixed16(values: &[f64]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|v| ((v * 65536.0).round() as i32).to_be_bytes())
+        .collect()
+}
+
+fn xyz_tag(xyz: [f64; 3]) -> Vec<u8> {
+    [b"XYZ \0\0\0\0".to_vec(), s15_fixed16(&xyz)].concat()
+}
+
+/// An RGB profile with the sRGB primaries and the given curve for every channel.
+fn rgb_profile(curve: Vec<u8>) -> Vec<u8> {
+    icc_profile(
+        b"RGB ",
+        &[
+            (b"rXYZ", xyz_tag([0.4360747, 0.2225045, 0.0139322])),
+            (b"gXYZ", xyz_tag([0.3850649, 0.7168786, 0.0971045])),
+            (b"bXYZ", xyz_tag([0.1430804, 0.0606169, 0.7141733])),
+            (b"rTRC", curve.clone()),
+            (b"gTRC", curve.clone()),
+            (b"bTRC", curve),
+        ],
+    )
+}
+
+fn srgb_curve() -> Vec<u8> {
+    let parameters = [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045];
+    [b"para\0\0\0\0\0\x03\0\0".to_vec(), s15_fixed16(&parameters)].concat()
+}
+
+fn linear_curve() -> Vec<u8> {
+    b"curv\0\0\0\0\0\0\0\0".to_vec()
+}
+
+#[test]
+fn test_icc_profiles_convert_to_srgb() {
+    let pixels = [
+        Pixel::new(0, 0, 0, u16::MAX),
+        Pixel::new(32767, 32767, 32767, 1000),
+        Pixel::new(65535, 20000, 3000, 0),
+        Pixel::new(65535, 65535, 65535, u16::MAX),
+    ];
+    let close = |a: u16, b: u16, tolerance: u16| a.abs_diff(b) <= tolerance;
+
+    // Converting from sRGB to sRGB only loses a little precision to the fixed point matrix.
+    let mut converted = pixels;
+    IccProfile::parse(&rgb_profile(srgb_curve()))
+        .unwrap()
+        .convert_to_srgb(&mut converted);
+    for (before, after) in pixels.iter().zip(&converted) {
+        assert!(close(before.red, after.red, 16), "{before:?} {after:?}");
+        assert!(close(before.green, after.green, 16), "{before:?} {after:?}");
+        assert!(close(before.blue, after.blue, 16), "{before:?} {after:?}");
+        assert_eq!(before.alpha, after.alpha);
+    }
+
+    // Linear light at half intensity is about 73.5% in sRGB.
+    let mut converted = pixels;
+    IccProfile::parse(&rgb_profile(linear_curve()))
+        .unwrap()
+        .convert_to_srgb(&mut converted);
+    assert!(close(converted[1].red, 48191, 16), "{:?}", converted[1]);
+    assert!(close(converted[1].blue, 48191, 16), "{:?}", converted[1]);
+    assert_eq!(converted[1].alpha, 1000);
+
+    // A gamma of 1.0 stored as a u8Fixed8 number.
+    let grey = icc_profile(
+        b"GRAY",
+        &[(b"kTRC", b"curv\0\0\0\0\0\0\0\x01\x01\0".to_vec())],
+    );
+    let mut converted = pixels;
+    IccProfile::parse(&grey)
+        .unwrap()
+        .convert_to_srgb(&mut converted);
+    assert_eq!(converted[1], Pixel::new(48191, 48191, 48191, 1000));
+    assert_eq!(converted[3], pixels[3]);
+}
+
+#[test]
+fn test_unsupported_icc_profiles_are_rejected() {
+    let lut = icc_profile(b"RGB ", &[(b"A2B0", b"mAB \0\0\0\0".to_vec())]);
+    assert_eq!(
+        IccProfile::parse(&lut).unwrap_err(),
+        DecodeError::UnsupportedIccProfile {
+            reason: "LUT-based profiles"
+        }
+    );
+    let cmyk = icc_profile(b"CMYK", &[]);
+    assert_eq!(
+        IccProfile::parse(&cmyk).unwrap_err(),
+        DecodeError::UnsupportedIccProfile {
+            reason: "profiles that aren't RGB or greyscale"
+        }
+    );
+    let profile = rgb_profile(linear_curve());
+    assert_eq!(
+        IccProfile::parse(&profile[..profile.len() - 1]).unwrap_err(),
+        DecodeError::InvalidIccProfile
+    );
+    assert_eq!(
+        IccProfile::parse(&profile[..100]).unwrap_err(),
+        DecodeError::InvalidIccProfile
+    );
+
+    let mut image = PNG::new(1, 1, vec![Pixel::default()]);
+    image
+        .extra_chunks
+        .push(Chunk::iCCP(iCCPChunk::new("LUT", &lut).unwrap()));
+    let encoded = image.encode().unwrap();
+    let options = DecoderOptions::new().convert_to_srgb(true);
+    assert_eq!(
+        PNG::decode_with(&encoded, &options).unwrap_err(),
+        DecodeError::UnsupportedIccProfile {
+            reason: "LUT-based profiles"
+        }
+    );
+    // Without conversion the profile is only kept.
+    PNG::decode(&encoded).unwrap();
+}
+
+#[test]
+fn test_decoding_converts_to_srgb() {
+    let mut image = PNG::new(1, 2, vec![Pixel::new(32767, 32767, 32767, 1000); 2]);
+    image.header.bit_depth = 16;
+    image
+        .extra_chunks
+        .push(Chunk::gAMA(gAMAChunk::new(100_000)));
+    let profile = iCCPChunk::new("Linear", &rgb_profile(linear_curve())).unwrap();
+    image.extra_chunks.push(Chunk::iCCP(profile));
+    let encoded = image.encode().unwrap();
+
+    let options = DecoderOptions::new().convert_to_srgb(true);
+    let decoded = PNG::decode_with(&encoded, &options).unwrap();
+    assert_eq!(
+        decoded.color_space(),
+        ColorSpace::Srgb(RenderingIntent::RelativeColorimetric)
+    );
+    assert_eq!(decoded.extra_chunks.len(), 1);
+    for pixel in decoded.pixels {
+        assert!(pixel.red.abs_diff(48191) <= 16, "{pixel:?}");
+        assert_eq!(pixel.alpha, 1000);
+    }
+
+    // Images without an iCCP chunk are left alone.
+    let plain = PNG::new(1, 1, vec![Pixel::new(0, 257, 514, 771)])
+        .encode()
+        .unwrap();
+    assert_eq!(
+        PNG::decode_with(&plain, &options).unwrap().pixels,
+        vec![Pixel::new(0, 257, 514, 771)]
+    );
+}
+
+/// The compressed image data of `pixels`, as stored in IDAT or fdAT chunks.
+fn compressed_frame(width: u32, height: u32, pixels: &[Pixel]) -> Vec<u8> {
+    let encoded = PNG::new(height, width, pixels).encode().unwrap();
+    read_chunks(&encoded)
+        .unwrap()
+        .iter()
+        .filter_map(|chunk| match chunk {
+            Chunk::IDAT(idat) => Some(idat.data()),
+            _ => None,
+        })
+        .flatten()
+        .copied()
+        .collect()
+}
+
+/// A frame's x and y offset, width, height, ops and pixels.
+type TestFrame = (u32, u32, u32, u32, DisposeOp, BlendOp, Vec<Pixel>);
+
+/// Builds an APNG from `frames`. The first frame is the default image if `default_is_frame` is
+/// set, otherwise a black canvas is.
+fn apng(width: u32, height: u32, frames: &[TestFrame], default_is_frame: bool) -> Vec<u8> {
+    let data = frames
+        .iter()
+        .map(|(_, _, w, h, _, _, pixels)| compressed_frame(*w, *h, pixels))
+        .collect::<Vec<_>>();
+    let default_image = compressed_frame(
+        width,
+        height,
+        &vec![Pixel::new(0, 0, 0, u16::MAX); (width * height) as usize],
+    );
+    let mut chunks = vec![
+        Chunk::IHDR(IHDRChunk::new(
+            width,
+            height,
+            8,
+            ColorType::TruecolorWithAlpha,
+            Interlacing::None,
+        )),
+        Chunk::acTL(acTLChunk::new(frames.len() as u32, 0)),
+    ];
+    if !default_is_frame {
+        chunks.push(Chunk::IDAT(IDATChunk::new(&default_image)));
+    }
+    let mut sequence_number = 0;
+    for (index, ((x, y, w, h, dispose_op, blend_op, _), data)) in
+        frames.iter().zip(&data).enumerate()
+    {
+        chunks.push(Chunk::fcTL(fcTLChunk::new(
+            sequence_number,
+            *w,
+            *h,
+            *x,
+            *y,
+            index as u16 + 1,
+            10,
+            *dispose_op,
+            *blend_op,
+        )));
+        sequence_number += 1;
+        if index == 0 && default_is_frame {
+            chunks.push(Chunk::IDAT(IDATChunk::new(data)));
+        } else {
+            // Split the data to check frames can span several chunks.
+            for part in data.chunks(data.len() / 2 + 1) {
+                chunks.push(Chunk::fdAT(fdATChunk::new(sequence_number, part)));
+                sequence_number += 1;
+            }
+        }
+    }
+    chunks.push(Chunk::IEND);
+    write_chunks(&chunks)
+}
+
+const RED: Pixel = Pixel {
+    red: u16::MAX,
+    green: 0,
+    blue: 0,
+    alpha: u16::MAX,
+};
+const BLUE: Pixel = Pixel {
+    red: 0,
+    green: 0,
+    blue: u16::MAX,
+    alpha: u16::MAX,
+};
+const CLEAR: Pixel = Pixel {
+    red: 0,
+    green: 0,
+    blue: 0,
+    alpha: 0,
+};
+
+#[test]
+fn test_animation_decodes_frames() {
+    let frames = [
+        (0, 0, 2, 2, DisposeOp::None, BlendOp::Source, vec![RED; 4]),
+        (1, 1, 1, 1, DisposeOp::Background, BlendOp::Over, vec![BLUE]),
+        (
+            0,
+            1,
+            2,
+            1,
+            DisposeOp::None,
+            BlendOp::Source,
+            vec![CLEAR, BLUE],
+        ),
+    ];
+    for default_is_frame in [true, false] {
+        let input = apng(2, 2, &frames, default_is_frame);
+        let animation = Animation::decode(&input).unwrap();
+        assert_eq!(animation.num_plays, 0);
+        assert_eq!(animation.default_image.is_none(), default_is_frame);
+        assert_eq!(animation.frames.len(), 3);
+        let frame = &animation.frames[1];
+        assert_eq!(
+            (frame.x_offset, frame.y_offset, frame.width, frame.height),
+            (1, 1, 1, 1)
+        );
+        assert_eq!(frame.delay(), std::time::Duration::from_millis(200));
+        assert_eq!(frame.dispose_op, DisposeOp::Background);
+        assert_eq!(frame.blend_op, BlendOp::Over);
+        assert_eq!(frame.pixels, vec![BLUE]);
+        assert_eq!(
+            animation.canvases().collect::<Vec<_>>(),
+            vec![
+                vec![RED; 4],
+                vec![RED, RED, RED, BLUE],
+                // The blue pixel was cleared, then the last frame replaced the bottom row.
+                vec![RED, RED, CLEAR, BLUE],
+            ]
+        );
+        // The default image is still there for decoders that don't support animation.
+        let image = PNG::decode(&input).unwrap();
+        let expected = if default_is_frame {
+            RED
+        } else {
+            Pixel::new(0, 0, 0, u16::MAX)
+        };
+        assert_eq!(image.pixels, vec![expected; 4]);
+    }
+}
+
+#[test]
+fn test_animation_blending_and_disposal() {
+    // Alpha is stored in 8 bits, so this is as close to half as it gets.
+    let half_blue = Pixel::new(0, 0, u16::MAX, 128 * 257);
+    let frames = [
+        (
+            0,
+            0,
+            2,
+            1,
+            DisposeOp::None,
+            BlendOp::Source,
+            vec![RED, CLEAR],
+        ),
+        (
+            0,
+            0,
+            2,
+            1,
+            DisposeOp::Previous,
+            BlendOp::Over,
+            vec![half_blue; 2],
+        ),
+        (
+            1,
+            0,
+            1,
+            1,
+            DisposeOp::Background,
+            BlendOp::Over,
+            vec![CLEAR],
+        ),
+        (0, 0, 1, 1, DisposeOp::None, BlendOp::Source, vec![BLUE]),
+    ];
+    let input = apng(2, 1, &frames, true);
+    let canvases = Animation::decode(&input)
+        .unwrap()
+        .canvases()
+        .collect::<Vec<_>>();
+    assert_eq!(canvases[0], vec![RED, CLEAR]);
+    // Blue at half alpha over red mixes the colors, and over nothing keeps its alpha.
+    assert_eq!(
+        canvases[1],
+        vec![Pixel::new(32639, 0, 32896, u16::MAX), half_blue]
+    );
+    // The second frame is reverted, and a clear pixel drawn over red leaves it alone.
+    assert_eq!(canvases[2], vec![RED, CLEAR]);
+    assert_eq!(canvases[3], vec![BLUE, CLEAR]);
+
+    // The first frame can't be reverted, so it's cleared.
+    let frames = [
+        (
+            0,
+            0,
+            2,
+            1,
+            DisposeOp::Previous,
+            BlendOp::Source,
+            vec![RED; 2],
+        ),
+        (1, 0, 1, 1, DisposeOp::None, BlendOp::Source, vec![BLUE]),
+    ];
+    let input = apng(2, 1, &frames, true);
+    let canvases = Animation::decode(&input)
+        .unwrap()
+        .canvases()
+        .collect::<Vec<_>>();
+    assert_eq!(canvases, vec![vec![RED; 2], vec![CLEAR, BLUE]]);
+
+    // Frames built with the wrong number of pixels aren't drawn.
+    let mut animation = Animation::decode(&input).unwrap();
+    animation.frames[0].width = 0;
+    animation.frames[1].pixels.push(RED);
+    let canvases = animation.canvases().collect::<Vec<_>>();
+    assert_eq!(canvases, vec![vec![CLEAR; 2]; 2]);
+}
+
+#[test]
+fn test_animation_limits_count_every_frame() {
+    let frames = vec![(0, 0, 2, 2, DisposeOp::None, BlendOp::Source, vec![RED; 4]); 3];
+    // Each 2x2 frame has 4 pixels, and 2 scanlines of a filter byte and 8 bytes of samples.
+    for (default_is_frame, pixels, bytes) in [(true, 12, 54), (false, 16, 72)] {
+        let input = apng(2, 2, &frames, default_is_frame);
+        let decode = |limits: DecodeLimits| {
+            Animation::decode_with(&input, &DecoderOptions::new().limits(limits)).map(|_| ())
+        };
+        assert_eq!(decode(DecodeLimits::new().max_pixels(pixels)), Ok(()));
+        assert_eq!(
+            decode(DecodeLimits::new().max_pixels(pixels - 1)),
+            Err(DecodeError::LimitExceeded {
+                limit: "max_pixels"
+            })
+        );
+        assert_eq!(
+            decode(DecodeLimits::new().max_decompressed_bytes(bytes)),
+            Ok(())
+        );
+        assert_eq!(
+            decode(DecodeLimits::new().max_decompressed_bytes(bytes - 1)),
+            Err(DecodeError::LimitExceeded {
+                limit: "max_decompressed_bytes"
+            })
+        );
+    }
+}
+
+#[test]
+fn test_still_images_decode_as_one_frame() {
+    let input = std::fs::read("tests/png-suite/basn6a16.png").unwrap();
+    let animation = Animation::decode(&input).unwrap();
+    let image = PNG::decode(&input).unwrap();
+    assert_eq!(animation.frames.len(), 1);
+    assert_eq!(animation.frames[0].pixels, image.pixels);
+    assert_eq!(animation.canvases().next().unwrap(), image.pixels);
+    assert_eq!(animation.default_image, None);
+}
+
+#[test]
+fn test_animations_reencode_as_still_images() {
+    let frames = [
+        (0, 0, 2, 2, DisposeOp::None, BlendOp::Source, vec![RED; 4]),
+        (1, 1, 1, 1, DisposeOp::None, BlendOp::Source, vec![BLUE]),
+    ];
+    for default_is_frame in [true, false] {
+        let input = apng(2, 2, &frames, default_is_frame);
+        let image = PNG::decode(&input).unwrap();
+        for encoded in [image.encode().unwrap(), image.encode_optimized().unwrap()] {
+            assert!(read_chunks(&encoded)
+                .unwrap()
+                .iter()
+                .all(|chunk| !matches!(chunk, Chunk::acTL(_) | Chunk::fcTL(_) | Chunk::fdAT(_))));
+            assert_eq!(PNG::decode(&encoded).unwrap().pixels, image.pixels);
+            let animation = Animation::decode(&encoded).unwrap();
+            assert_eq!(animation.frames.len(), 1);
+            assert_eq!(animation.frames[0].pixels, image.pixels);
+        }
+    }
+}
+
+#[test]
+fn test_invalid_animations_are_rejected() {
+    let frames = [
+        (0, 0, 2, 2, DisposeOp::None, BlendOp::Source, vec![RED; 4]),
+        (1, 1, 1, 1, DisposeOp::None, BlendOp::Source, vec![BLUE]),
+    ];
+    let input = apng(2, 2, &frames, true);
+    // IHDR, acTL, fcTL, IDAT, fcTL, fdAT, fdAT, IEND
+    let chunks = read_chunks(&input).unwrap();
+    let decode_modified = |modify: &dyn Fn(&mut Vec<Chunk>)| {
+        let mut chunks = chunks.clone();
+        modify(&mut chunks);
+        Animation::decode(&write_chunks(&chunks)).map(|_| ())
+    };
+    let frame_control = |sequence_number, width, x_offset| {
+        Chunk::fcTL(fcTLChunk::new(
+            sequence_number,
+            width,
+            1,
+            x_offset,
+            0,
+            0,
+            0,
+            DisposeOp::None,
+            BlendOp::Source,
+        ))
+    };
+    assert_eq!(decode_modified(&|_| {}), Ok(()));
+
+    // Swapping the two fdAT chunks puts their sequence numbers out of order.
+    assert_eq!(
+        decode_modified(&|chunks| chunks.swap(5, 6)),
+        Err(DecodeError::InvalidSequenceNumber {
+            expected: 2,
+            actual: 3
+        })
+    );
+    assert_eq!(
+        decode_modified(&|chunks| chunks[4] = frame_control(1, 2, 1)),
+        Err(DecodeError::InvalidAnimation {
+            reason: "frame lies outside the canvas"
+        })
+    );
+    assert_eq!(
+        decode_modified(&|chunks| chunks[2] = frame_control(0, 1, 0)),
+        Err(DecodeError::InvalidAnimation {
+            reason: "default image frame doesn't fill the canvas"
+        })
+    );
+    assert_eq!(
+        decode_modified(&|chunks| chunks[1] = Chunk::acTL(acTLChunk::new(3, 0))),
+        Err(DecodeError::InvalidAnimation {
+            reason: "number of frames doesn't match the acTL chunk"
+        })
+    );
+    assert_eq!(
+        decode_modified(&|chunks| chunks.swap(4, 5)),
+        Err(DecodeError::InvalidSequenceNumber {
+            expected: 1,
+            actual: 2
+        })
+    );
+    // Without the acTL chunk the other animation chunks are ignored.
+    assert_eq!(
+        decode_modified(&|chunks| {
+            chunks.remove(1);
+        }),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_native_decoding_matches_decode() {
+    let mut paths: Vec<_> = std::fs::read_dir("tests/png-suite/")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            name.ends_with(".png") && !name.starts_with('x')
+        })
+        .collect();
+    paths.sort();
+    for path in paths {
+        let input = std::fs::read(&path).unwrap();
+        let image = PNG::decode(&input).unwrap();
+        let native = NativeImage::decode(&input).unwrap();
+        let header = &native.header;
+        let layout_matches = match (header.color_type, header.bit_depth) {
+            (ColorType::Greyscale, 16) => matches!(native.pixels, PixelBuffer::Gray16(_)),
+            (ColorType::Greyscale, _) => matches!(native.pixels, PixelBuffer::Gray8(_)),
+            (ColorType::GreyscaleWithAlpha, 16) => {
+                matches!(native.pixels, PixelBuffer::GrayAlpha16(_))
+            }
+            (ColorType::GreyscaleWithAlpha, _) => {
+                matches!(native.pixels, PixelBuffer::GrayAlpha8(_))
+            }
+            (ColorType::Truecolor, 16) => matches!(native.pixels, PixelBuffer::Rgb16(_)),
+            (ColorType::Truecolor, _) => matches!(native.pixels, PixelBuffer::Rgb8(_)),
+            (ColorType::TruecolorWithAlpha, 16) => matches!(native.pixels, PixelBuffer::Rgba16(_)),
+            (ColorType::TruecolorWithAlpha, _) => matches!(native.pixels, PixelBuffer::Rgba8(_)),
+            (ColorType::IndexedColor, _) => matches!(native.pixels, PixelBuffer::Indexed(_)),
+        };
+        assert!(layout_matches, "{path:?}");
+        assert_eq!(native.pixels.len(), image.pixels.len(), "{path:?}");
+        assert_eq!(native.to_pixels().unwrap(), image.pixels, "{path:?}");
+
+        let converted = NativeImage::from_png(&image).unwrap();
+        assert_eq!(converted.header, image.header, "{path:?}");
+        assert_eq!(converted.to_pixels().unwrap(), image.pixels, "{path:?}");
+        assert_eq!(native.clone().into_png().unwrap().pixels, image.pixels);
+    }
+}
+
+#[test]
+fn test_native_samples_are_unscaled() {
+    let input = std::fs::read("tests/png-suite/basn0g01.png").unwrap();
+    let PixelBuffer::Gray8(samples) = NativeImage::decode(&input).unwrap().pixels else {
+        panic!("1 bit greyscale should decode to Gray8");
+    };
+    assert!(samples.iter().all(|s| *s <= 1));
+    assert!(samples.contains(&0) && samples.contains(&1));
+
+    // Transparency stays in the tRNS chunk until the pixels are expanded.
+    let pixels = [Pixel::new(0, 0, 0, 0), Pixel::new(257, 257, 257, u16::MAX)];
+    let image = NativeImage::from_pixels(2, 1, &pixels, ColorType::Greyscale, 8).unwrap();
+    assert_eq!(image.pixels, PixelBuffer::Gray8(vec![0, 1]));
+    assert_eq!(image.transparency, Some(vec![0, 0]));
+    assert_eq!(image.to_pixels().unwrap(), pixels);
+    let image = NativeImage::from_pixels(2, 1, &pixels, ColorType::IndexedColor, 1).unwrap();
+    assert_eq!(image.pixels, PixelBuffer::Indexed(vec![0, 1]));
+    assert_eq!(image.to_pixels().unwrap(), pixels);
+
+    assert_eq!(
+        NativeImage::from_pixels(2, 1, &pixels, ColorType::Truecolor, 4),
+        Err(EncodeError::InvalidBitDepth {
+            color_type: ColorType::Truecolor,
+            bit_depth: 4
+        })
+    );
+    assert_eq!(
+        NativeImage::from_pixels(2, 2, &pixels, ColorType::Truecolor, 8),
+        Err(EncodeError::NotEnoughPixels {
+            expected: 4,
+            actual: 2
+        })
+    );
+}
+
+#[test]
+fn test_decode_into_matches_decode() {
+    let to_8_bits = |sample: u16| ((sample as u32 * 255 + 32767) / 65535) as u8;
+    let formats = [
+        PixelFormat::Rgba8,
+        PixelFormat::Bgra8,
+        PixelFormat::Rgb8,
+        PixelFormat::Gray8,
+        PixelFormat::Rgba16Be,
+        PixelFormat::Rgba16Le,
+    ];
+    let mut paths: Vec<_> = std::fs::read_dir("tests/png-suite/")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            name.ends_with(".png") && !name.starts_with('x')
+        })
+        .collect();
+    paths.sort();
+    for path in paths {
+        let input = std::fs::read(&path).unwrap();
+        let image = PNG::decode(&input).unwrap();
+        for format in formats {
+            let pixel_len = format.bytes_per_pixel();
+            let row_len = image.header.width as usize * pixel_len;
+            // Padding between rows is left alone.
+            let stride = row_len + 3;
+            let layout = PixelLayout::new(format).stride(stride);
+            let mut buffer = vec![0xAA; layout.buffer_len(image.header.width, image.header.height)];
+            let header = PNG::decode_into(&input, &mut buffer, layout).unwrap();
+            assert_eq!(header, image.header);
+            let rows = buffer.chunks(stride);
+            for (row, pixels) in rows.zip(image.pixels.chunks(image.header.width as usize)) {
+                let expected: Vec<u8> = pixels
+                    .iter()
+                    .flat_map(|p| match format {
+                        PixelFormat::Rgba8 => {
+                            [p.red, p.green, p.blue, p.alpha].map(to_8_bits).to_vec()
+                        }
+                        PixelFormat::Bgra8 => {
+                            [p.blue, p.green, p.red, p.alpha].map(to_8_bits).to_vec()
+                        }
+                        PixelFormat::Rgb8 => [p.red, p.green, p.blue].map(to_8_bits).to_vec(),
+                        PixelFormat::Gray8 => {
+                            let weighted =
+                                2126 * p.red as u32 + 7152 * p.green as u32 + 722 * p.blue as u32;
+                            vec![to_8_bits(((weighted + 5000) / 10000) as u16)]
+                        }
+                        PixelFormat::Rgba16Be => [p.red, p.green, p.blue, p.alpha]
+                            .into_iter()
+                            .flat_map(u16::to_be_bytes)
+                            .collect(),
+                        PixelFormat::Rgba16Le => [p.red, p.green, p.blue, p.alpha]
+                            .into_iter()
+                            .flat_map(u16::to_le_bytes)
+                            .collect(),
+                    })
+                    .collect();
+                assert_eq!(&row[..row_len], expected, "{path:?} {format:?}");
+                assert!(row[row_len..].iter().all(|b| *b == 0xAA), "{path:?}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_decode_into_checks_the_buffer() {
+    let input = std::fs::read("tests/png-suite/basn2c08.png").unwrap();
+    let layout = PixelLayout::new(PixelFormat::Rgb8);
+    assert_eq!(layout.buffer_len(32, 32), 32 * 32 * 3);
+    let mut buffer = vec![0; 32 * 32 * 3 - 1];
+    assert_eq!(
+        PNG::decode_into(&input, &mut buffer, layout),
+        Err(DecodeError::BufferTooSmall {
+            expected: 32 * 32 * 3,
+            actual: 32 * 32 * 3 - 1
+        })
+    );
+    buffer.push(0);
+    assert_eq!(
+        PNG::decode_into(&input, &mut buffer, layout.stride(95)),
+        Err(DecodeError::StrideTooSmall {
+            expected: 96,
+            actual: 95
+        })
+    );
+    assert!(PNG::decode_into(&input, &mut buffer, layout).is_ok());
+    assert!(PNG::decode_into(b"not a png", &mut buffer, layout).is_err());
+    assert_eq!(
+        PNG::decode_into_with_limits(
+            &input,
+            &mut buffer,
+            layout,
+            &DecodeLimits::new().max_width(31)
+        ),
+        Err(DecodeError::LimitExceeded { limit: "max_width" })
+    );
+
+    // A stride so big the buffer length overflows.
+    let tall = PNG::new(3, 1, vec![Pixel::default(); 3]).encode().unwrap();
+    let layout = layout.stride(usize::MAX / 2);
+    assert_eq!(layout.buffer_len(1, 3), usize::MAX);
+    assert_eq!(
+        PNG::decode_into(&tall, &mut buffer, layout),
+        Err(DecodeError::BufferTooSmall {
+            expected: usize::MAX,
+            actual: buffer.len()
+        })
+    );
+}
+
+#[test]
+fn test_png_suite_backgrounds() {
+    let cases = [
+        ("bgai4a08", None),
+        ("bgbn4a08", Some(Pixel::new(0, 0, 0, u16::MAX))),
+        (
+            "bggn4a16",
+            Some(Pixel::new(0xab84, 0xab84, 0xab84, u16::MAX)),
+        ),
+        (
+            "bgwn6a08",
+            Some(Pixel::new(u16::MAX, u16::MAX, u16::MAX, u16::MAX)),
+        ),
+        (
+            "bgyn6a16",
+            Some(Pixel::new(u16::MAX, u16::MAX, 0, u16::MAX)),
+        ),
+        // Indexed images' palette index is replaced by the color it refers to.
+        ("tbbn3p08", Some(Pixel::new(0, 0, 0, u16::MAX))),
+    ];
+    for (name, background) in cases {
+        let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+        let original = PNG::decode(&input).unwrap();
+        assert_eq!(original.background(), background, "{name}");
+
+        let mut image = PNG::decode(&input).unwrap();
+        assert_eq!(image.flatten(None), background.is_some(), "{name}");
+        let Some(background) = background else {
+            assert_eq!(image.pixels, original.pixels, "{name}");
+            continue;
+        };
+        assert!(image.pixels.iter().all(|p| p.alpha == u16::MAX), "{name}");
+        for (flat, pixel) in image.pixels.iter().zip(original.pixels.iter()) {
+            match pixel.alpha {
+                0 => assert_eq!(*flat, background, "{name}"),
+                u16::MAX => assert_eq!(flat, pixel, "{name}"),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[test]
+fn test_flatten_blends_16_bit_alpha() {
+    let pixels = vec![
+        Pixel::new(u16::MAX, 0, 0, 32768),
+        Pixel::new(u16::MAX, 0, 0, 1),
+        Pixel::new(0, 0, 0, 0),
+    ];
+    let mut image = PNG::new(1, 3, pixels);
+    assert!(!image.flatten(None));
+    assert!(image.flatten(Some(Pixel::new(0, 0, u16::MAX, u16::MAX))));
+    assert_eq!(
+        image.pixels,
+        [
+            Pixel::new(32768, 0, 32767, u16::MAX),
+            Pixel::new(1, 0, 65534, u16::MAX),
+            Pixel::new(0, 0, u16::MAX, u16::MAX),
+        ]
+    );
+}
+
+#[test]
+fn test_background_chunk_round_trips() {
+    for background in [
+        Background::Grey(0x1234),
+        Background::Rgb(1, 2, 3),
+        Background::PaletteIndex(7),
+    ] {
+        let chunk = Chunk::bKGD(bKGDChunk::new(background));
+        let bytes = chunk.to_bytes();
+        assert_eq!(Chunk::parse(b"bKGD", &bytes[8..bytes.len() - 4]), Ok(chunk));
+    }
+    assert_eq!(
+        Chunk::parse(b"bKGD", &[0, 0, 0]),
+        Err(DecodeError::MalformedChunk {
+            chunk_type: *b"bKGD"
+        })
+    );
+    // A background of the wrong length is ignored, and replaced when encoding.
+    let input = with_chunk("basn0g01", b"bKGD", &[0, 0, 0]);
+    let mut image = PNG::decode(&input).unwrap();
+    assert_eq!(image.background(), None);
+    assert!(!image.flatten(None));
+    let encoded = image.encode().unwrap();
+    assert!(read_chunks(&encoded)
+        .unwrap()
+        .iter()
+        .all(|chunk| chunk.chunk_type() != b"bKGD"));
+
+    // The background is rewritten for the format the image is encoded in, after the palette.
+    for name in ["tbbn3p08", "bggn4a16", "bgyn6a16", "tbwn0g16"] {
+        let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+        let image = PNG::decode(&input).unwrap();
+        for encoded in [image.encode().unwrap(), image.encode_optimized().unwrap()] {
+            assert_eq!(
+                PNG::decode(&encoded).unwrap().background(),
+                image.background(),
+                "{name}"
+            );
+            let types: Vec<_> = read_chunks(&encoded)
+                .unwrap()
+                .iter()
+                .map(|chunk| *chunk.chunk_type())
+                .collect();
+            let position = |chunk_type| types.iter().position(|t| t == chunk_type);
+            assert!(position(b"bKGD") < position(b"IDAT"), "{name}");
+            assert!(position(b"PLTE") < position(b"bKGD"), "{name}");
+        }
+    }
+}
+
+#[test]
+fn test_png_suite_significant_bits() {
+    let cases = [
+        ("cs3n2c16", 16, 13),
+        ("cs3n3p08", 8, 3),
+        ("cs5n2c08", 8, 5),
+        ("cs5n3p08", 8, 5),
+        ("cs8n2c08", 8, 8),
+        ("cs8n3p08", 8, 8),
+    ];
+    for (name, depth, bits) in cases {
+        let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+        let decode = |scaling| {
+            let options = DecoderOptions::new().sample_scaling(scaling);
+            PNG::decode_with(&input, &options).unwrap().pixels
+        };
+        let original = PNG::decode(&input).unwrap().pixels;
+        let reduced = decode(SampleScaling::Reduced);
+        let rescaled = decode(SampleScaling::Rescaled);
+        let max = (1u32 << bits) - 1;
+        for ((pixel, reduced), rescaled) in original.iter().zip(&reduced).zip(&rescaled) {
+            for (sample, reduced, rescaled) in [
+                (pixel.red, reduced.red, rescaled.red),
+                (pixel.green, reduced.green, rescaled.green),
+                (pixel.blue, reduced.blue, rescaled.blue),
+            ] {
+                let stored = if depth == 16 { sample } else { sample >> 8 };
+                assert_eq!(reduced, stored >> (depth - bits), "{name}");
+                let expected = (reduced as u32 * u16::MAX as u32 + max / 2) / max;
+                assert_eq!(rescaled as u32, expected, "{name}");
+            }
+            // None of these have an alpha channel.
+            assert_eq!(reduced.alpha, pixel.alpha, "{name}");
+            assert_eq!(rescaled.alpha, pixel.alpha, "{name}");
+        }
+    }
+}
+
+#[test]
+fn test_significant_bits_chunk_round_trips() {
+    for significant_bits in [
+        SignificantBits::Grey(3),
+        SignificantBits::GreyAlpha(10, 16),
+        SignificantBits::Rgb(5, 6, 5),
+        SignificantBits::Rgba(8, 8, 8, 1),
+    ] {
+        let chunk = Chunk::sBIT(sBITChunk::new(significant_bits));
+        let bytes = chunk.to_bytes();
+        assert_eq!(Chunk::parse(b"sBIT", &bytes[8..bytes.len() - 4]), Ok(chunk));
+    }
+    let input = std::fs::read("tests/png-suite/basn2c08.png").unwrap();
+    let expected = PNG::decode(&input).unwrap();
+    let reduced = DecoderOptions::new().sample_scaling(SampleScaling::Reduced);
+    for data in [&[][..], &[8, 0, 8], &[8, 8, 8, 8, 8]] {
+        assert_eq!(
+            Chunk::parse(b"sBIT", data),
+            Err(DecodeError::MalformedChunk {
+                chunk_type: *b"sBIT"
+            })
+        );
+        // Images with a malformed sBIT chunk are decoded as if every bit is significant.
+        let input = with_chunk("basn2c08", b"sBIT", data);
+        let image = PNG::decode_with(&input, &reduced).unwrap();
+        assert!(image
+            .pixels
+            .iter()
+            .zip(&expected.pixels)
+            .all(|(a, b)| a.red == b.red >> 8));
+    }
+
+    // 10 bit samples stored at 16 bits keep their precision through the sBIT chunk.
+    let pixels: Vec<_> = (0..64u16)
+        .map(|i| Pixel::new(i << 10 | i << 4, 1023 << 6, i << 6, u16::MAX))
+        .collect();
+    let mut image = PNG::new(8, 8, pixels.clone());
+    image.header = IHDRChunk::new(8, 8, 16, ColorType::Truecolor, Interlacing::None);
+    let options = EncoderOptions::new().significant_bits(10);
+    let encoded = image.encode_with(&options).unwrap();
+    let chunks = read_chunks(&encoded).unwrap();
+    assert!(
+        chunks.contains(&Chunk::sBIT(sBITChunk::new(SignificantBits::Rgb(
+            10, 10, 10
+        ))))
+    );
+    let options = DecoderOptions::new().sample_scaling(SampleScaling::Reduced);
+    let decoded = PNG::decode_with(&encoded, &options).unwrap();
+    for (decoded, pixel) in decoded.pixels.iter().zip(&pixels) {
+        assert_eq!(decoded.red, pixel.red >> 6);
+        assert_eq!(decoded.green, 1023);
+        assert_eq!(decoded.blue, pixel.blue >> 6);
+    }
+
+    // The chunk is converted to the format the image is encoded in, ahead of the palette.
+    for name in ["cs3n2c16", "cs5n2c08", "cs5n3p08"] {
+        let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+        let image = PNG::decode(&input).unwrap();
+        for encoded in [image.encode().unwrap(), image.encode_optimized().unwrap()] {
+            let chunks = read_chunks(&encoded).unwrap();
+            let header = PNG::read_info(&encoded).unwrap();
+            let Some(Chunk::sBIT(sbit)) = chunks.iter().find(|c| c.chunk_type() == b"sBIT") else {
+                panic!("{name} lost its sBIT chunk");
+            };
+            let depth = match header.color_type {
+                ColorType::IndexedColor => 8,
+                _ => header.bit_depth,
+            };
+            let bits = match sbit.significant_bits() {
+                SignificantBits::Grey(bits) if header.color_type == ColorType::Greyscale => bits,
+                SignificantBits::Rgb(red, ..)
+                    if matches!(
+                        header.color_type,
+                        ColorType::Truecolor | ColorType::IndexedColor
+                    ) =>
+                {
+                    red
+                }
+                bits => panic!("{name} has {bits:?} for {:?}", header.color_type),
+            };
+            assert!(bits <= depth, "{name}");
+            let types: Vec<_> = chunks.iter().map(|chunk| *chunk.chunk_type()).collect();
+            let position = |chunk_type| types.iter().position(|t| t == chunk_type);
+            assert!(position(b"sBIT") < position(b"IDAT"), "{name}");
+            if let Some(plte) = position(b"PLTE") {
+                assert!(position(b"sBIT") < Some(plte), "{name}");
+            }
+        }
+    }
+}
+
+#[test]
+fn test_png_suite_exif() {
+    let input = std::fs::read("tests/png-suite/exif2c08.png").unwrap();
+    let image = PNG::decode(&input).unwrap();
+    let chunk = image.exif().unwrap();
+    assert_eq!(chunk.data().len(), 978);
+    assert!(chunk.data().starts_with(b"MM\0*"));
+    assert_eq!(
+        Exif::parse(chunk.data()),
+        Ok(Exif {
+            orientation: Some(Orientation::Normal),
+            x_resolution: Some((72, 1)),
+            y_resolution: Some((72, 1)),
+            resolution_unit: Some(2),
+            ..Exif::default()
+        })
+    );
+    let encoded = image.encode().unwrap();
+    assert_eq!(PNG::decode(&encoded).unwrap().exif(), Some(chunk));
+
+    let mut stripped = PNG::decode(&input).unwrap();
+    stripped.set_exif(None);
+    assert_eq!(stripped.exif(), None);
+    let encoded = stripped.encode().unwrap();
+    assert!(read_chunks(&encoded)
+        .unwrap()
+        .iter()
+        .all(|chunk| chunk.chunk_type() != b"eXIf"));
+}
+
+#[test]
+fn test_exif_round_trips() {
+    let exif = Exif {
+        orientation: Some(Orientation::Rotate270),
+        date_time: Some("2024:02:29 13:45:00".to_owned()),
+        make: Some("Camera Co".to_owned()),
+        model: Some("X1".to_owned()),
+        software: Some("simple-png".to_owned()),
+        x_resolution: Some((300, 1)),
+        y_resolution: Some((600, 2)),
+        resolution_unit: Some(2),
+    };
+    let data = exif.to_bytes();
+    assert_eq!(Exif::parse(&data), Ok(exif.clone()));
+    assert_eq!(
+        Exif::parse(&Exif::default().to_bytes()),
+        Ok(Exif::default())
+    );
+
+    let chunk = eXIfChunk::new(data.clone()).unwrap();
+    let mut image = PNG::new(1, 1, vec![Pixel::new(0, 0, 0, u16::MAX)]);
+    image.set_exif(Some(chunk.clone()));
+    let model_only = Exif {
+        model: Some("X2".to_owned()),
+        ..Exif::default()
+    };
+    image.set_exif(Some(eXIfChunk::new(model_only.to_bytes()).unwrap()));
+    assert_eq!(image.extra_chunks.len(), 1);
+    let encoded = image.encode().unwrap();
+    let decoded = PNG::decode(&encoded).unwrap();
+    assert_eq!(Exif::parse(decoded.exif().unwrap().data()), Ok(model_only));
+
+    let bytes = Chunk::eXIf(chunk.clone()).to_bytes();
+    assert_eq!(
+        Chunk::parse(b"eXIf", &bytes[8..bytes.len() - 4]),
+        Ok(Chunk::eXIf(chunk))
+    );
+    assert_eq!(
+        Chunk::parse(b"eXIf", b"JFIF"),
+        Err(DecodeError::MalformedChunk {
+            chunk_type: *b"eXIf"
+        })
+    );
+    let input = with_chunk("basn0g01", b"eXIf", b"JFIF");
+    let mut image = PNG::decode(&input).unwrap();
+    assert_eq!(image.exif(), None);
+    assert!(!image.apply_orientation());
+    assert_eq!(eXIfChunk::new(vec![]), Err(EncodeError::InvalidExif));
+    assert_eq!(Exif::parse(&data[..20]), Err(DecodeError::InvalidExif));
+}
+
+#[test]
+fn test_apply_orientation() {
+    // a b c
+    // d e f
+    let [a, b, c, d, e, f] = [0, 1, 2, 3, 4, 5].map(|i| Pixel::new(i, 0, 0, u16::MAX));
+    let cases = [
+        (Orientation::Normal, (3, 2), [a, b, c, d, e, f]),
+        (Orientation::FlipHorizontal, (3, 2), [c, b, a, f, e, d]),
+        (Orientation::Rotate180, (3, 2), [f, e, d, c, b, a]),
+        (Orientation::FlipVertical, (3, 2), [d, e, f, a, b, c]),
+        (Orientation::Transpose, (2, 3), [a, d, b, e, c, f]),
+        (Orientation::Rotate90, (2, 3), [d, a, e, b, f, c]),
+        (Orientation::Transverse, (2, 3), [f, c, e, b, d, a]),
+        (Orientation::Rotate270, (2, 3), [c, f, b, e, a, d]),
+    ];
+    for (orientation, (width, height), expected) in cases {
+        let mut image = PNG::new(2, 3, vec![a, b, c, d, e, f]);
+        image
+            .extra_chunks
+            .push(Chunk::pHYs(pHYsChunk::new(1, 2, Unit::Meter)));
+        let exif = Exif {
+            orientation: Some(orientation),
+            ..Exif::default()
+        };
+        image.set_exif(Some(eXIfChunk::new(exif.to_bytes()).unwrap()));
+        let turned = orientation != Orientation::Normal;
+        assert_eq!(image.apply_orientation(), turned, "{orientation:?}");
+        assert_eq!(image.pixels, expected, "{orientation:?}");
+        assert_eq!(
+            (image.header.width, image.header.height),
+            (width, height),
+            "{orientation:?}"
+        );
+        let Chunk::pHYs(phys) = &image.extra_chunks[0] else {
+            panic!("pHYs chunk moved");
+        };
+        let swapped = orientation.swaps_dimensions();
+        assert_eq!(phys.x_axis_ppu(), if swapped { 2 } else { 1 });
+        let exif = Exif::parse(image.exif().unwrap().data()).unwrap();
+        assert_eq!(exif.orientation, Some(Orientation::Normal));
+        assert!(!image.apply_orientation());
+    }
+
+    // Little endian data, rotated 90 degrees clockwise.
+    let mut data = b"II*\0\x08\0\0\0\x01\0".to_vec();
+    data.extend(b"\x12\x01\x03\0\x01\0\0\0\x06\0\0\0");
+    data.extend(b"\0\0\0\0");
+    let mut image = PNG::new(2, 3, vec![a, b, c, d, e, f]);
+    image.set_exif(Some(eXIfChunk::new(data).unwrap()));
+    assert!(image.apply_orientation());
+    assert_eq!(image.pixels, [d, a, e, b, f, c]);
+    let data = image.exif().unwrap().data();
+    assert_eq!(data[18..20], [1, 0]);
+}
+
+#[test]
+fn test_png_suite_modification_times() {
+    let cases = [
+        ("cm0n0g04", (2000, 1, 1, 12, 34, 56), 946730096),
+        ("cm7n0g04", (1970, 1, 1, 0, 0, 0), 0),
+        ("cm9n0g04", (1999, 12, 31, 23, 59, 59), 946684799),
+    ];
+    for (name, (year, month, day, hour, minute, second), timestamp) in cases {
+        let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+        let image = PNG::decode(&input).unwrap();
+        let time = image.modification_time().unwrap();
+        assert_eq!(
+            *time,
+            tIMEChunk::new(year, month, day, hour, minute, second).unwrap()
+        );
+        assert_eq!(time.date_time().unwrap().unix_timestamp(), timestamp);
+        let encoded = image.encode().unwrap();
+        assert_eq!(
+            PNG::decode(&encoded).unwrap().modification_time(),
+            Some(time)
+        );
+    }
+}
+
+#[test]
+fn test_time_chunk_validation() {
+    assert!(tIMEChunk::new(2024, 2, 29, 23, 59, 60).is_ok());
+    assert!(tIMEChunk::new(2000, 2, 29, 0, 0, 0).is_ok());
+    for (year, month, day, hour, minute, second) in [
+        (2023, 2, 29, 0, 0, 0),
+        (1900, 2, 29, 0, 0, 0),
+        (2024, 4, 31, 0, 0, 0),
+        (2024, 0, 1, 0, 0, 0),
+        (2024, 13, 1, 0, 0, 0),
+        (2024, 1, 0, 0, 0, 0),
+        (2024, 1, 1, 24, 0, 0),
+        (2024, 1, 1, 0, 60, 0),
+        (2024, 1, 1, 0, 0, 61),
+    ] {
+        assert_eq!(
+            tIMEChunk::new(year, month, day, hour, minute, second),
+            Err(EncodeError::InvalidTime)
+        );
+    }
+
+    let time = tIMEChunk::new(2024, 2, 29, 23, 59, 60).unwrap();
+    let bytes = Chunk::tIME(time).to_bytes();
+    assert_eq!(
+        Chunk::parse(b"tIME", &bytes[8..bytes.len() - 4]),
+        Ok(Chunk::tIME(time))
+    );
+    for data in [&[0x07, 0xe8, 2, 30, 0, 0, 0][..], &[0x07, 0xe8, 2, 1, 0, 0]] {
+        assert_eq!(
+            Chunk::parse(b"tIME", data),
+            Err(DecodeError::MalformedChunk {
+                chunk_type: *b"tIME"
+            })
+        );
+        // An invalid time doesn't stop the image from being decoded.
+        let input = with_chunk("basn0g01", b"tIME", data);
+        let image = PNG::decode(&input).unwrap();
+        assert_eq!(image.modification_time(), None);
+        assert_eq!(image.pixels.len(), 32 * 32);
+        let options = EncoderOptions::new().time_stamp(TimeStamp::Fixed(time));
+        let encoded = image.encode_with(&options).unwrap();
+        let chunks = read_chunks(&encoded).unwrap();
+        let times: Vec<_> = chunks
+            .iter()
+            .filter(|c| c.chunk_type() == b"tIME")
+            .collect();
+        assert_eq!(times, [&Chunk::tIME(time)]);
+    }
+
+    // Leap seconds can't be represented, and other offsets are converted to UTC.
+    let date_time = time.date_time().unwrap();
+    assert_eq!(date_time.second(), 59);
+    let offset = time::UtcOffset::from_hms(2, 0, 0).unwrap();
+    let converted = tIMEChunk::from_date_time(date_time.to_offset(offset)).unwrap();
+    assert_eq!(converted, tIMEChunk::new(2024, 2, 29, 23, 59, 59).unwrap());
+    assert_eq!(converted.date_time(), Some(date_time));
+}
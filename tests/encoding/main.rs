@@ -0,0 +1,733 @@
+use simple_png::{
+    animation::{Animation, Frame},
+    chunks::{read_chunks, tIMEChunk, BlendOp, Chunk, DisposeOp},
+    depth::reduce_depth,
+    ColorType, CompressionStrategy, DecodeError, Dither, EncodeError, EncoderOptions, Filter,
+    FilterStrategy, IndexedImage, Interlacing, Pixel, StreamingEncoder, TimeStamp, PNG,
+};
+
+fn png_suite_images() -> impl Iterator<Item = (String, Vec<u8>)> {
+    let mut paths: Vec<_> = std::fs::read_dir("tests/png-suite/")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_str().unwrap();
+            name.ends_with(".png") && !name.starts_with('x')
+        })
+        .collect();
+    paths.sort();
+    paths.into_iter().map(|path| {
+        let name = path.file_stem().unwrap().to_str().unwrap().to_owned();
+        (name, std::fs::read(path).unwrap())
+    })
+}
+
+#[test]
+fn png_suite_round_trips_in_original_format() {
+    for (name, input) in png_suite_images() {
+        let original = PNG::decode(&input).unwrap();
+        let encoded = original.encode().unwrap();
+        let decoded = PNG::decode(&encoded).unwrap();
+        assert_eq!(
+            decoded.header.color_type, original.header.color_type,
+            "{name}"
+        );
+        assert_eq!(
+            decoded.header.bit_depth, original.header.bit_depth,
+            "{name}"
+        );
+        assert_eq!(
+            decoded.header.interlace_method, original.header.interlace_method,
+            "{name}"
+        );
+        assert_eq!(decoded.pixels, original.pixels, "{name}");
+    }
+}
+
+#[test]
+fn every_color_type_and_bit_depth_round_trips() {
+    let formats = [
+        (ColorType::Greyscale, &[1u8, 2, 4, 8, 16][..]),
+        (ColorType::Truecolor, &[8, 16]),
+        (ColorType::IndexedColor, &[1, 2, 4, 8]),
+        (ColorType::GreyscaleWithAlpha, &[8, 16]),
+        (ColorType::TruecolorWithAlpha, &[8, 16]),
+    ];
+    for (color_type, bit_depths) in formats {
+        for &bit_depth in bit_depths {
+            // Two colors that survive every bit depth, in an odd width to exercise padding.
+            let (width, height) = (7, 3);
+            let black = Pixel::new(0, 0, 0, u16::MAX);
+            let white = Pixel::new(u16::MAX, u16::MAX, u16::MAX, u16::MAX);
+            let pixels: Vec<_> = (0..width * height)
+                .map(|i| if i % 3 == 0 { white } else { black })
+                .collect();
+            let mut image = PNG::new(height, width, &pixels);
+            image.header.color_type = color_type;
+            image.header.bit_depth = bit_depth;
+            let encoded = image.encode().unwrap();
+            let decoded = PNG::decode(&encoded).unwrap();
+            assert_eq!(decoded.header.color_type, color_type);
+            assert_eq!(decoded.header.bit_depth, bit_depth);
+            assert_eq!(decoded.pixels, pixels, "{color_type:?} at {bit_depth} bits");
+        }
+    }
+}
+
+#[test]
+fn greyscale_is_smaller_than_truecolor_with_alpha() {
+    let input = std::fs::read("tests/png-suite/basn0g08.png").unwrap();
+    let image = PNG::decode(&input).unwrap();
+    let mut rgba = PNG::new(image.header.height, image.header.width, &image.pixels);
+    rgba.header.bit_depth = 16;
+    assert!(image.encode().unwrap().len() < rgba.encode().unwrap().len());
+}
+
+#[test]
+fn transparent_color_is_stored_in_trns() {
+    let transparent = Pixel::new(0, 0, 0, 0);
+    let opaque = Pixel::new(u16::MAX, 0, 0, u16::MAX);
+    let pixels = vec![transparent, opaque, opaque, transparent];
+    let mut image = PNG::new(2, 2, &pixels);
+    image.header.color_type = ColorType::Truecolor;
+    let encoded = image.encode().unwrap();
+    let decoded = PNG::decode(&encoded).unwrap();
+    assert_eq!(decoded.pixels, pixels);
+}
+
+#[test]
+fn invalid_formats_are_rejected() {
+    let pixels = vec![Pixel::default(); 4];
+    let mut image = PNG::new(2, 2, &pixels);
+    image.header.color_type = ColorType::Truecolor;
+    image.header.bit_depth = 4;
+    assert_eq!(
+        image.encode(),
+        Err(EncodeError::InvalidBitDepth {
+            color_type: ColorType::Truecolor,
+            bit_depth: 4
+        })
+    );
+
+    let image = PNG::new(3, 2, &pixels);
+    assert_eq!(
+        image.encode(),
+        Err(EncodeError::NotEnoughPixels {
+            expected: 6,
+            actual: 4
+        })
+    );
+}
+
+#[test]
+fn too_many_colors_for_palette_is_an_error() {
+    let pixels: Vec<_> = (0..3)
+        .map(|i| Pixel::new(i * 300, 0, 0, u16::MAX))
+        .collect();
+    let mut image = PNG::new(1, 3, &pixels);
+    image.header.color_type = ColorType::IndexedColor;
+    image.header.bit_depth = 1;
+    assert_eq!(image.encode(), Err(EncodeError::TooManyColors { max: 2 }));
+}
+
+#[test]
+fn png_suite_round_trips_when_optimized() {
+    for (name, input) in png_suite_images() {
+        let original = PNG::decode(&input).unwrap();
+        let encoded = original.encode_optimized().unwrap();
+        let decoded = PNG::decode(&encoded).unwrap();
+        assert_eq!(decoded.pixels, original.pixels, "{name}");
+    }
+}
+
+#[test]
+fn optimized_encoding_picks_smallest_format() {
+    let cases = [
+        ("basn0g01", ColorType::Greyscale, 1),
+        ("basn0g16", ColorType::Greyscale, 16),
+        ("basn2c16", ColorType::Truecolor, 16),
+        ("basn4a08", ColorType::GreyscaleWithAlpha, 8),
+        ("basn3p02", ColorType::IndexedColor, 2),
+        ("tbbn0g04", ColorType::Greyscale, 4),
+    ];
+    for (name, color_type, bit_depth) in cases {
+        let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+        let original = PNG::decode(&input).unwrap();
+        // Start from the 16 bit RGBA bloat the optimizer is meant to undo.
+        let mut bloated = PNG::new(
+            original.header.height,
+            original.header.width,
+            original.pixels,
+        );
+        bloated.header.bit_depth = 16;
+        let encoded = bloated.encode_optimized().unwrap();
+        let decoded = PNG::decode(&encoded).unwrap();
+        assert_eq!(decoded.header.color_type, color_type, "{name}");
+        assert_eq!(decoded.header.bit_depth, bit_depth, "{name}");
+        assert!(encoded.len() < bloated.encode().unwrap().len(), "{name}");
+    }
+}
+
+#[test]
+fn every_compression_setting_round_trips() {
+    let input = std::fs::read("tests/png-suite/basn2c08.png").unwrap();
+    let original = PNG::decode(&input).unwrap();
+    let strategies = [
+        CompressionStrategy::Default,
+        CompressionStrategy::Filtered,
+        CompressionStrategy::Rle,
+        CompressionStrategy::HuffmanOnly,
+    ];
+    for level in 0..=10 {
+        for strategy in strategies {
+            let options = EncoderOptions::new()
+                .compression_level(level)
+                .compression_strategy(strategy);
+            let encoded = original.encode_with(&options).unwrap();
+            let decoded = PNG::decode(&encoded).unwrap();
+            assert_eq!(decoded.pixels, original.pixels, "{level} {strategy:?}");
+        }
+    }
+    let stored = original
+        .encode_with(&EncoderOptions::new().compression_level(0))
+        .unwrap();
+    let compressed = original
+        .encode_with(&EncoderOptions::new().compression_level(9))
+        .unwrap();
+    assert!(compressed.len() < stored.len());
+}
+
+#[test]
+fn every_filter_strategy_round_trips() {
+    let strategies = [
+        FilterStrategy::Fixed(Filter::None),
+        FilterStrategy::Fixed(Filter::Sub),
+        FilterStrategy::Fixed(Filter::Up),
+        FilterStrategy::Fixed(Filter::Average),
+        FilterStrategy::Fixed(Filter::Paeth),
+        FilterStrategy::MinimumSum,
+        FilterStrategy::Entropy,
+        FilterStrategy::BruteForce,
+    ];
+    for name in ["basn0g01", "basn2c16", "basn3p04", "basn6a08"] {
+        let input = std::fs::read(format!("tests/png-suite/{name}.png")).unwrap();
+        let original = PNG::decode(&input).unwrap();
+        for strategy in strategies {
+            let options = EncoderOptions::new().filter_strategy(strategy);
+            let encoded = original.encode_with(&options).unwrap();
+            let decoded = PNG::decode(&encoded).unwrap();
+            assert_eq!(decoded.pixels, original.pixels, "{name} {strategy:?}");
+        }
+    }
+}
+
+#[test]
+fn image_data_is_split_into_idat_chunks() {
+    let input = std::fs::read("tests/png-suite/basn6a16.png").unwrap();
+    let original = PNG::decode(&input).unwrap();
+    let options = EncoderOptions::new()
+        .compression_level(0)
+        .max_idat_size(1000);
+    let encoded = original.encode_with(&options).unwrap();
+    let idat_count = encoded.windows(4).filter(|w| w == b"IDAT").count();
+    // 32x32 pixels at 8 bytes each don't fit in fewer than 9 chunks of 1000 bytes.
+    assert!(idat_count >= 9, "{idat_count}");
+    assert_eq!(PNG::decode(&encoded).unwrap().pixels, original.pixels);
+}
+
+#[test]
+fn interlaced_encoding_round_trips_for_all_small_sizes() {
+    let formats = [
+        (ColorType::IndexedColor, 1),
+        (ColorType::IndexedColor, 4),
+        (ColorType::Greyscale, 2),
+        (ColorType::TruecolorWithAlpha, 16),
+    ];
+    for width in 1..=17 {
+        for height in 1..=17 {
+            let pixels: Vec<_> = (0..width * height)
+                .map(|i| {
+                    let v = if (i * 7 + i / 3) % 2 == 0 {
+                        0
+                    } else {
+                        u16::MAX
+                    };
+                    Pixel::new(v, v, v, u16::MAX)
+                })
+                .collect();
+            for (color_type, bit_depth) in formats {
+                let mut image = PNG::new(height, width, &pixels);
+                image.header.color_type = color_type;
+                image.header.bit_depth = bit_depth;
+                let options = EncoderOptions::new().interlacing(Interlacing::Adam7);
+                let encoded = image.encode_with(&options).unwrap();
+                let decoded = PNG::decode(&encoded).unwrap();
+                assert_eq!(decoded.header.interlace_method, Interlacing::Adam7);
+                assert_eq!(
+                    decoded.pixels, pixels,
+                    "{width}x{height} {color_type:?} {bit_depth}"
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn interlaced_png_suite_images_can_be_deinterlaced() {
+    for (name, input) in png_suite_images().filter(|(name, _)| name.contains("i3p")) {
+        let original = PNG::decode(&input).unwrap();
+        assert_eq!(
+            original.header.interlace_method,
+            Interlacing::Adam7,
+            "{name}"
+        );
+        let options = EncoderOptions::new().interlacing(Interlacing::None);
+        let encoded = original.encode_with(&options).unwrap();
+        let decoded = PNG::decode(&encoded).unwrap();
+        assert_eq!(decoded.header.interlace_method, Interlacing::None, "{name}");
+        assert_eq!(decoded.pixels, original.pixels, "{name}");
+    }
+}
+
+#[test]
+fn streaming_encoder_matches_png_suite_images() {
+    for (name, input) in png_suite_images() {
+        let original = PNG::decode(&input).unwrap();
+        let mut header = original.header.clone();
+        header.interlace_method = Interlacing::None;
+        if header.color_type == ColorType::IndexedColor {
+            header.color_type = ColorType::TruecolorWithAlpha;
+            header.bit_depth = 8;
+        }
+        let options = EncoderOptions::new().max_idat_size(100);
+        let mut encoder =
+            StreamingEncoder::with_options(Vec::new(), header.clone(), &options).unwrap();
+        for row in original.pixels.chunks(original.header.width as usize) {
+            encoder.write_row(row).unwrap();
+        }
+        let encoded = encoder.finish().unwrap();
+        let decoded = PNG::decode(&encoded).unwrap();
+        assert_eq!(decoded.header.color_type, header.color_type, "{name}");
+        assert_eq!(decoded.header.bit_depth, header.bit_depth, "{name}");
+        // Color types without alpha can't use tRNS when streaming, so alpha is dropped.
+        let expected: Vec<_> = original
+            .pixels
+            .iter()
+            .map(|p| match header.color_type {
+                ColorType::Greyscale | ColorType::Truecolor => Pixel {
+                    alpha: u16::MAX,
+                    ..*p
+                },
+                _ => *p,
+            })
+            .collect();
+        assert_eq!(decoded.pixels, expected, "{name}");
+    }
+}
+
+#[test]
+fn streaming_encoder_checks_row_count() {
+    let row = vec![Pixel::default(); 3];
+    let header = PNG::new(2, 3, &row).header;
+    let mut encoder = StreamingEncoder::new(Vec::new(), header.clone()).unwrap();
+    encoder.write_row(&row).unwrap();
+    assert_eq!(
+        encoder.finish().unwrap_err(),
+        EncodeError::MissingRows {
+            expected: 2,
+            actual: 1
+        }
+    );
+
+    let mut encoder = StreamingEncoder::new(Vec::new(), header.clone()).unwrap();
+    encoder.write_row(&row).unwrap();
+    encoder.write_row(&row).unwrap();
+    assert_eq!(
+        encoder.write_row(&row),
+        Err(EncodeError::TooManyRows { height: 2 })
+    );
+    assert_eq!(
+        StreamingEncoder::new(Vec::new(), header.clone())
+            .unwrap()
+            .write_row(&row[..2]),
+        Err(EncodeError::NotEnoughPixels {
+            expected: 3,
+            actual: 2
+        })
+    );
+
+    let mut indexed = header.clone();
+    indexed.color_type = ColorType::IndexedColor;
+    assert!(matches!(
+        StreamingEncoder::new(Vec::new(), indexed),
+        Err(EncodeError::UnsupportedColorType(ColorType::IndexedColor))
+    ));
+    let options = EncoderOptions::new().interlacing(Interlacing::Adam7);
+    assert!(matches!(
+        StreamingEncoder::with_options(Vec::new(), header, &options),
+        Err(EncodeError::UnsupportedInterlacing)
+    ));
+}
+
+/// A 4x4 animation of a square moving over a background, using every dispose and blend op.
+fn test_animation() -> Animation<'static> {
+    let background = Pixel::new(0, 0, 0, u16::MAX);
+    let square = Pixel::new(u16::MAX, 0, 0, u16::MAX);
+    let clear = Pixel::new(0, 0, 0, 0);
+    let mut animation = Animation::new(4, 4);
+    animation.num_plays = 3;
+    animation.frames = vec![
+        Frame::new(4, 4, vec![background; 16]),
+        Frame {
+            x_offset: 1,
+            y_offset: 1,
+            delay_num: 1,
+            delay_den: 10,
+            dispose_op: DisposeOp::Previous,
+            ..Frame::new(2, 2, vec![square; 4])
+        },
+        Frame {
+            x_offset: 2,
+            y_offset: 2,
+            delay_num: 1,
+            delay_den: 0,
+            dispose_op: DisposeOp::Background,
+            blend_op: BlendOp::Over,
+            ..Frame::new(2, 2, vec![square, clear, clear, square])
+        },
+        Frame {
+            delay_num: 2,
+            delay_den: 10,
+            ..Frame::new(1, 1, vec![square])
+        },
+    ];
+    animation
+}
+
+#[test]
+fn animation_round_trips() {
+    let mut animation = test_animation();
+    let encoded = animation.encode().unwrap();
+    let decoded = Animation::decode(&encoded).unwrap();
+    assert_eq!(decoded, animation);
+
+    // Sequence numbers count up through every fcTL and fdAT chunk. The first frame is stored in
+    // IDAT chunks, so it has no fdAT chunks.
+    let options = EncoderOptions::new().max_idat_size(8);
+    let encoded = animation.encode_with(&options).unwrap();
+    let chunks = read_chunks(&encoded).unwrap();
+    let sequence_numbers = chunks
+        .iter()
+        .filter_map(|chunk| match chunk {
+            Chunk::fcTL(fctl) => Some(fctl.sequence_number()),
+            Chunk::fdAT(fdat) => Some(fdat.sequence_number()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        sequence_numbers,
+        (0..sequence_numbers.len() as u32).collect::<Vec<_>>()
+    );
+    assert!(matches!(chunks[1], Chunk::acTL(_)));
+    assert!(matches!(chunks[2], Chunk::fcTL(_)));
+    assert!(matches!(chunks[3], Chunk::IDAT(_)));
+    assert_eq!(Animation::decode(&encoded).unwrap(), animation);
+
+    // A default image that isn't part of the animation comes before the first fcTL chunk.
+    animation.default_image = Some(vec![Pixel::new(0, 0, u16::MAX, u16::MAX); 16]);
+    let encoded = animation.encode().unwrap();
+    assert_eq!(Animation::decode(&encoded).unwrap(), animation);
+    assert_eq!(
+        PNG::decode(&encoded).unwrap().pixels,
+        animation.default_image.unwrap()
+    );
+}
+
+#[test]
+fn animation_formats_are_shared_by_every_frame() {
+    let mut animation = test_animation();
+    let encoded = animation
+        .encode_with(&EncoderOptions::new().optimize(true))
+        .unwrap();
+    let decoded = Animation::decode(&encoded).unwrap();
+    // Three colors, one of them transparent, fit in a 2 bit palette.
+    assert_eq!(decoded.header.color_type, ColorType::IndexedColor);
+    assert_eq!(decoded.header.bit_depth, 2);
+    assert_eq!(decoded.frames, animation.frames);
+
+    // The transparent color is only in the third frame, but the tRNS chunk covers all of them.
+    for frame in animation.frames.iter_mut() {
+        for pixel in frame.pixels.iter_mut().filter(|p| p.alpha == 0) {
+            *pixel = Pixel::new(0, u16::MAX, 0, 0);
+        }
+    }
+    animation.header.color_type = ColorType::Truecolor;
+    let encoded = animation.encode().unwrap();
+    assert_eq!(
+        Animation::decode(&encoded).unwrap().frames,
+        animation.frames
+    );
+}
+
+#[test]
+fn cropped_animation_looks_the_same() {
+    let mut animation = test_animation();
+    let canvases = animation.canvases().collect::<Vec<_>>();
+    animation.crop_frames().unwrap();
+    assert_eq!(animation.canvases().collect::<Vec<_>>(), canvases);
+    let regions = animation
+        .frames
+        .iter()
+        .map(|frame| (frame.x_offset, frame.y_offset, frame.width, frame.height))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        regions,
+        [(0, 0, 4, 4), (1, 1, 2, 2), (1, 1, 3, 3), (0, 0, 4, 4)]
+    );
+    assert_eq!(animation.frames[1].delay_num, 1);
+
+    // A sticker with a small moving part gets smaller.
+    let mut sticker = Animation::new(64, 64);
+    for position in 0..8 {
+        let mut pixels = vec![Pixel::new(0, 0, u16::MAX, u16::MAX); 64 * 64];
+        pixels[position * 65] = Pixel::new(u16::MAX, 0, 0, u16::MAX);
+        sticker.frames.push(Frame::new(64, 64, pixels));
+    }
+    let canvases = sticker.canvases().collect::<Vec<_>>();
+    let size = sticker.encode().unwrap().len();
+    sticker.crop_frames().unwrap();
+    let encoded = sticker.encode().unwrap();
+    assert!(encoded.len() < size, "{} vs {size}", encoded.len());
+    let decoded = Animation::decode(&encoded).unwrap();
+    assert_eq!(decoded.canvases().collect::<Vec<_>>(), canvases);
+
+    // Frames that couldn't be encoded can't be cropped either, and are left alone.
+    let invalid = |reason| Err(EncodeError::InvalidAnimation { reason });
+    let mut empty = Animation::new(0, 2);
+    empty.frames.push(Frame::new(0, 2, vec![]));
+    assert_eq!(
+        empty.crop_frames(),
+        invalid("frame lies outside the canvas")
+    );
+    let mut animation = test_animation();
+    animation.frames[1].x_offset = 3;
+    assert_eq!(
+        animation.crop_frames(),
+        invalid("frame lies outside the canvas")
+    );
+    let mut animation = test_animation();
+    animation.frames[1].pixels.pop();
+    assert!(matches!(
+        animation.crop_frames(),
+        Err(EncodeError::NotEnoughPixels { .. })
+    ));
+    animation.frames[1].pixels.push(Pixel::default());
+    animation.frames[1].pixels.push(Pixel::default());
+    let frames = animation.frames.clone();
+    assert_eq!(
+        animation.crop_frames(),
+        invalid("frame has more pixels than its size")
+    );
+    assert_eq!(animation.frames, frames);
+}
+
+#[test]
+fn invalid_animations_are_rejected() {
+    let invalid = |reason| Err(EncodeError::InvalidAnimation { reason });
+    assert_eq!(
+        Animation::new(2, 2).encode(),
+        invalid("animation has no frames")
+    );
+    let mut animation = Animation::new(2, 2);
+    animation
+        .frames
+        .push(Frame::new(1, 1, vec![Pixel::default()]));
+    assert_eq!(
+        animation.encode(),
+        invalid("first frame doesn't fill the canvas")
+    );
+    animation.frames[0] = Frame::new(2, 2, vec![Pixel::default(); 4]);
+    animation.frames.push(Frame {
+        x_offset: 1,
+        ..Frame::new(2, 1, vec![Pixel::default(); 2])
+    });
+    assert_eq!(animation.encode(), invalid("frame lies outside the canvas"));
+    animation.frames[1] = Frame::new(2, 1, vec![Pixel::default()]);
+    assert_eq!(
+        animation.encode(),
+        Err(EncodeError::NotEnoughPixels {
+            expected: 2,
+            actual: 1
+        })
+    );
+}
+
+#[test]
+fn indexed_images_keep_their_palette() {
+    let mut indexed_count = 0;
+    for (name, input) in png_suite_images() {
+        let original = PNG::decode(&input).unwrap();
+        if original.header.color_type != ColorType::IndexedColor {
+            assert_eq!(
+                IndexedImage::decode(&input),
+                Err(DecodeError::UnsupportedColorType(
+                    original.header.color_type
+                )),
+                "{name}"
+            );
+            continue;
+        }
+        indexed_count += 1;
+        let image = IndexedImage::decode(&input).unwrap();
+        assert_eq!(image.to_pixels().unwrap(), original.pixels, "{name}");
+        let encoded = image.encode().unwrap();
+        assert_eq!(IndexedImage::decode(&encoded).unwrap(), image, "{name}");
+        // The PLTE and tRNS chunks are written back exactly as they were.
+        let palette_chunks = |data| {
+            read_chunks(data)
+                .unwrap()
+                .into_iter()
+                .filter(|chunk| matches!(chunk, Chunk::PLTE(_) | Chunk::tRNS(_)))
+                .map(|chunk| chunk.to_bytes())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(palette_chunks(&encoded), palette_chunks(&input), "{name}");
+    }
+    assert!(indexed_count > 10);
+}
+
+#[test]
+fn indexed_images_can_be_edited() {
+    let input = std::fs::read("tests/png-suite/tbbn3p08.png").unwrap();
+    let mut image = IndexedImage::decode(&input).unwrap();
+    let transparent = image.alpha.iter().position(|a| *a == 0).unwrap() as u8;
+    image.palette[transparent as usize] = [1, 2, 3];
+    image.alpha[transparent as usize] = 128;
+    image.indices[0] = transparent;
+    let encoded = image.encode().unwrap();
+    let decoded = PNG::decode(&encoded).unwrap();
+    assert_eq!(decoded.pixels[0], Pixel::new(257, 514, 771, 128 * 257));
+
+    // A small palette fits in a lower bit depth.
+    let mut image = IndexedImage::new(2, 2, vec![[0, 0, 0], [255, 255, 255]], vec![0, 1, 1, 0]);
+    let encoded = image
+        .encode_with(&EncoderOptions::new().optimize(true))
+        .unwrap();
+    let decoded = IndexedImage::decode(&encoded).unwrap();
+    assert_eq!(decoded.header.bit_depth, 1);
+    assert_eq!(decoded.indices, image.indices);
+    assert_eq!(decoded.palette, image.palette);
+    assert!(decoded.alpha.is_empty());
+
+    image.indices[3] = 2;
+    assert_eq!(image.encode(), Err(EncodeError::PaletteIndexOutOfRange(2)));
+    image.indices.truncate(3);
+    assert_eq!(
+        image.encode(),
+        Err(EncodeError::NotEnoughPixels {
+            expected: 4,
+            actual: 3
+        })
+    );
+    image.indices = vec![0; 4];
+    image.header.bit_depth = 1;
+    image.palette.push([255, 0, 0]);
+    assert_eq!(image.encode(), Err(EncodeError::TooManyColors { max: 2 }));
+}
+
+#[test]
+fn dithering_leaves_exact_samples_alone() {
+    for (name, input) in png_suite_images() {
+        let original = PNG::decode(&input).unwrap();
+        for dither in [Dither::Ordered, Dither::ErrorDiffusion] {
+            let options = EncoderOptions::new().dither(dither);
+            let encoded = original.encode_with(&options).unwrap();
+            let decoded = PNG::decode(&encoded).unwrap();
+            assert_eq!(decoded.pixels, original.pixels, "{name} {dither:?}");
+        }
+    }
+}
+
+#[test]
+fn dithering_keeps_the_average_brightness() {
+    let (width, height) = (32, 32);
+    let grey = 0x6000;
+    let pixels = vec![Pixel::new(grey, grey, grey, u16::MAX); width * height];
+    let mut image = PNG::new(height as u32, width as u32, pixels.clone());
+    image.header.color_type = ColorType::Greyscale;
+    image.header.bit_depth = 1;
+    let brightness = |data: &[u8]| {
+        let pixels = PNG::decode(data).unwrap().pixels;
+        let sum: f64 = pixels.iter().map(|p| p.red as f64).sum();
+        sum / pixels.len() as f64 / u16::MAX as f64
+    };
+    let expected = grey as f64 / u16::MAX as f64;
+    for dither in [Dither::None, Dither::Ordered, Dither::ErrorDiffusion] {
+        let options = EncoderOptions::new().dither(dither);
+        let encoded = image.encode_with(&options).unwrap();
+        let error = (brightness(&encoded) - expected).abs();
+        if dither == Dither::None {
+            assert!(error > 0.3, "{dither:?}");
+        } else {
+            assert!(error < 0.01, "{dither:?} is off by {error}");
+        }
+
+        // Streaming the rows dithers them the same way.
+        let mut encoder =
+            StreamingEncoder::with_options(vec![], image.header.clone(), &options).unwrap();
+        for row in pixels.chunks(width) {
+            encoder.write_row(row).unwrap();
+        }
+        let streamed = encoder.finish().unwrap();
+        assert_eq!(
+            PNG::decode(&streamed).unwrap().pixels,
+            PNG::decode(&encoded).unwrap().pixels,
+            "{dither:?}"
+        );
+    }
+
+    let mut reduced = pixels.clone();
+    reduce_depth(&mut reduced, width as u32, 1, Dither::Ordered);
+    let white = reduced.iter().filter(|p| p.red == u16::MAX).count();
+    assert_eq!(white, width * height * 3 / 8);
+    assert!(reduced.iter().all(|p| p.red == 0 || p.red == u16::MAX));
+}
+
+#[test]
+fn time_stamps_are_written() {
+    let input = std::fs::read("tests/png-suite/cm0n0g04.png").unwrap();
+    let image = PNG::decode(&input).unwrap();
+    let fixed = tIMEChunk::new(2001, 2, 3, 4, 5, 6).unwrap();
+    let options = EncoderOptions::new().time_stamp(TimeStamp::Fixed(fixed));
+    let encoded = image.encode_with(&options).unwrap();
+    // Encoding with a fixed time is reproducible, and replaces the image's own tIME chunk.
+    assert_eq!(image.encode_with(&options).unwrap(), encoded);
+    let times: Vec<_> = read_chunks(&encoded)
+        .unwrap()
+        .into_iter()
+        .filter(|chunk| chunk.chunk_type() == b"tIME")
+        .collect();
+    assert_eq!(times, [Chunk::tIME(fixed)]);
+
+    let before = time::OffsetDateTime::now_utc().unix_timestamp();
+    let options = EncoderOptions::new().time_stamp(TimeStamp::Now);
+    let encoded = PNG::new(1, 1, vec![Pixel::new(0, 0, 0, u16::MAX)])
+        .encode_with(&options)
+        .unwrap();
+    let after = time::OffsetDateTime::now_utc().unix_timestamp();
+    let decoded = PNG::decode(&encoded).unwrap();
+    let stamped = decoded.modification_time().unwrap().date_time().unwrap();
+    assert!((before..=after).contains(&stamped.unix_timestamp()));
+
+    let options = EncoderOptions::new().time_stamp(TimeStamp::Fixed(fixed));
+    let mut encoder =
+        StreamingEncoder::with_options(vec![], image.header.clone(), &options).unwrap();
+    for row in image.pixels.chunks(image.header.width as usize) {
+        encoder.write_row(row).unwrap();
+    }
+    let streamed = encoder.finish().unwrap();
+    assert_eq!(
+        PNG::decode(&streamed).unwrap().modification_time(),
+        Some(&fixed)
+    );
+}